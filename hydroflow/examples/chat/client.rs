@@ -50,7 +50,7 @@ pub(crate) async fn run_client(outbound: UdpSink, inbound: UdpStream, opts: Opts
         // take stdin and send to server as a msg
         // the cross_join serves to buffer msgs until the connection request is acked
         msg_send = cross_join() -> map(|(msg, _)| (msg, server_addr)) -> [1]outbound_chan;
-        lines = source_stdin()
+        source_stdin()
           -> map(|l| Message::ChatMsg {
                     nickname: opts.name.clone(),
                     message: l.unwrap(),
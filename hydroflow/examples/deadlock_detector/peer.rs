@@ -19,6 +19,8 @@ pub(crate) async fn run_detector(opts: Opts, peer_list: Vec<String>) {
     let stdin_lines = LinesStream::new(reader.lines());
 
     let mut df: Hydroflow = hydroflow_syntax! {
+        // Every wait-for edge ever received is kept, so cycles across all of them are found.
+        #![allow(unbounded_state)]
         // fetch peers from file, convert ip:port to a SocketAddr, and tee
         peers = source_iter(peer_list)
             -> map(|s| s.parse::<SocketAddr>().unwrap())
@@ -18,6 +18,8 @@ pub fn main() {
     let (edges_send, edges_recv) = hydroflow::util::unbounded_channel::<(usize, usize)>();
 
     let mut df = hydroflow_syntax! {
+        // The reachable vertices and edges are kept as the graph grows.
+        #![allow(unbounded_state)]
         // inputs: the origin vertex (node 0) and stream of input edges
         origin = source_iter(vec![0]);
         stream_of_edges = source_stream(edges_recv);
@@ -18,6 +18,8 @@ pub fn main() {
     let (pairs_send, pairs_recv) = hydroflow::util::unbounded_channel::<(usize, usize)>();
 
     let mut df = hydroflow_syntax! {
+        // The reachable vertices and edges are kept as the graph grows.
+        #![allow(unbounded_state)]
         origin = source_iter(vec![0]);
         stream_of_edges = source_stream(pairs_recv) -> tee();
         reached_vertices = merge()->tee();
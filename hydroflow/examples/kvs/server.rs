@@ -10,6 +10,8 @@ pub(crate) async fn run_server(outbound: UdpSink, inbound: UdpStream, graph: Opt
     println!("Server live!");
 
     let mut df: Hydroflow = hydroflow_syntax! {
+        // The store keeps every key which is written.
+        #![allow(unbounded_state)]
         // NW channels
         outbound_chan = merge() -> dest_sink_serde(outbound);
         inbound_chan = source_stream_serde(inbound)
@@ -20,6 +20,8 @@ pub fn main() {
     let (edges_send, edges_recv) = hydroflow::util::unbounded_channel::<(usize, usize)>();
 
     let mut df = hydroflow_syntax! {
+        // Every edge is kept, so cliques across all of them are found.
+        #![allow(unbounded_state)]
         edges = source_stream(edges_recv) -> tee();
 
         // set up the two joins
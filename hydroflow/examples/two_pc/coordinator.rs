@@ -13,6 +13,9 @@ pub(crate) async fn run_coordinator(
     graph: Option<GraphType>,
 ) {
     let mut df: Hydroflow = hydroflow_syntax! {
+        // The commit votes of every transaction are kept, this example never garbage collects them.
+        #![allow(unbounded_state)]
+
         // fetch subordinates from file, convert ip:port to a SocketAddr, and tee
         subords = source_iter(subordinates)
             -> map(|s| s.parse::<SocketAddr>().unwrap())
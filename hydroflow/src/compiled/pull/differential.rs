@@ -0,0 +1,305 @@
+//! State for the differential (retraction-aware) operators, `diff_join`, `diff_group_by`,
+//! `diff_unique`, `diff_difference`, and `consolidate`.
+//!
+//! Differential streams carry `(item, diff)` pairs, where `diff` is the change in the
+//! multiplicity of `item`: positive for insertions and negative for retractions. The state
+//! structs here keep the accumulated multiplicities across ticks and emit the corresponding
+//! changes to their outputs.
+
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::hash::Hash;
+
+/// A change to the multiplicity of an item: positive for insertions, negative for retractions.
+pub type Diff = i64;
+
+/// Sums the diffs of equal items, dropping items whose diffs cancel out.
+pub fn consolidate<T>(input: impl IntoIterator<Item = (T, Diff)>) -> HashMap<T, Diff>
+where
+    T: Eq + Hash,
+{
+    let mut counts = HashMap::new();
+    for (item, diff) in input {
+        update_count(&mut counts, item, diff);
+    }
+    counts
+}
+
+/// Adds `diff` to the count of `item`, removing the entry if it becomes zero. Returns the old and
+/// new counts.
+fn update_count<T>(counts: &mut HashMap<T, Diff>, item: T, diff: Diff) -> (Diff, Diff)
+where
+    T: Eq + Hash,
+{
+    match counts.entry(item) {
+        Entry::Occupied(mut occupied) => {
+            let old = *occupied.get();
+            let new = old + diff;
+            if 0 == new {
+                occupied.remove();
+            } else {
+                *occupied.get_mut() = new;
+            }
+            (old, new)
+        }
+        Entry::Vacant(vacant) => {
+            if 0 != diff {
+                vacant.insert(diff);
+            }
+            (0, diff)
+        }
+    }
+}
+
+/// State for `diff_join`, the accumulated multiplicities of each side's key-value pairs.
+#[derive(Debug)]
+pub struct DiffJoinState<K, V1, V2> {
+    lhs: HashMap<K, HashMap<V1, Diff>>,
+    rhs: HashMap<K, HashMap<V2, Diff>>,
+}
+impl<K, V1, V2> Default for DiffJoinState<K, V1, V2> {
+    fn default() -> Self {
+        Self {
+            lhs: HashMap::new(),
+            rhs: HashMap::new(),
+        }
+    }
+}
+impl<K, V1, V2> DiffJoinState<K, V1, V2>
+where
+    K: Clone + Eq + Hash,
+    V1: Clone + Eq + Hash,
+    V2: Clone + Eq + Hash,
+{
+    /// Joins the changes of both sides against the accumulated state, returning the changes to
+    /// the join output.
+    ///
+    /// Uses the delta rule `d(L * R) = dL * R + (L + dL) * dR`, so the left changes are joined
+    /// with the old right state and the right changes with the updated left state.
+    #[allow(clippy::type_complexity)]
+    pub fn join(
+        &mut self,
+        lhs: impl IntoIterator<Item = ((K, V1), Diff)>,
+        rhs: impl IntoIterator<Item = ((K, V2), Diff)>,
+    ) -> Vec<((K, (V1, V2)), Diff)> {
+        let mut output = Vec::new();
+        for ((k, v1), diff) in lhs {
+            if let Some(matches) = self.rhs.get(&k) {
+                output.extend(
+                    matches
+                        .iter()
+                        .map(|(v2, count)| ((k.clone(), (v1.clone(), v2.clone())), diff * count)),
+                );
+            }
+            update_count(self.lhs.entry(k).or_default(), v1, diff);
+        }
+        for ((k, v2), diff) in rhs {
+            if let Some(matches) = self.lhs.get(&k) {
+                output.extend(
+                    matches
+                        .iter()
+                        .map(|(v1, count)| ((k.clone(), (v1.clone(), v2.clone())), count * diff)),
+                );
+            }
+            update_count(self.rhs.entry(k).or_default(), v2, diff);
+        }
+        self.lhs.retain(|_, vals| !vals.is_empty());
+        self.rhs.retain(|_, vals| !vals.is_empty());
+        output
+    }
+}
+
+/// State for `diff_unique`, the accumulated multiplicity of each item.
+#[derive(Debug)]
+pub struct DiffUniqueState<T> {
+    counts: HashMap<T, Diff>,
+}
+impl<T> Default for DiffUniqueState<T> {
+    fn default() -> Self {
+        Self {
+            counts: HashMap::new(),
+        }
+    }
+}
+impl<T> DiffUniqueState<T>
+where
+    T: Clone + Eq + Hash,
+{
+    /// Applies the changes, returning `(item, 1)` for items which became present and
+    /// `(item, -1)` for items which are no longer present.
+    pub fn unique(&mut self, input: impl IntoIterator<Item = (T, Diff)>) -> Vec<(T, Diff)> {
+        consolidate(input)
+            .into_iter()
+            .filter_map(|(item, diff)| {
+                let (old, new) = update_count(&mut self.counts, item.clone(), diff);
+                presence_change(0 < old, 0 < new).map(|diff| (item, diff))
+            })
+            .collect()
+    }
+}
+
+/// State for `diff_difference`, the accumulated multiplicities of the `pos` and `neg` items.
+#[derive(Debug)]
+pub struct DiffDifferenceState<T> {
+    pos: HashMap<T, Diff>,
+    neg: HashMap<T, Diff>,
+}
+impl<T> Default for DiffDifferenceState<T> {
+    fn default() -> Self {
+        Self {
+            pos: HashMap::new(),
+            neg: HashMap::new(),
+        }
+    }
+}
+impl<T> DiffDifferenceState<T>
+where
+    T: Clone + Eq + Hash,
+{
+    /// An item is in the output if it is present in `pos` and not present in `neg`.
+    fn contains(&self, item: &T) -> bool {
+        0 < self.pos.get(item).copied().unwrap_or(0)
+            && 0 >= self.neg.get(item).copied().unwrap_or(0)
+    }
+
+    /// Applies the changes to both sides, returning `(item, 1)` for items which entered the set
+    /// difference and `(item, -1)` for items which left it.
+    pub fn difference(
+        &mut self,
+        pos: impl IntoIterator<Item = (T, Diff)>,
+        neg: impl IntoIterator<Item = (T, Diff)>,
+    ) -> Vec<(T, Diff)> {
+        let pos = consolidate(pos);
+        let neg = consolidate(neg);
+
+        let mut touched = HashMap::new();
+        for item in pos.keys().chain(neg.keys()) {
+            if !touched.contains_key(item) {
+                touched.insert(item.clone(), self.contains(item));
+            }
+        }
+
+        for (item, diff) in pos {
+            update_count(&mut self.pos, item, diff);
+        }
+        for (item, diff) in neg {
+            update_count(&mut self.neg, item, diff);
+        }
+
+        touched
+            .into_iter()
+            .filter_map(|(item, was_present)| {
+                presence_change(was_present, self.contains(&item)).map(|diff| (item, diff))
+            })
+            .collect()
+    }
+}
+
+/// Applies the changes to the `'static` state of `diff_group_by`, the total multiplicity and
+/// aggregate of each group. `agg` applies a change of `diff` in the multiplicity of a value to
+/// the aggregate, so only the changed values are aggregated. For each group whose aggregate
+/// changed the old aggregate is retracted and the new aggregate inserted. A group is present
+/// while its total multiplicity is positive.
+pub fn diff_group_by_into<K, V, A>(
+    groups: &mut HashMap<K, (Diff, A)>,
+    input: impl IntoIterator<Item = ((K, V), Diff)>,
+    mut init: impl FnMut() -> A,
+    mut agg: impl FnMut(&mut A, V, Diff),
+) -> Vec<((K, A), Diff)>
+where
+    K: Clone + Eq + Hash,
+    A: Clone + Eq,
+{
+    // The aggregate of each changed group before these changes, `None` if it was not present.
+    let mut old_aggregates = HashMap::new();
+    for ((k, v), diff) in input {
+        if 0 == diff {
+            continue;
+        }
+        let (count, accum) = groups.entry(k.clone()).or_insert_with(|| (0, (init)()));
+        old_aggregates
+            .entry(k)
+            .or_insert_with(|| (0 < *count).then(|| accum.clone()));
+        *count += diff;
+        (agg)(accum, v, diff);
+    }
+
+    let mut output = Vec::new();
+    for (k, old_aggregate) in old_aggregates {
+        let (count, accum) = &groups[&k];
+        let new_aggregate = (0 < *count).then(|| accum.clone());
+        if 0 == *count {
+            groups.remove(&k);
+        }
+        if old_aggregate == new_aggregate {
+            continue;
+        }
+        if let Some(old_aggregate) = old_aggregate {
+            output.push(((k.clone(), old_aggregate), -1));
+        }
+        if let Some(new_aggregate) = new_aggregate {
+            output.push(((k, new_aggregate), 1));
+        }
+    }
+    output
+}
+
+/// Returns the diff to emit when an item's presence changes from `old` to `new`.
+fn presence_change(old: bool, new: bool) -> Option<Diff> {
+    match (old, new) {
+        (false, true) => Some(1),
+        (true, false) => Some(-1),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::{diff_group_by_into, DiffJoinState, DiffUniqueState};
+
+    #[test]
+    fn diff_join_retraction() {
+        let mut state = DiffJoinState::default();
+
+        let mut out = state.join([((1, "a"), 1)], [((1, "x"), 1), ((1, "y"), 1)]);
+        out.sort();
+        assert_eq!(out, vec![((1, ("a", "x")), 1), ((1, ("a", "y")), 1)]);
+
+        let mut out = state.join([((1, "a"), -1)], []);
+        out.sort();
+        assert_eq!(out, vec![((1, ("a", "x")), -1), ((1, ("a", "y")), -1)]);
+
+        assert_eq!(state.join([], [((1, "z"), 1)]), vec![]);
+    }
+
+    #[test]
+    fn diff_unique_and_group_by() {
+        let mut unique = DiffUniqueState::default();
+        assert_eq!(unique.unique([("a", 1), ("a", 1)]), vec![("a", 1)]);
+        assert_eq!(unique.unique([("a", -1)]), vec![]);
+        assert_eq!(unique.unique([("a", -1)]), vec![("a", -1)]);
+
+        let mut groups = HashMap::new();
+        let sum = |accum: &mut i64, val: i64, diff: i64| *accum += val * diff;
+        assert_eq!(
+            diff_group_by_into(&mut groups, [(("k", 1), 1), (("k", 2), 1)], || 0, sum),
+            vec![(("k", 3), 1)]
+        );
+        assert_eq!(
+            diff_group_by_into(&mut groups, [(("k", 2), -1)], || 0, sum),
+            vec![(("k", 3), -1), (("k", 1), 1)]
+        );
+        // Changes which cancel out within a tick emit nothing.
+        assert_eq!(
+            diff_group_by_into(&mut groups, [(("k", 5), 1), (("k", 5), -1)], || 0, sum),
+            vec![]
+        );
+        assert_eq!(
+            diff_group_by_into(&mut groups, [(("k", 1), -1)], || 0, sum),
+            vec![(("k", 1), -1)]
+        );
+        assert!(groups.is_empty());
+    }
+}
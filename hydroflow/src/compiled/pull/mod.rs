@@ -4,5 +4,14 @@ pub use batch_join::*;
 mod cross_join;
 pub use cross_join::*;
 
+mod differential;
+pub use differential::*;
+
+mod persist_keyed;
+pub use persist_keyed::*;
+
 mod symmetric_hash_join;
 pub use symmetric_hash_join::*;
+
+mod top_k;
+pub use top_k::*;
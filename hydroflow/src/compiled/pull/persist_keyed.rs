@@ -0,0 +1,78 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+
+/// State for `persist_keyed`, the current value of each key.
+#[derive(Debug)]
+pub struct PersistKeyedState<K, V> {
+    values: HashMap<K, V>,
+}
+impl<K, V> Default for PersistKeyedState<K, V> {
+    fn default() -> Self {
+        Self {
+            values: HashMap::new(),
+        }
+    }
+}
+impl<K, V> PersistKeyedState<K, V>
+where
+    K: Clone + Eq + Hash,
+    V: Clone + Eq,
+{
+    /// Applies the deletes and then the upserts, so a key both deleted and upserted ends up with
+    /// the upserted value. Returns `(key, Some(value))` for each key whose value changed and
+    /// `(key, None)` for each key which was removed.
+    pub fn apply(
+        &mut self,
+        deletes: impl IntoIterator<Item = K>,
+        upserts: impl IntoIterator<Item = (K, V)>,
+    ) -> Vec<(K, Option<V>)> {
+        // The value of each touched key before this update.
+        let mut old_values = HashMap::new();
+        for k in deletes {
+            let old_value = self.values.remove(&k);
+            old_values.entry(k).or_insert(old_value);
+        }
+        for (k, v) in upserts {
+            let old_value = self.values.insert(k.clone(), v);
+            old_values.entry(k).or_insert(old_value);
+        }
+
+        old_values
+            .into_iter()
+            .filter_map(|(k, old_value)| {
+                let new_value = self.values.get(&k);
+                (old_value.as_ref() != new_value).then(|| (k, new_value.cloned()))
+            })
+            .collect()
+    }
+
+    /// Returns clones of the current key-value pairs.
+    pub fn contents(&self) -> Vec<(K, V)> {
+        self.values
+            .iter()
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PersistKeyedState;
+
+    #[test]
+    fn upsert_delete() {
+        let mut state = PersistKeyedState::default();
+        let mut changes = state.apply([], [("a", 1), ("b", 2)]);
+        changes.sort();
+        assert_eq!(changes, vec![("a", Some(1)), ("b", Some(2))]);
+
+        // Overwrite with same value is not a change, deleting a missing key is not a change.
+        let mut changes = state.apply(["b", "c"], [("a", 1), ("a", 3)]);
+        changes.sort();
+        assert_eq!(changes, vec![("a", Some(3)), ("b", None)]);
+
+        // Delete then upsert in the same update keeps the upserted value.
+        assert_eq!(state.apply(["a"], [("a", 3)]), vec![]);
+        assert_eq!(state.contents(), vec![("a", 3)]);
+    }
+}
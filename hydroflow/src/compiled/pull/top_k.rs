@@ -0,0 +1,192 @@
+use std::cmp::{Ordering, Reverse};
+use std::collections::hash_map::Entry;
+use std::collections::{BinaryHeap, HashMap};
+use std::hash::Hash;
+
+/// An item in a [`TopKState`], ordered by its key. Ties are broken in favor of the item that was
+/// inserted first.
+#[derive(Debug)]
+struct TopKEntry<K, T> {
+    key: K,
+    seq: Reverse<usize>,
+    item: T,
+}
+impl<K: Ord, T> PartialEq for TopKEntry<K, T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+impl<K: Ord, T> Eq for TopKEntry<K, T> {}
+impl<K: Ord, T> PartialOrd for TopKEntry<K, T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+impl<K: Ord, T> Ord for TopKEntry<K, T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (&self.key, self.seq).cmp(&(&other.key, other.seq))
+    }
+}
+
+/// Keeps the `k` items with the greatest keys seen so far, using a bounded min-heap so memory is
+/// `O(k)` regardless of how many items are inserted.
+#[derive(Debug)]
+pub struct TopKState<K, T> {
+    k: usize,
+    heap: BinaryHeap<Reverse<TopKEntry<K, T>>>,
+    next_seq: usize,
+}
+impl<K, T> TopKState<K, T>
+where
+    K: Ord,
+{
+    pub fn new(k: usize) -> Self {
+        Self {
+            k,
+            heap: BinaryHeap::with_capacity(k + 1),
+            next_seq: 0,
+        }
+    }
+
+    /// Inserts each item, keyed by `key_fn`, evicting the smallest items once there are more than
+    /// `k`.
+    pub fn extend(&mut self, iter: impl IntoIterator<Item = T>, mut key_fn: impl FnMut(&T) -> K) {
+        for item in iter {
+            let key = (key_fn)(&item);
+            if self.heap.len() >= self.k {
+                match self.heap.peek() {
+                    Some(Reverse(min)) if min.key < key => {}
+                    _ => continue,
+                }
+            }
+            self.heap.push(Reverse(TopKEntry {
+                key,
+                seq: Reverse(self.next_seq),
+                item,
+            }));
+            self.next_seq += 1;
+            if self.heap.len() > self.k {
+                self.heap.pop();
+            }
+        }
+    }
+
+    /// Returns clones of the current top `k` items, greatest key first.
+    pub fn sorted(&self) -> Vec<T>
+    where
+        T: Clone,
+    {
+        let mut entries = self
+            .heap
+            .iter()
+            .map(|Reverse(entry)| entry)
+            .collect::<Vec<_>>();
+        entries.sort_unstable_by(|a, b| b.cmp(a));
+        entries
+            .into_iter()
+            .map(|entry| entry.item.clone())
+            .collect()
+    }
+
+    /// Consumes the state, returning the top `k` items, greatest key first.
+    pub fn into_sorted_vec(self) -> Vec<T> {
+        // `Reverse` makes the ascending sort greatest-first.
+        self.heap
+            .into_sorted_vec()
+            .into_iter()
+            .map(|Reverse(entry)| entry.item)
+            .collect()
+    }
+}
+
+/// A [`TopKState`] for each group, used by `top_k_by_group`.
+#[derive(Debug)]
+pub struct TopKByGroupState<G, K, T> {
+    k: usize,
+    groups: HashMap<G, TopKState<K, T>>,
+}
+impl<G, K, T> TopKByGroupState<G, K, T>
+where
+    G: Eq + Hash,
+    K: Ord,
+{
+    pub fn new(k: usize) -> Self {
+        Self {
+            k,
+            groups: HashMap::new(),
+        }
+    }
+
+    /// Inserts each `(group, item)` pair into its group's top `k`.
+    pub fn extend(
+        &mut self,
+        iter: impl IntoIterator<Item = (G, T)>,
+        mut key_fn: impl FnMut(&T) -> K,
+    ) {
+        for (group, item) in iter {
+            let state = match self.groups.entry(group) {
+                Entry::Occupied(occupied) => occupied.into_mut(),
+                Entry::Vacant(vacant) => vacant.insert(TopKState::new(self.k)),
+            };
+            state.extend([item], &mut key_fn);
+        }
+    }
+
+    /// Returns clones of the current top `k` items of each group, greatest key first within each
+    /// group.
+    pub fn sorted(&self) -> Vec<(G, T)>
+    where
+        G: Clone,
+        T: Clone,
+    {
+        self.groups
+            .iter()
+            .flat_map(|(group, state)| {
+                state
+                    .sorted()
+                    .into_iter()
+                    .map(move |item| (group.clone(), item))
+            })
+            .collect()
+    }
+
+    /// Consumes the state, returning the top `k` items of each group, greatest key first within
+    /// each group.
+    pub fn into_sorted_vec(self) -> Vec<(G, T)>
+    where
+        G: Clone,
+    {
+        self.groups
+            .into_iter()
+            .flat_map(|(group, state)| {
+                state
+                    .into_sorted_vec()
+                    .into_iter()
+                    .map(move |item| (group.clone(), item))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TopKState;
+
+    #[test]
+    fn top_k_bounded() {
+        let mut state = TopKState::new(3);
+        state.extend([5, 1, 9, 3, 7], |&x| x);
+        assert_eq!(state.heap.len(), 3);
+        assert_eq!(state.sorted(), vec![9, 7, 5]);
+
+        state.extend([8, 2], |&x| x);
+        assert_eq!(state.into_sorted_vec(), vec![9, 8, 7]);
+    }
+
+    #[test]
+    fn top_k_ties_keep_first() {
+        let mut state = TopKState::new(2);
+        state.extend([("a", 1), ("b", 1), ("c", 1)], |&(_, key)| key);
+        assert_eq!(state.sorted(), vec![("a", 1), ("b", 1)]);
+    }
+}
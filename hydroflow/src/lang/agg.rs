@@ -0,0 +1,309 @@
+//! Reusable aggregations for the `fold` and `group_by` operators, e.g. `group_by(agg::Sum)`.
+//!
+//! Several aggregations can be computed in a single pass by combining them into a variadic tuple
+//! with [`var_expr!`](crate::var_expr): `group_by(var_expr!(agg::Count, agg::Sum))` outputs
+//! `var_expr!(count, sum)` for each group.
+
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+use std::ops::AddAssign;
+
+/// An aggregation over items of type `Item`.
+///
+/// The accumulator is kept separately from the aggregator so that `'static` operators can keep
+/// updating it across ticks, calling [`Aggregator::finish`] to read the current result each tick.
+pub trait Aggregator<Item> {
+    /// Intermediate state of the aggregation.
+    type Accum;
+    /// Final result of the aggregation.
+    type Output;
+
+    /// Creates the accumulator for an empty input.
+    fn init(&self) -> Self::Accum;
+    /// Adds `item` into the accumulator.
+    fn update(&self, accum: &mut Self::Accum, item: Item);
+    /// Computes the result from the accumulator.
+    fn finish(&self, accum: &Self::Accum) -> Self::Output;
+}
+
+/// Aggregates `items`, returning the result. Used by `fold`.
+pub fn fold<Item, A>(agg: &A, items: impl IntoIterator<Item = Item>) -> A::Output
+where
+    A: Aggregator<Item>,
+{
+    let mut accum = agg.init();
+    for item in items {
+        agg.update(&mut accum, item);
+    }
+    agg.finish(&accum)
+}
+
+/// Aggregates `items` into `accum`, initializing it first if it is `None`, and returns the result.
+/// Used by `fold::<'static>`.
+pub fn fold_into<Item, A>(
+    agg: &A,
+    accum: &mut Option<A::Accum>,
+    items: impl IntoIterator<Item = Item>,
+) -> A::Output
+where
+    A: Aggregator<Item>,
+{
+    let accum = accum.get_or_insert_with(|| agg.init());
+    for item in items {
+        agg.update(accum, item);
+    }
+    agg.finish(accum)
+}
+
+/// Aggregates the items of each group, returning the result for each group. Used by `group_by`.
+pub fn group_by<K, Item, A>(
+    agg: &A,
+    items: impl IntoIterator<Item = (K, Item)>,
+) -> Vec<(K, A::Output)>
+where
+    K: Eq + Hash,
+    A: Aggregator<Item>,
+{
+    let mut groups = HashMap::new();
+    update_groups(agg, &mut groups, items);
+    groups
+        .into_iter()
+        .map(|(k, accum)| (k, agg.finish(&accum)))
+        .collect()
+}
+
+/// Aggregates the items of each group into `groups`. If there were any items returns the result
+/// for every group, otherwise returns nothing. Used by `group_by::<'static>`.
+pub fn group_by_into<K, Item, A>(
+    agg: &A,
+    groups: &mut HashMap<K, A::Accum>,
+    items: impl IntoIterator<Item = (K, Item)>,
+) -> Vec<(K, A::Output)>
+where
+    K: Eq + Hash + Clone,
+    A: Aggregator<Item>,
+{
+    if !update_groups(agg, groups, items) {
+        return Vec::new();
+    }
+    groups
+        .iter()
+        .map(|(k, accum)| (k.clone(), agg.finish(accum)))
+        .collect()
+}
+
+/// Returns true if there were any items.
+fn update_groups<K, Item, A>(
+    agg: &A,
+    groups: &mut HashMap<K, A::Accum>,
+    items: impl IntoIterator<Item = (K, Item)>,
+) -> bool
+where
+    K: Eq + Hash,
+    A: Aggregator<Item>,
+{
+    let mut any = false;
+    for (k, item) in items {
+        any = true;
+        let accum = match groups.entry(k) {
+            Entry::Occupied(occupied) => occupied.into_mut(),
+            Entry::Vacant(vacant) => vacant.insert(agg.init()),
+        };
+        agg.update(accum, item);
+    }
+    any
+}
+
+/// Counts the number of items.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Count;
+impl<Item> Aggregator<Item> for Count {
+    type Accum = usize;
+    type Output = usize;
+
+    fn init(&self) -> Self::Accum {
+        0
+    }
+    fn update(&self, accum: &mut Self::Accum, _item: Item) {
+        *accum += 1;
+    }
+    fn finish(&self, accum: &Self::Accum) -> Self::Output {
+        *accum
+    }
+}
+
+/// Sums the items, starting from `Item::default()`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Sum;
+impl<Item> Aggregator<Item> for Sum
+where
+    Item: AddAssign + Default + Clone,
+{
+    type Accum = Item;
+    type Output = Item;
+
+    fn init(&self) -> Self::Accum {
+        Item::default()
+    }
+    fn update(&self, accum: &mut Self::Accum, item: Item) {
+        *accum += item;
+    }
+    fn finish(&self, accum: &Self::Accum) -> Self::Output {
+        accum.clone()
+    }
+}
+
+/// The least item, or `None` if there are no items.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Min;
+impl<Item> Aggregator<Item> for Min
+where
+    Item: Ord + Clone,
+{
+    type Accum = Option<Item>;
+    type Output = Option<Item>;
+
+    fn init(&self) -> Self::Accum {
+        None
+    }
+    fn update(&self, accum: &mut Self::Accum, item: Item) {
+        match accum {
+            Some(min) if *min <= item => {}
+            _ => *accum = Some(item),
+        }
+    }
+    fn finish(&self, accum: &Self::Accum) -> Self::Output {
+        accum.clone()
+    }
+}
+
+/// The greatest item, or `None` if there are no items.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Max;
+impl<Item> Aggregator<Item> for Max
+where
+    Item: Ord + Clone,
+{
+    type Accum = Option<Item>;
+    type Output = Option<Item>;
+
+    fn init(&self) -> Self::Accum {
+        None
+    }
+    fn update(&self, accum: &mut Self::Accum, item: Item) {
+        match accum {
+            Some(max) if *max >= item => {}
+            _ => *accum = Some(item),
+        }
+    }
+    fn finish(&self, accum: &Self::Accum) -> Self::Output {
+        accum.clone()
+    }
+}
+
+/// The arithmetic mean of the items as an `f64`, or `None` if there are no items.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Avg;
+impl<Item> Aggregator<Item> for Avg
+where
+    Item: Into<f64>,
+{
+    /// Sum and count.
+    type Accum = (f64, usize);
+    type Output = Option<f64>;
+
+    fn init(&self) -> Self::Accum {
+        (0.0, 0)
+    }
+    fn update(&self, (sum, count): &mut Self::Accum, item: Item) {
+        *sum += item.into();
+        *count += 1;
+    }
+    fn finish(&self, &(sum, count): &Self::Accum) -> Self::Output {
+        (0 != count).then_some(sum / count as f64)
+    }
+}
+
+/// Counts the number of distinct items.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct CountDistinct;
+impl<Item> Aggregator<Item> for CountDistinct
+where
+    Item: Eq + Hash,
+{
+    type Accum = HashSet<Item>;
+    type Output = usize;
+
+    fn init(&self) -> Self::Accum {
+        HashSet::new()
+    }
+    fn update(&self, accum: &mut Self::Accum, item: Item) {
+        accum.insert(item);
+    }
+    fn finish(&self, accum: &Self::Accum) -> Self::Output {
+        accum.len()
+    }
+}
+
+/// The empty variadic aggregates nothing.
+impl<Item> Aggregator<Item> for () {
+    type Accum = ();
+    type Output = ();
+
+    fn init(&self) -> Self::Accum {}
+    fn update(&self, _accum: &mut Self::Accum, _item: Item) {}
+    fn finish(&self, _accum: &Self::Accum) -> Self::Output {}
+}
+
+/// A variadic of aggregators computes each aggregation over the same items.
+impl<Item, A, Rest> Aggregator<Item> for (A, Rest)
+where
+    Item: Clone,
+    A: Aggregator<Item>,
+    Rest: Aggregator<Item>,
+{
+    type Accum = (A::Accum, Rest::Accum);
+    type Output = (A::Output, Rest::Output);
+
+    fn init(&self) -> Self::Accum {
+        (self.0.init(), self.1.init())
+    }
+    fn update(&self, (accum, rest): &mut Self::Accum, item: Item) {
+        self.0.update(accum, item.clone());
+        self.1.update(rest, item);
+    }
+    fn finish(&self, (accum, rest): &Self::Accum) -> Self::Output {
+        (self.0.finish(accum), self.1.finish(rest))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use variadics::{var_args, var_expr};
+
+    use super::{fold, Aggregator, Avg, Count, CountDistinct, Max, Min, Sum};
+
+    fn aggregate<Item, A: Aggregator<Item>>(agg: A, items: Vec<Item>) -> A::Output {
+        fold(&agg, items)
+    }
+
+    #[test]
+    fn basic() {
+        assert_eq!(4, aggregate(Count, vec![1, 2, 2, 3]));
+        assert_eq!(8, aggregate(Sum, vec![1, 2, 2, 3]));
+        assert_eq!(Some(1), aggregate(Min, vec![2, 1, 3]));
+        assert_eq!(Some(3), aggregate(Max, vec![2, 1, 3]));
+        assert_eq!(Some(2.0), aggregate(Avg, vec![1, 2, 3]));
+        assert_eq!(None, aggregate(Avg, Vec::<u32>::new()));
+        assert_eq!(3, aggregate(CountDistinct, vec![1, 2, 2, 3]));
+    }
+
+    #[test]
+    fn variadic() {
+        let var_args!(count, sum, max) = aggregate(var_expr!(Count, Sum, Max), vec![5_u32, 1, 3]);
+        assert_eq!(3, count);
+        assert_eq!(9, sum);
+        assert_eq!(Some(5), max);
+    }
+}
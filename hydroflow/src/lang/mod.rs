@@ -1,5 +1,7 @@
+pub mod agg;
 pub mod clear;
 pub mod collections;
 pub mod lattice;
 pub mod monotonic_map;
+pub mod sketch;
 pub mod tag;
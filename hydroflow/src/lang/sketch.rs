@@ -0,0 +1,560 @@
+//! Bounded-memory approximate sketches, used by the `approx_distinct`, `approx_top_k`, and
+//! `approx_quantile` operators.
+//!
+//! Each sketch is also an [`Aggregator`] accumulator, so sketches can be computed per group, e.g.
+//! `group_by(sketch::ApproxDistinct::default())`.
+//!
+//! Sketches built on different nodes can be combined if they were built with the same parameters.
+//! [`HyperLogLog`] merges are idempotent, so it is a lattice: it implements [`MergeSketch`] and
+//! the [`Merge`] lattice trait via [`SketchRepr`]. [`CountMinSketch`], [`TopKSketch`], and
+//! [`QuantileSketch`] instead have a `combine` method which adds counts, so it combines sketches of
+//! *disjoint* streams and each sketch should only be combined in once.
+
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BinaryHeap, HashMap};
+use std::hash::{Hash, Hasher};
+
+use siphasher::sip::SipHasher13;
+
+use super::agg::Aggregator;
+use super::lattice::{Lattice, LatticeRepr, Merge};
+
+/// Sketches with an idempotent, commutative, and associative merge, which can be merged with
+/// another sketch built with the same parameters.
+pub trait MergeSketch {
+    /// Merges `other` into `self`. Returns true if `self` changed.
+    fn merge(&mut self, other: Self) -> bool;
+}
+
+/// Hashes `item` along with `seed`. Uses SipHash-1-3 with fixed keys, which unlike the standard
+/// library's `DefaultHasher` is specified to never change, so sketches built on different nodes
+/// are compatible.
+fn hash_with_seed<T: Hash + ?Sized>(seed: u64, item: &T) -> u64 {
+    let mut hasher = SipHasher13::new_with_keys(0x6879_6472_6f66_6c6f, 0x736b_6574_6368_6573);
+    seed.hash(&mut hasher);
+    item.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// HyperLogLog distinct-count estimator using `2^precision` one-byte registers.
+///
+/// The standard error is about `1.04 / sqrt(2^precision)`, e.g. 1.6% for the default precision of
+/// 12 (4 KiB).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HyperLogLog {
+    precision: u8,
+    registers: Vec<u8>,
+}
+impl Default for HyperLogLog {
+    fn default() -> Self {
+        Self::new(12)
+    }
+}
+impl HyperLogLog {
+    /// Creates an empty sketch. `precision` must be between 4 and 16 inclusive.
+    pub fn new(precision: u8) -> Self {
+        assert!(
+            (4..=16).contains(&precision),
+            "HyperLogLog precision must be between 4 and 16, got {}",
+            precision
+        );
+        Self {
+            precision,
+            registers: vec![0; 1 << precision],
+        }
+    }
+
+    pub fn insert<T: Hash + ?Sized>(&mut self, item: &T) {
+        let hash = hash_with_seed(0, item);
+        let index = (hash >> (64 - self.precision)) as usize;
+        // Position of the first set bit after the index bits, capped by the remaining bits.
+        let rank = ((hash << self.precision).leading_zeros() as u8).min(64 - self.precision) + 1;
+        let register = &mut self.registers[index];
+        *register = (*register).max(rank);
+    }
+
+    /// Estimated number of distinct items inserted.
+    pub fn estimate(&self) -> f64 {
+        let m = self.registers.len() as f64;
+        let alpha = match self.registers.len() {
+            16 => 0.673,
+            32 => 0.697,
+            64 => 0.709,
+            _ => 0.7213 / (1.0 + 1.079 / m),
+        };
+        let sum: f64 = self
+            .registers
+            .iter()
+            .map(|&register| 2.0_f64.powi(-(register as i32)))
+            .sum();
+        let estimate = alpha * m * m / sum;
+
+        // Small range correction, linear counting.
+        let zeros = self
+            .registers
+            .iter()
+            .filter(|&&register| 0 == register)
+            .count();
+        if estimate <= 2.5 * m && 0 != zeros {
+            m * (m / zeros as f64).ln()
+        } else {
+            estimate
+        }
+    }
+}
+impl MergeSketch for HyperLogLog {
+    fn merge(&mut self, other: Self) -> bool {
+        assert_eq!(self.precision, other.precision);
+        let mut changed = false;
+        for (register, other) in self.registers.iter_mut().zip(other.registers) {
+            if other > *register {
+                *register = other;
+                changed = true;
+            }
+        }
+        changed
+    }
+}
+
+/// Count-Min sketch, estimating the count of each item with one-sided error: estimates never
+/// undercount, and overcount by at most `e / width` of the total count with probability
+/// `1 - e^-depth`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CountMinSketch {
+    width: usize,
+    depth: usize,
+    counts: Vec<u64>,
+}
+impl Default for CountMinSketch {
+    fn default() -> Self {
+        Self::new(2048, 4)
+    }
+}
+impl CountMinSketch {
+    pub fn new(width: usize, depth: usize) -> Self {
+        assert!(0 < width && 0 < depth);
+        Self {
+            width,
+            depth,
+            counts: vec![0; width * depth],
+        }
+    }
+
+    fn index<T: Hash + ?Sized>(&self, row: usize, item: &T) -> usize {
+        row * self.width + (hash_with_seed(row as u64, item) % self.width as u64) as usize
+    }
+
+    /// Adds `count` to `item`, returning the new estimated count of `item`.
+    pub fn add<T: Hash + ?Sized>(&mut self, item: &T, count: u64) -> u64 {
+        let mut estimate = u64::MAX;
+        for row in 0..self.depth {
+            let index = self.index(row, item);
+            self.counts[index] += count;
+            estimate = estimate.min(self.counts[index]);
+        }
+        estimate
+    }
+
+    /// Estimated count of `item`.
+    pub fn estimate<T: Hash + ?Sized>(&self, item: &T) -> u64 {
+        (0..self.depth)
+            .map(|row| self.counts[self.index(row, item)])
+            .min()
+            .unwrap()
+    }
+
+    /// Adds the counts of `other`, a sketch of a disjoint stream, into `self`.
+    pub fn combine(&mut self, other: Self) {
+        assert_eq!((self.width, self.depth), (other.width, other.depth));
+        for (count, other) in self.counts.iter_mut().zip(other.counts) {
+            *count += other;
+        }
+    }
+}
+
+/// Heavy hitters: tracks the `k` items with the greatest estimated counts, using a
+/// [`CountMinSketch`] for the estimates so memory is bounded regardless of the number of distinct
+/// items.
+#[derive(Clone, Debug)]
+pub struct TopKSketch<T> {
+    k: usize,
+    counts: CountMinSketch,
+    /// Each candidate's estimate and the sequence number of its entry in `heap`.
+    candidates: HashMap<T, (u64, u64)>,
+    /// Min-heap of `(estimate, seq)` entries. Estimates only increase, so an entry is stale if its
+    /// candidate has since been updated or evicted, in which case it is not in `heap_items`.
+    heap: BinaryHeap<Reverse<(u64, u64)>>,
+    /// The candidate of each entry in `heap` which is not stale.
+    heap_items: HashMap<u64, T>,
+    next_seq: u64,
+}
+impl<T> TopKSketch<T>
+where
+    T: Hash + Eq + Clone,
+{
+    pub fn new(k: usize) -> Self {
+        Self {
+            k,
+            counts: CountMinSketch::default(),
+            candidates: HashMap::with_capacity(k + 1),
+            heap: BinaryHeap::with_capacity(k + 1),
+            heap_items: HashMap::with_capacity(k + 1),
+            next_seq: 0,
+        }
+    }
+
+    pub fn insert(&mut self, item: T) {
+        let estimate = self.counts.add(&item, 1);
+        self.offer(item, estimate);
+    }
+
+    /// Updates `item`'s estimate in the candidates, evicting the least candidate if there are
+    /// more than `k`.
+    fn offer(&mut self, item: T, estimate: u64) {
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        if let Some((_, old_seq)) = self.candidates.insert(item.clone(), (estimate, seq)) {
+            self.heap_items.remove(&old_seq);
+        }
+        self.heap.push(Reverse((estimate, seq)));
+        self.heap_items.insert(seq, item);
+
+        while self.candidates.len() > self.k {
+            let Reverse((_, seq)) = self.heap.pop().unwrap();
+            if let Some(least) = self.heap_items.remove(&seq) {
+                self.candidates.remove(&least);
+            }
+        }
+        // Drop the stale entries once they outnumber the candidates.
+        if self.heap.len() > 2 * self.candidates.len() + 16 {
+            self.heap = self
+                .candidates
+                .values()
+                .map(|&entry| Reverse(entry))
+                .collect();
+        }
+    }
+
+    /// The top `k` items and their estimated counts, greatest count first.
+    pub fn top_k(&self) -> Vec<(T, u64)> {
+        let mut top_k = self
+            .candidates
+            .iter()
+            .map(|(item, &(estimate, _))| (item.clone(), estimate))
+            .collect::<Vec<_>>();
+        top_k.sort_by_key(|&(_, estimate)| Reverse(estimate));
+        top_k
+    }
+
+    /// Adds the counts of `other`, a sketch of a disjoint stream, into `self`.
+    pub fn combine(&mut self, other: Self) {
+        assert_eq!(self.k, other.k);
+        self.counts.combine(other.counts);
+        let items = self
+            .candidates
+            .drain()
+            .map(|(item, _)| item)
+            .chain(other.candidates.into_keys())
+            .collect::<Vec<_>>();
+        self.heap.clear();
+        self.heap_items.clear();
+        for item in items {
+            let estimate = self.counts.estimate(&item);
+            self.offer(item, estimate);
+        }
+    }
+}
+
+/// Quantile sketch with relative accuracy `alpha` (DDSketch): values are counted in
+/// logarithmically sized buckets, so any quantile estimate is within `alpha * value` of a value of
+/// that rank. The number of buckets per sign is bounded by `max_buckets`, collapsing the smallest
+/// magnitudes together when exceeded.
+#[derive(Clone, Debug, PartialEq)]
+pub struct QuantileSketch {
+    alpha: f64,
+    gamma_ln: f64,
+    max_buckets: usize,
+    positive: BTreeMap<i32, u64>,
+    negative: BTreeMap<i32, u64>,
+    zeros: u64,
+    count: u64,
+}
+impl Default for QuantileSketch {
+    fn default() -> Self {
+        Self::new(0.01, 2048)
+    }
+}
+impl QuantileSketch {
+    pub fn new(alpha: f64, max_buckets: usize) -> Self {
+        assert!(0.0 < alpha && alpha < 1.0 && 0 < max_buckets);
+        Self {
+            alpha,
+            gamma_ln: ((1.0 + alpha) / (1.0 - alpha)).ln(),
+            max_buckets,
+            positive: BTreeMap::new(),
+            negative: BTreeMap::new(),
+            zeros: 0,
+            count: 0,
+        }
+    }
+
+    /// Number of values inserted.
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    pub fn insert(&mut self, value: f64) {
+        self.count += 1;
+        if value > f64::MIN_POSITIVE {
+            let key = self.key(value);
+            *self.positive.entry(key).or_default() += 1;
+            Self::collapse(&mut self.positive, self.max_buckets);
+        } else if value < -f64::MIN_POSITIVE {
+            let key = self.key(-value);
+            *self.negative.entry(key).or_default() += 1;
+            Self::collapse(&mut self.negative, self.max_buckets);
+        } else {
+            self.zeros += 1;
+        }
+    }
+
+    fn key(&self, magnitude: f64) -> i32 {
+        (magnitude.ln() / self.gamma_ln).ceil() as i32
+    }
+
+    fn value(&self, key: i32) -> f64 {
+        // Midpoint of the bucket `(gamma^(key-1), gamma^key]`, in relative terms.
+        2.0 * (key as f64 * self.gamma_ln).exp() / (1.0 + self.gamma_ln.exp())
+    }
+
+    /// Merges the smallest-magnitude buckets until there are at most `max_buckets`.
+    fn collapse(store: &mut BTreeMap<i32, u64>, max_buckets: usize) {
+        while store.len() > max_buckets {
+            let (_, count) = store.pop_first().unwrap();
+            *store.first_entry().unwrap().get_mut() += count;
+        }
+    }
+
+    /// Estimates the `q`-quantile, for `q` between 0 and 1. Returns `None` if empty.
+    pub fn quantile(&self, q: f64) -> Option<f64> {
+        if 0 == self.count {
+            return None;
+        }
+        let rank = (q.clamp(0.0, 1.0) * (self.count - 1) as f64).round() as u64;
+
+        // Ascending value order: negatives by descending magnitude, zeros, positives.
+        let mut seen = 0;
+        for (&key, &count) in self.negative.iter().rev() {
+            seen += count;
+            if rank < seen {
+                return Some(-self.value(key));
+            }
+        }
+        seen += self.zeros;
+        if rank < seen {
+            return Some(0.0);
+        }
+        for (&key, &count) in self.positive.iter() {
+            seen += count;
+            if rank < seen {
+                return Some(self.value(key));
+            }
+        }
+        unreachable!("rank is less than count");
+    }
+
+    /// Adds the counts of `other`, a sketch of a disjoint stream, into `self`.
+    pub fn combine(&mut self, other: Self) {
+        assert_eq!(
+            (self.alpha, self.max_buckets),
+            (other.alpha, other.max_buckets)
+        );
+        for (key, count) in other.positive {
+            *self.positive.entry(key).or_default() += count;
+        }
+        for (key, count) in other.negative {
+            *self.negative.entry(key).or_default() += count;
+        }
+        Self::collapse(&mut self.positive, self.max_buckets);
+        Self::collapse(&mut self.negative, self.max_buckets);
+        self.zeros += other.zeros;
+        self.count += other.count;
+    }
+}
+
+/// Lattice of sketches of type `S`, merged with [`MergeSketch::merge`].
+pub struct Sketch<S> {
+    _phantom: std::marker::PhantomData<S>,
+}
+impl<S> Lattice for Sketch<S> {}
+
+pub struct SketchRepr<S> {
+    _phantom: std::marker::PhantomData<S>,
+}
+impl<S: MergeSketch + Clone> LatticeRepr for SketchRepr<S> {
+    type Lattice = Sketch<S>;
+    type Repr = S;
+}
+impl<S: MergeSketch + Clone> Merge<SketchRepr<S>> for SketchRepr<S> {
+    fn merge(this: &mut S, delta: S) -> bool {
+        this.merge(delta)
+    }
+}
+
+/// Approximate distinct count with a [`HyperLogLog`] of the given precision.
+#[derive(Clone, Copy, Debug)]
+pub struct ApproxDistinct {
+    pub precision: u8,
+}
+impl Default for ApproxDistinct {
+    fn default() -> Self {
+        Self { precision: 12 }
+    }
+}
+impl<Item: Hash> Aggregator<Item> for ApproxDistinct {
+    type Accum = HyperLogLog;
+    type Output = usize;
+
+    fn init(&self) -> Self::Accum {
+        HyperLogLog::new(self.precision)
+    }
+    fn update(&self, accum: &mut Self::Accum, item: Item) {
+        accum.insert(&item);
+    }
+    fn finish(&self, accum: &Self::Accum) -> Self::Output {
+        accum.estimate().round() as usize
+    }
+}
+
+/// The approximate `k` most frequent items with their estimated counts, using a [`TopKSketch`].
+#[derive(Clone, Copy, Debug)]
+pub struct ApproxTopK {
+    pub k: usize,
+}
+impl ApproxTopK {
+    pub fn new(k: usize) -> Self {
+        Self { k }
+    }
+}
+impl<Item> Aggregator<Item> for ApproxTopK
+where
+    Item: Hash + Eq + Clone,
+{
+    type Accum = TopKSketch<Item>;
+    type Output = Vec<(Item, u64)>;
+
+    fn init(&self) -> Self::Accum {
+        TopKSketch::new(self.k)
+    }
+    fn update(&self, accum: &mut Self::Accum, item: Item) {
+        accum.insert(item);
+    }
+    fn finish(&self, accum: &Self::Accum) -> Self::Output {
+        accum.top_k()
+    }
+}
+
+/// The approximate `q`-quantile, using a [`QuantileSketch`]. `None` if there are no items.
+#[derive(Clone, Copy, Debug)]
+pub struct ApproxQuantile {
+    pub q: f64,
+}
+impl ApproxQuantile {
+    pub fn new(q: f64) -> Self {
+        Self { q }
+    }
+}
+impl<Item: Into<f64>> Aggregator<Item> for ApproxQuantile {
+    type Accum = QuantileSketch;
+    type Output = Option<f64>;
+
+    fn init(&self) -> Self::Accum {
+        QuantileSketch::default()
+    }
+    fn update(&self, accum: &mut Self::Accum, item: Item) {
+        accum.insert(item.into());
+    }
+    fn finish(&self, accum: &Self::Accum) -> Self::Output {
+        accum.quantile(self.q)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hyperloglog() {
+        let mut a = HyperLogLog::default();
+        let mut b = HyperLogLog::default();
+        for i in 0..10_000 {
+            a.insert(&i);
+            b.insert(&(i + 5_000));
+        }
+        // The standard error is about 1.8% at this load, allow about four times that.
+        let estimate = a.estimate();
+        assert!((9_300.0..10_700.0).contains(&estimate), "{}", estimate);
+
+        // Duplicates don't change the estimate, merging is idempotent.
+        a.insert(&0);
+        assert_eq!(estimate, a.estimate());
+        assert!(<SketchRepr<_> as Merge<_>>::merge(&mut a, b.clone()));
+        assert!(!a.merge(b));
+        let estimate = a.estimate();
+        assert!((14_250.0..15_750.0).contains(&estimate), "{}", estimate);
+    }
+
+    #[test]
+    fn top_k() {
+        let mut a = TopKSketch::new(2);
+        let mut b = TopKSketch::new(2);
+        for i in 0..1_000 {
+            a.insert(i % 50);
+            b.insert(i % 100);
+            if 0 == i % 10 {
+                a.insert(7);
+                b.insert(77);
+            }
+        }
+        assert_eq!(7, a.top_k()[0].0);
+        // Stale heap entries are dropped.
+        assert!(a.heap.len() <= 2 * a.candidates.len() + 16);
+        a.combine(b);
+        let top_k = a.top_k();
+        assert_eq!(
+            vec![7, 77],
+            top_k.iter().map(|&(x, _)| x).collect::<Vec<_>>()
+        );
+        assert!(top_k[0].1 >= 130);
+    }
+
+    #[test]
+    fn quantile() {
+        let mut a = QuantileSketch::default();
+        let mut b = QuantileSketch::default();
+        for i in 1..=1_000 {
+            a.insert(i as f64);
+            b.insert(-(i as f64));
+        }
+        let median = a.quantile(0.5).unwrap();
+        assert!((495.0..=505.0).contains(&median), "{}", median);
+        let p99 = a.quantile(0.99).unwrap();
+        assert!((980.0..=1000.0).contains(&p99), "{}", p99);
+
+        a.combine(b);
+        b = QuantileSketch::default();
+        b.insert(0.0);
+        a.combine(b);
+        assert_eq!(Some(0.0), a.quantile(0.5));
+        let min = a.quantile(0.0).unwrap();
+        assert!((-1_010.0..=-990.0).contains(&min), "{}", min);
+
+        let mut bounded = QuantileSketch::new(0.01, 10);
+        for i in 1..=1_000 {
+            bounded.insert(i as f64);
+        }
+        assert_eq!(10, bounded.positive.len());
+        let max = bounded.quantile(1.0).unwrap();
+        assert!((990.0..=1010.0).contains(&max), "{}", max);
+    }
+}
@@ -0,0 +1,119 @@
+//! Adapters which run a [`Hydroflow`] graph as an async [`Stream`] or [`Sink`].
+//!
+//! The graph is driven by whoever polls the adapter, with no separate task, so it can be embedded
+//! in request handlers or used as the input of another graph's `source_stream`.
+
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures::{Sink, Stream};
+use tokio::sync::mpsc::error::SendError;
+use tokio::sync::mpsc::UnboundedSender;
+
+use super::graph::Hydroflow;
+
+impl Hydroflow {
+    /// Turns this graph into a [`Stream`] of the items from `output`, e.g. an `output()` port
+    /// handle. See [`HydroflowStream`].
+    pub fn into_stream<S>(self, output: S) -> HydroflowStream<S>
+    where
+        S: Stream + Unpin,
+    {
+        HydroflowStream::new(self, output)
+    }
+
+    /// Turns this graph into a [`Sink`] of items for `input`, e.g. an `input()` port handle. See
+    /// [`HydroflowSink`].
+    pub fn into_sink<T>(self, input: UnboundedSender<T>) -> HydroflowSink<T> {
+        HydroflowSink::new(self, input)
+    }
+}
+
+/// A [`Stream`] of a graph's output, which runs the graph whenever it is polled and no output
+/// is ready.
+///
+/// Inputs can still be sent into the graph while it is being polled, for example through
+/// `input()` port handles, which wake the stream.
+pub struct HydroflowStream<S> {
+    df: Hydroflow,
+    output: S,
+}
+impl<S> HydroflowStream<S>
+where
+    S: Stream + Unpin,
+{
+    pub fn new(df: Hydroflow, output: S) -> Self {
+        Self { df, output }
+    }
+
+    /// Returns the graph.
+    pub fn df(&mut self) -> &mut Hydroflow {
+        &mut self.df
+    }
+
+    /// Returns the graph and the output stream.
+    pub fn into_inner(self) -> (Hydroflow, S) {
+        (self.df, self.output)
+    }
+}
+impl<S> Stream for HydroflowStream<S>
+where
+    S: Stream + Unpin,
+{
+    type Item = S::Item;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        if let Poll::Ready(item) = Pin::new(&mut this.output).poll_next(cx) {
+            return Poll::Ready(item);
+        }
+        if this.df.poll_run_available(cx).is_pending() {
+            return Poll::Pending;
+        }
+        // The graph is idle and will wake `cx` on new events. Check for any output it produced.
+        Pin::new(&mut this.output).poll_next(cx)
+    }
+}
+
+/// A [`Sink`] into a graph's input, which runs the graph when flushed.
+///
+/// Sending never waits as the input is unbounded, but the graph is only run during
+/// [`Sink::poll_flush`] and [`Sink::poll_close`]. Errors if the graph dropped its receiver.
+pub struct HydroflowSink<T> {
+    df: Hydroflow,
+    input: UnboundedSender<T>,
+}
+impl<T> HydroflowSink<T> {
+    pub fn new(df: Hydroflow, input: UnboundedSender<T>) -> Self {
+        Self { df, input }
+    }
+
+    /// Returns the graph.
+    pub fn df(&mut self) -> &mut Hydroflow {
+        &mut self.df
+    }
+
+    /// Returns the graph and the input sender.
+    pub fn into_inner(self) -> (Hydroflow, UnboundedSender<T>) {
+        (self.df, self.input)
+    }
+}
+impl<T> Sink<T> for HydroflowSink<T> {
+    type Error = SendError<T>;
+
+    fn poll_ready(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: T) -> Result<(), Self::Error> {
+        self.get_mut().input.send(item)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.get_mut().df.poll_run_available(cx).map(Ok)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.poll_flush(cx)
+    }
+}
@@ -1,4 +1,5 @@
 use std::any::Any;
+use std::cell::Cell;
 use std::marker::PhantomData;
 
 use std::future::Future;
@@ -8,7 +9,7 @@ use tokio::task::JoinHandle;
 
 use super::graph::{HandoffData, StateData};
 use super::state::StateHandle;
-use super::{StateId, SubgraphId};
+use super::{HandoffId, StateId, SubgraphId};
 
 /// The main state of the Hydroflow instance, which is provided as a reference
 /// to each operator as it is run.
@@ -31,6 +32,10 @@ pub struct Context {
     /// not being forwarded to a running operator, this field is (mostly)
     /// meaningless.
     pub(crate) subgraph_id: SubgraphId,
+    /// Output handoffs of the currently running subgraph, lent by the scheduler while it runs.
+    pub(crate) subgraph_succs: Vec<HandoffId>,
+    /// Set if the currently running subgraph called [`Self::block_until_woken`].
+    pub(crate) subgraph_blocked: Cell<bool>,
 
     /// Join handles for spawned tasks.
     pub(crate) task_join_handles: Vec<JoinHandle<()>>,
@@ -46,11 +51,34 @@ impl Context {
         self.current_stratum
     }
 
+    /// Gets the ID of the currently running subgraph.
+    pub fn current_subgraph(&self) -> SubgraphId {
+        self.subgraph_id
+    }
+
     /// Schedules a subgraph.
     pub fn schedule_subgraph(&self, sg_id: SubgraphId) {
         self.event_queue_send.send(sg_id).unwrap()
     }
 
+    /// Tells the scheduler that the current subgraph cannot accept more input, for example because
+    /// an async sink is still busy. The subgraph will not run again, and so will not poll its
+    /// sources, until it is woken through its [`Self::waker`] or [`Self::schedule_subgraph`].
+    pub fn block_until_woken(&self) {
+        self.subgraph_blocked.set(true);
+    }
+
+    /// Returns true if the current subgraph should stop pulling from its sources: it called
+    /// [`Self::block_until_woken`] or one of its output handoffs is full.
+    pub fn is_blocked(&self) -> bool {
+        self.subgraph_blocked.get()
+            || self.subgraph_succs.iter().any(|&handoff_id| {
+                let handoff = &self.handoffs[handoff_id.0];
+                // Don't block on a full handoff that only this subgraph can drain.
+                handoff.handoff.is_full() && !handoff.succs.contains(&self.subgraph_id)
+            })
+    }
+
     /// Returns a `Waker` for interacting with async Rust.
     pub fn waker(&self) -> std::task::Waker {
         use futures::task::ArcWake;
@@ -0,0 +1,765 @@
+//! Builds graphs at runtime from named operators, without the surface syntax macro.
+//!
+//! The graph is checked and partitioned into subgraphs the same way as `hydroflow_syntax!`, but
+//! each operator runs dynamically on [`Value`]s with boxed closures. Only some operators are
+//! supported, see [`DYN_OPERATORS`]. Persistence lifetimes are given with
+//! [`DynGraphBuilder::add_operator_with_persistence`] and default to the same as in the surface
+//! syntax.
+//!
+//! Requires the `dyn_graph` feature.
+//!
+//! ```rust
+//! use hydroflow::scheduled::dyn_graph::{DynArg, DynGraphBuilder, Value};
+//!
+//! let mut builder = DynGraphBuilder::new();
+//! let source = builder.add_operator(
+//!     "source_iter",
+//!     vec![DynArg::Values(vec![1.into(), 2.into(), 3.into()])],
+//! );
+//! let double = builder.add_operator(
+//!     "map",
+//!     vec![DynArg::map(|v| match v {
+//!         Value::Int(x) => Value::Int(2 * x),
+//!         other => other,
+//!     })],
+//! );
+//! let print = builder.add_operator("for_each", vec![DynArg::for_each(|v| println!("{:?}", v))]);
+//! builder.add_edge(source, double);
+//! builder.add_edge(double, print);
+//!
+//! let mut df = builder.build().unwrap();
+//! df.run_available();
+//! ```
+
+use std::collections::{HashMap, HashSet};
+use std::pin::Pin;
+use std::task::Poll;
+
+use futures::Stream;
+use hydroflow_lang::diagnostic::{Diagnostic, Level};
+use hydroflow_lang::graph::flat_graph::FlatGraph;
+use hydroflow_lang::graph::partitioned_graph::PartitionedGraph;
+use hydroflow_lang::graph::{GraphEdgeId, GraphNodeId, Node, PortIndexValue};
+use hydroflow_lang::parse::HfCode;
+use proc_macro2::{Ident, Literal, Span, TokenStream};
+use quote::{quote, ToTokens};
+use serde::{Deserialize, Serialize};
+use slotmap::SecondaryMap;
+use syn::{GenericArgument, Lifetime};
+
+pub use hydroflow_lang::graph::ops::Persistence;
+
+use super::context::Context;
+use super::graph::Hydroflow;
+use super::handoff::VecHandoff;
+use super::port::{RecvCtx, RecvPort, SendCtx, SendPort};
+use crate::lang::collections::Iter;
+
+/// Operators which can be used in a [`DynGraphBuilder`].
+pub const DYN_OPERATORS: &[&str] = &[
+    "difference",
+    "filter",
+    "filter_map",
+    "flat_map",
+    "for_each",
+    "identity",
+    "inspect",
+    "join",
+    "map",
+    "merge",
+    "next_stratum",
+    "next_tick",
+    "null",
+    "source_iter",
+    "source_stream",
+    "tee",
+    "unique",
+];
+
+/// Dynamically typed items which flow through a [`DynGraphBuilder`] graph.
+///
+/// Operators which use pairs, such as `join()`, expect two-element tuples. `join()` outputs
+/// `Tuple([key, Tuple([v1, v2])])`, like `(key, (v1, v2))` in the surface syntax.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Value {
+    Unit,
+    Bool(bool),
+    Int(i64),
+    Str(String),
+    Tuple(Vec<Value>),
+}
+impl From<()> for Value {
+    fn from(_: ()) -> Self {
+        Self::Unit
+    }
+}
+impl From<bool> for Value {
+    fn from(value: bool) -> Self {
+        Self::Bool(value)
+    }
+}
+impl From<i64> for Value {
+    fn from(value: i64) -> Self {
+        Self::Int(value)
+    }
+}
+impl From<&str> for Value {
+    fn from(value: &str) -> Self {
+        Self::Str(value.to_owned())
+    }
+}
+impl From<String> for Value {
+    fn from(value: String) -> Self {
+        Self::Str(value)
+    }
+}
+impl<A: Into<Value>, B: Into<Value>> From<(A, B)> for Value {
+    fn from((a, b): (A, B)) -> Self {
+        Self::Tuple(vec![a.into(), b.into()])
+    }
+}
+
+/// An argument of an operator in a [`DynGraphBuilder`].
+pub enum DynArg {
+    /// Items for `source_iter()`.
+    Values(Vec<Value>),
+    /// Items for `source_stream()`.
+    Stream(Pin<Box<dyn Stream<Item = Value>>>),
+    /// Closure for `map()`.
+    Map(Box<dyn FnMut(Value) -> Value>),
+    /// Closure for `filter()`.
+    Filter(Box<dyn FnMut(&Value) -> bool>),
+    /// Closure for `filter_map()`.
+    FilterMap(Box<dyn FnMut(Value) -> Option<Value>>),
+    /// Closure for `flat_map()`.
+    FlatMap(Box<dyn FnMut(Value) -> Vec<Value>>),
+    /// Closure for `inspect()`.
+    Inspect(Box<dyn FnMut(&Value)>),
+    /// Closure for `for_each()`.
+    ForEach(Box<dyn FnMut(Value)>),
+}
+impl DynArg {
+    pub fn stream(stream: impl 'static + Stream<Item = Value>) -> Self {
+        Self::Stream(Box::pin(stream))
+    }
+    pub fn map(f: impl 'static + FnMut(Value) -> Value) -> Self {
+        Self::Map(Box::new(f))
+    }
+    pub fn filter(f: impl 'static + FnMut(&Value) -> bool) -> Self {
+        Self::Filter(Box::new(f))
+    }
+    pub fn filter_map(f: impl 'static + FnMut(Value) -> Option<Value>) -> Self {
+        Self::FilterMap(Box::new(f))
+    }
+    pub fn flat_map(f: impl 'static + FnMut(Value) -> Vec<Value>) -> Self {
+        Self::FlatMap(Box::new(f))
+    }
+    pub fn inspect(f: impl 'static + FnMut(&Value)) -> Self {
+        Self::Inspect(Box::new(f))
+    }
+    pub fn for_each(f: impl 'static + FnMut(Value)) -> Self {
+        Self::ForEach(Box::new(f))
+    }
+}
+
+/// A port of an operator in a [`DynGraphBuilder`], like `[0]` or `[pos]` in the surface syntax.
+#[derive(Clone, Debug)]
+pub enum DynPort {
+    Elided,
+    Index(usize),
+    Name(String),
+}
+impl From<usize> for DynPort {
+    fn from(index: usize) -> Self {
+        Self::Index(index)
+    }
+}
+impl From<&str> for DynPort {
+    fn from(name: &str) -> Self {
+        Self::Name(name.to_owned())
+    }
+}
+
+/// ID of an operator in a [`DynGraphBuilder`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct DynNodeId(usize);
+
+/// Builds a [`Hydroflow`] graph at runtime, see the [module docs](self).
+#[derive(Default)]
+pub struct DynGraphBuilder {
+    operators: Vec<(String, Vec<Persistence>, Vec<DynArg>)>,
+    edges: Vec<(DynNodeId, DynPort, DynNodeId, DynPort)>,
+}
+impl DynGraphBuilder {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Adds the operator `name` with `args`, e.g. `"map"` with a [`DynArg::Map`].
+    pub fn add_operator(&mut self, name: &str, args: Vec<DynArg>) -> DynNodeId {
+        self.add_operator_with_persistence(name, Vec::new(), args)
+    }
+
+    /// Adds the operator `name` with persistence lifetimes and `args`, e.g. `"join"` with
+    /// `[Persistence::Tick, Persistence::Static]` for `join::<'tick, 'static>()`.
+    pub fn add_operator_with_persistence(
+        &mut self,
+        name: &str,
+        persistence: Vec<Persistence>,
+        args: Vec<DynArg>,
+    ) -> DynNodeId {
+        self.operators.push((name.to_owned(), persistence, args));
+        DynNodeId(self.operators.len() - 1)
+    }
+
+    /// Connects the output of `src` to the input of `dst`, like `src -> dst`.
+    pub fn add_edge(&mut self, src: DynNodeId, dst: DynNodeId) {
+        self.add_edge_with_ports(src, DynPort::Elided, dst, DynPort::Elided);
+    }
+
+    /// Connects the output port of `src` to the input port of `dst`, like
+    /// `src[src_port] -> [dst_port]dst`.
+    pub fn add_edge_with_ports(
+        &mut self,
+        src: DynNodeId,
+        src_port: impl Into<DynPort>,
+        dst: DynNodeId,
+        dst_port: impl Into<DynPort>,
+    ) {
+        self.edges
+            .push((src, src_port.into(), dst, dst_port.into()));
+    }
+
+    /// Checks and partitions the graph, and builds it into a runnable [`Hydroflow`] instance.
+    ///
+    /// Returns the diagnostics if there are any errors. Diagnostic spans are meaningless outside
+    /// of a macro, so messages from this builder name the operator.
+    pub fn build(self) -> Result<Hydroflow, Vec<Diagnostic>> {
+        let Self { operators, edges } = self;
+
+        let mut diagnostics = Vec::new();
+        for (name, _persistence, _args) in operators.iter() {
+            if !DYN_OPERATORS.contains(&&**name) {
+                diagnostics.push(Diagnostic::spanned(
+                    Span::call_site(),
+                    Level::Error,
+                    format!("Operator `{}` is not supported in dynamic graphs.", name),
+                ));
+            }
+        }
+        for (src, src_port, dst, dst_port) in edges.iter() {
+            for node_id in [src, dst] {
+                if operators.len() <= node_id.0 {
+                    diagnostics.push(Diagnostic::spanned(
+                        Span::call_site(),
+                        Level::Error,
+                        format!("Edge uses `{:?}`, which is not from this builder.", node_id),
+                    ));
+                }
+            }
+            for port in [src_port, dst_port] {
+                if let DynPort::Name(name) = port {
+                    if syn::parse_str::<Ident>(name).is_err() {
+                        diagnostics.push(Diagnostic::spanned(
+                            Span::call_site(),
+                            Level::Error,
+                            format!("Port name `{}` is not a valid identifier.", name),
+                        ));
+                    }
+                }
+            }
+        }
+        if !diagnostics.is_empty() {
+            return Err(diagnostics);
+        }
+
+        // Write the graph as surface syntax, with a placeholder for each argument.
+        let node_idents: Vec<Ident> = (0..operators.len())
+            .map(|idx| Ident::new(&*format!("n{}", idx), Span::call_site()))
+            .collect();
+        // Index of each operator by the name of its node, to match arguments back to nodes.
+        let node_indices: HashMap<Ident, usize> = node_idents
+            .iter()
+            .cloned()
+            .enumerate()
+            .map(|(idx, ident)| (ident, idx))
+            .collect();
+        let node_stmts =
+            operators
+                .iter()
+                .zip(&node_idents)
+                .map(|((name, persistence, args), ident)| {
+                    let name = Ident::new(name, Span::call_site());
+                    let generics = (!persistence.is_empty()).then(|| {
+                        let lifetimes = persistence.iter().map(|&persistence| {
+                            let name = match persistence {
+                                Persistence::Tick => "'tick",
+                                Persistence::Static => "'static",
+                            };
+                            Lifetime::new(name, Span::call_site())
+                        });
+                        quote! { ::<#( #lifetimes ),*> }
+                    });
+                    let args = args.iter().map(|_| quote! { __hydroflow_dyn_arg });
+                    quote! { #ident = #name #generics( #( #args ),* ); }
+                });
+        let port_tokens = |port: &DynPort| match port {
+            DynPort::Elided => TokenStream::new(),
+            &DynPort::Index(index) => {
+                let index = Literal::usize_unsuffixed(index);
+                quote! { [#index] }
+            }
+            DynPort::Name(name) => {
+                let name = Ident::new(name, Span::call_site());
+                quote! { [#name] }
+            }
+        };
+        let edge_stmts = edges.iter().map(|(src, src_port, dst, dst_port)| {
+            let src = &node_idents[src.0];
+            let dst = &node_idents[dst.0];
+            let src_port = port_tokens(src_port);
+            let dst_port = port_tokens(dst_port);
+            quote! { #src #src_port -> #dst_port #dst; }
+        });
+        let hf_code: HfCode = syn::parse2(quote! {
+            #( #node_stmts )*
+            #( #edge_stmts )*
+        })
+        .map_err(|err| vec![err.into()])?;
+
+        let flat_graph = FlatGraph::from_hfcode(hf_code);
+        if flat_graph.diagnostics().iter().any(Diagnostic::is_error) {
+            return Err(flat_graph.diagnostics().to_vec());
+        }
+        let part_graph = flat_graph.into_partitioned_graph().map_err(|d| vec![d])?;
+
+        let mut args: Vec<Option<Vec<DynArg>>> = operators
+            .into_iter()
+            .map(|(_name, _persistence, args)| Some(args))
+            .collect();
+        instantiate(&part_graph, &node_indices, &mut args)
+    }
+}
+
+/// Helper: creates the handoffs and subgraphs of `part_graph` in a new [`Hydroflow`] instance.
+/// `args` are the arguments of each operator added to the builder, indexed by `node_indices`.
+fn instantiate(
+    part_graph: &PartitionedGraph,
+    node_indices: &HashMap<Ident, usize>,
+    args: &mut [Option<Vec<DynArg>>],
+) -> Result<Hydroflow, Vec<Diagnostic>> {
+    let mut df = Hydroflow::new();
+
+    let mut send_ports: SecondaryMap<GraphNodeId, SendPort<VecHandoff<Value>>> =
+        SecondaryMap::new();
+    let mut recv_ports: SecondaryMap<GraphNodeId, RecvPort<VecHandoff<Value>>> =
+        SecondaryMap::new();
+    for hoff_id in part_graph.handoff_ids() {
+        let (send_port, recv_port) = df.make_edge(format!("handoff {:?}", hoff_id));
+        send_ports.insert(hoff_id, send_port);
+        recv_ports.insert(hoff_id, recv_port);
+    }
+
+    let mut diagnostics = Vec::new();
+    for (subgraph_id, subgraph_nodes) in part_graph.subgraphs() {
+        let recv_hoffs = part_graph.subgraph_recv_handoffs(subgraph_id);
+        let send_hoffs = part_graph.subgraph_send_handoffs(subgraph_id);
+
+        // Where an output goes, given the node or handoff it is connected to.
+        let target = |node_id: GraphNodeId, input: usize| match part_graph.node(node_id) {
+            Node::Operator(_) => Target::Node {
+                node: subgraph_nodes
+                    .iter()
+                    .position(|&other| other == node_id)
+                    .unwrap(),
+                input,
+            },
+            Node::Handoff { .. } => {
+                Target::Handoff(send_hoffs.iter().position(|&h| h == node_id).unwrap())
+            }
+        };
+        // Input index of `edge_id` into `node_id`, determined by the edge's own input port.
+        let input_index = |node_id: GraphNodeId, edge_id: GraphEdgeId| {
+            part_graph
+                .node_predecessors(node_id)
+                .iter()
+                .position(|&(_port, other, _pred)| other == edge_id)
+                .unwrap()
+        };
+
+        let recv_targets = recv_hoffs
+            .iter()
+            .map(|&hoff_id| {
+                let (_port, edge_id, succ) = part_graph.node_successors(hoff_id)[0];
+                target(succ, input_index(succ, edge_id))
+            })
+            .collect();
+
+        let mut nodes = Vec::new();
+        for &node_id in subgraph_nodes {
+            let Node::Operator(operator) = part_graph.node(node_id) else {
+                unreachable!("Handoffs are not part of subgraphs.");
+            };
+            let name = operator.name_string();
+            // Implicitly inserted operators, such as `tee()`, have no name and no arguments.
+            let node_args = part_graph
+                .node_name(node_id)
+                .and_then(|ident| node_indices.get(ident))
+                .and_then(|&idx| args[idx].take())
+                .unwrap_or_default();
+            let persistence = operator
+                .type_arguments()
+                .into_iter()
+                .flatten()
+                .filter_map(|generic_arg| match generic_arg {
+                    GenericArgument::Lifetime(lifetime) if lifetime.ident == "static" => {
+                        Some(Persistence::Static)
+                    }
+                    GenericArgument::Lifetime(_) => Some(Persistence::Tick),
+                    _ => None,
+                })
+                .collect::<Vec<_>>();
+            let preds = part_graph.node_predecessors(node_id);
+            let op = match DynOp::new(&name, &persistence, node_args, &preds) {
+                Ok(op) => op,
+                Err(message) => {
+                    diagnostics.push(Diagnostic::spanned(
+                        Span::call_site(),
+                        Level::Error,
+                        message,
+                    ));
+                    continue;
+                }
+            };
+            let outputs = part_graph
+                .node_successors(node_id)
+                .into_iter()
+                .map(|(_port, edge_id, succ)| target(succ, input_index(succ, edge_id)))
+                .collect();
+            nodes.push(DynNode {
+                op,
+                num_inputs: preds.len(),
+                outputs,
+            });
+        }
+
+        let mut subgraph = DynSubgraph {
+            nodes,
+            recv_targets,
+        };
+        df.add_subgraph_stratified_n_m(
+            format!("Subgraph {:?}", subgraph_id),
+            part_graph.subgraph_stratum(subgraph_id),
+            recv_hoffs
+                .iter()
+                .map(|&hoff_id| recv_ports.remove(hoff_id).unwrap())
+                .collect(),
+            send_hoffs
+                .iter()
+                .map(|&hoff_id| send_ports.remove(hoff_id).unwrap())
+                .collect(),
+            move |context, recv_ctxs, send_ctxs| subgraph.run(context, recv_ctxs, send_ctxs),
+        );
+    }
+
+    if diagnostics.is_empty() {
+        Ok(df)
+    } else {
+        Err(diagnostics)
+    }
+}
+
+/// Where items go: an input of a node in the same subgraph, or a send handoff.
+enum Target {
+    Node { node: usize, input: usize },
+    Handoff(usize),
+}
+
+/// An operator within a [`DynSubgraph`].
+struct DynNode {
+    op: DynOp,
+    num_inputs: usize,
+    /// Sorted by output port.
+    outputs: Vec<Target>,
+}
+
+/// A subgraph, which runs its nodes in topological order on batches of items.
+struct DynSubgraph {
+    /// In topological order.
+    nodes: Vec<DynNode>,
+    /// For each recv handoff, where its items go.
+    recv_targets: Vec<Target>,
+}
+impl DynSubgraph {
+    fn run(
+        &mut self,
+        context: &Context,
+        recv_ctxs: &[&RecvCtx<VecHandoff<Value>>],
+        send_ctxs: &[&SendCtx<VecHandoff<Value>>],
+    ) {
+        let mut inputs: Vec<Vec<Vec<Value>>> = self
+            .nodes
+            .iter()
+            .map(|node| vec![Vec::new(); node.num_inputs])
+            .collect();
+        let route =
+            |inputs: &mut Vec<Vec<Vec<Value>>>, target: &Target, items: Vec<Value>| match *target {
+                Target::Node { node, input } => inputs[node][input].extend(items),
+                Target::Handoff(idx) => {
+                    send_ctxs[idx].give(Iter(items.into_iter()));
+                }
+            };
+
+        for (target, recv_ctx) in self.recv_targets.iter().zip(recv_ctxs) {
+            route(&mut inputs, target, recv_ctx.take_inner().into());
+        }
+        for (idx, node) in self.nodes.iter_mut().enumerate() {
+            let node_inputs = std::mem::take(&mut inputs[idx]);
+            let outputs = node.op.run(context, node_inputs, node.outputs.len());
+            for (target, items) in node.outputs.iter().zip(outputs) {
+                route(&mut inputs, target, items);
+            }
+        }
+    }
+}
+
+/// Operator state, cleared at the start of each tick if its persistence is `'tick`.
+struct PersistState<T> {
+    persistence: Persistence,
+    tick: usize,
+    state: T,
+}
+impl<T: Default> PersistState<T> {
+    fn new(persistence: Persistence) -> Self {
+        Self {
+            persistence,
+            tick: 0,
+            state: Default::default(),
+        }
+    }
+    fn get(&mut self, context: &Context) -> &mut T {
+        if Persistence::Tick == self.persistence && self.tick != context.current_tick() {
+            self.tick = context.current_tick();
+            self.state = Default::default();
+        }
+        &mut self.state
+    }
+}
+
+/// The runtime implementation of an operator.
+enum DynOp {
+    SourceIter(Option<Vec<Value>>),
+    SourceStream(Pin<Box<dyn Stream<Item = Value>>>),
+    Map(Box<dyn FnMut(Value) -> Value>),
+    Filter(Box<dyn FnMut(&Value) -> bool>),
+    FilterMap(Box<dyn FnMut(Value) -> Option<Value>>),
+    FlatMap(Box<dyn FnMut(Value) -> Vec<Value>>),
+    Inspect(Box<dyn FnMut(&Value)>),
+    ForEach(Box<dyn FnMut(Value)>),
+    /// `identity()`, `merge()`, `tee()`, `next_stratum()`, and `next_tick()`, delays are handled by
+    /// the handoffs.
+    PassThrough,
+    Null,
+    Unique(PersistState<HashSet<Value>>),
+    Join([PersistState<HashMap<Value, Vec<Value>>>; 2]),
+    Difference {
+        /// Index of the `pos` input.
+        pos: usize,
+        neg_state: PersistState<HashSet<Value>>,
+    },
+}
+impl DynOp {
+    /// Creates the operator `name` from its `persistence` lifetimes and `args`, or returns an error
+    /// message.
+    fn new(
+        name: &str,
+        persistence: &[Persistence],
+        args: Vec<DynArg>,
+        preds: &[(&PortIndexValue, GraphEdgeId, GraphNodeId)],
+    ) -> Result<Self, String> {
+        let mut args = args.into_iter();
+        let op = match (name, args.next()) {
+            ("source_iter", Some(DynArg::Values(values))) => Self::SourceIter(Some(values)),
+            ("source_stream", Some(DynArg::Stream(stream))) => Self::SourceStream(stream),
+            ("map", Some(DynArg::Map(f))) => Self::Map(f),
+            ("filter", Some(DynArg::Filter(f))) => Self::Filter(f),
+            ("filter_map", Some(DynArg::FilterMap(f))) => Self::FilterMap(f),
+            ("flat_map", Some(DynArg::FlatMap(f))) => Self::FlatMap(f),
+            ("inspect", Some(DynArg::Inspect(f))) => Self::Inspect(f),
+            ("for_each", Some(DynArg::ForEach(f))) => Self::ForEach(f),
+            ("identity" | "merge" | "tee" | "next_stratum" | "next_tick", None) => {
+                Self::PassThrough
+            }
+            ("null", None) => Self::Null,
+            ("unique", None) => match *persistence {
+                [] => Self::Unique(PersistState::new(Persistence::Static)),
+                [a] => Self::Unique(PersistState::new(a)),
+                _ => {
+                    return Err(
+                        "Operator `unique` expects zero or one persistence lifetimes.".to_owned(),
+                    )
+                }
+            },
+            ("join", None) => {
+                let [lhs, rhs] = match *persistence {
+                    [] => [Persistence::Static, Persistence::Static],
+                    [a] => [a, a],
+                    [a, b] => [a, b],
+                    _ => {
+                        return Err(
+                            "Operator `join` expects zero, one, or two persistence lifetimes."
+                                .to_owned(),
+                        )
+                    }
+                };
+                Self::Join([PersistState::new(lhs), PersistState::new(rhs)])
+            }
+            ("difference", None) => Self::Difference {
+                pos: preds
+                    .iter()
+                    .position(|(port, _, _)| {
+                        matches!(port, PortIndexValue::Path(path) if "pos" == path.to_token_stream().to_string())
+                    })
+                    .ok_or_else(|| "`difference` must have a `[pos]` input.".to_owned())?,
+                neg_state: match *persistence {
+                    [] | [Persistence::Tick] | [Persistence::Tick, Persistence::Tick] => {
+                        PersistState::new(Persistence::Tick)
+                    }
+                    [Persistence::Tick, Persistence::Static] => {
+                        PersistState::new(Persistence::Static)
+                    }
+                    [_, _, _, ..] => {
+                        return Err(
+                            "Operator `difference` expects zero, one, or two persistence lifetimes."
+                                .to_owned(),
+                        )
+                    }
+                    _ => {
+                        return Err(
+                            "Operator `difference` only supports `'tick` persistence of its `pos` input in dynamic graphs."
+                                .to_owned(),
+                        )
+                    }
+                },
+            },
+            (name, _) => {
+                return Err(format!(
+                    "Operator `{}` was given the wrong kind of `DynArg`.",
+                    name
+                ))
+            }
+        };
+        Ok(op)
+    }
+
+    /// Runs the operator on a batch of `inputs`, one `Vec` per input, returning one `Vec` per
+    /// output.
+    fn run(
+        &mut self,
+        context: &Context,
+        mut inputs: Vec<Vec<Value>>,
+        num_outputs: usize,
+    ) -> Vec<Vec<Value>> {
+        let output = match self {
+            Self::SourceIter(values) => values.take().unwrap_or_default(),
+            Self::SourceStream(stream) => {
+                let waker = context.waker();
+                let mut cx = std::task::Context::from_waker(&waker);
+                let mut output = Vec::new();
+                // Backpressure: stop polling while downstream is full or busy.
+                while !context.is_blocked() {
+                    match stream.as_mut().poll_next(&mut cx) {
+                        Poll::Ready(Some(item)) => output.push(item),
+                        Poll::Ready(None) | Poll::Pending => break,
+                    }
+                }
+                output
+            }
+            Self::Map(f) => inputs.into_iter().flatten().map(f).collect(),
+            Self::Filter(f) => inputs
+                .into_iter()
+                .flatten()
+                .filter(|item| f(item))
+                .collect(),
+            Self::FilterMap(f) => inputs.into_iter().flatten().filter_map(f).collect(),
+            Self::FlatMap(f) => inputs.into_iter().flatten().flat_map(f).collect(),
+            Self::Inspect(f) => inputs
+                .into_iter()
+                .flatten()
+                .inspect(|item| f(item))
+                .collect(),
+            Self::ForEach(f) => {
+                inputs.into_iter().flatten().for_each(f);
+                Vec::new()
+            }
+            Self::PassThrough => inputs.into_iter().flatten().collect(),
+            Self::Null => Vec::new(),
+            Self::Unique(state) => {
+                let seen = state.get(context);
+                inputs
+                    .into_iter()
+                    .flatten()
+                    .filter(|item| seen.insert(item.clone()))
+                    .collect()
+            }
+            Self::Join(states) => {
+                let [lhs, rhs] = states;
+                let halves = [lhs.get(context), rhs.get(context)];
+                let mut output = Vec::new();
+                for (side, input) in inputs.into_iter().enumerate() {
+                    for item in input {
+                        let (key, value) = into_pair(item, "join");
+                        let values = halves[side].entry(key.clone()).or_default();
+                        if values.contains(&value) {
+                            continue;
+                        }
+                        values.push(value.clone());
+                        for other in halves[1 - side].get(&key).into_iter().flatten() {
+                            let (v0, v1) = if 0 == side {
+                                (value.clone(), other.clone())
+                            } else {
+                                (other.clone(), value.clone())
+                            };
+                            output
+                                .push(Value::Tuple(vec![key.clone(), Value::Tuple(vec![v0, v1])]));
+                        }
+                    }
+                }
+                output
+            }
+            Self::Difference { pos, neg_state } => {
+                let neg = neg_state.get(context);
+                let pos_items = std::mem::take(&mut inputs[*pos]);
+                neg.extend(inputs.into_iter().flatten());
+                pos_items
+                    .into_iter()
+                    .filter(|item| !neg.contains(item))
+                    .collect()
+            }
+        };
+        fan_out(output, num_outputs)
+    }
+}
+
+/// Helper: splits a two-element [`Value::Tuple`] into its elements, panicking otherwise.
+fn into_pair(item: Value, op_name: &str) -> (Value, Value) {
+    match item {
+        Value::Tuple(elems) if 2 == elems.len() => {
+            let mut elems = elems.into_iter();
+            (elems.next().unwrap(), elems.next().unwrap())
+        }
+        other => panic!(
+            "`{}()` expects items to be pairs, `Value::Tuple([key, value])`, got `{:?}`.",
+            op_name, other
+        ),
+    }
+}
+
+/// Helper: copies `items` into each of `num_outputs` outputs.
+fn fan_out(items: Vec<Value>, num_outputs: usize) -> Vec<Vec<Value>> {
+    let mut outputs = vec![items; num_outputs.min(1)];
+    for _ in 1..num_outputs {
+        outputs.push(outputs[0].clone());
+    }
+    outputs
+}
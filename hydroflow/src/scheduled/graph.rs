@@ -1,10 +1,11 @@
 use std::any::Any;
 use std::borrow::Cow;
 use std::cell::Cell;
-use std::collections::VecDeque;
 use std::future::Future;
 use std::marker::PhantomData;
 use std::num::NonZeroUsize;
+use std::task::Poll;
+use std::time::{Duration, Instant};
 
 use hydroflow_lang::graph::serde_graph::SerdeGraph;
 use ref_cast::RefCast;
@@ -16,6 +17,7 @@ use super::handoff::handoff_list::PortList;
 use super::handoff::{Handoff, HandoffMeta};
 use super::port::{RecvCtx, RecvPort, SendCtx, SendPort, RECV, SEND};
 use super::reactor::Reactor;
+use super::scheduler::{FifoScheduler, Priority, Scheduler};
 use super::state::StateHandle;
 use super::subgraph::Subgraph;
 use super::{HandoffId, SubgraphId};
@@ -25,17 +27,28 @@ pub struct Hydroflow {
     pub(super) subgraphs: Vec<SubgraphData>,
     pub(super) context: Context,
 
-    /// TODO(mingwei): separate scheduler into its own struct/trait?
-    /// Index is stratum, value is FIFO queue for that stratum.
-    stratum_queues: Vec<VecDeque<SubgraphId>>,
+    /// Index is stratum, value is the queue of scheduled subgraphs for that stratum.
+    stratum_queues: Vec<Box<dyn Scheduler>>,
+    /// Creates the queue for each stratum, see [`Self::set_scheduler`].
+    make_scheduler: fn() -> Box<dyn Scheduler>,
     event_queue_recv: UnboundedReceiver<SubgraphId>,
 
+    /// Limits for each [`Self::run_available`] or [`Self::run_tick`] call.
+    run_budget: RunBudget,
+    /// Subgraph runs left in the current call, if limited.
+    runs_left: Option<usize>,
+    /// When the current call must stop, if limited.
+    run_deadline: Option<Instant>,
+    /// Capacity of handoffs added by [`Self::make_edge`], see [`Self::set_handoff_capacity`].
+    handoff_capacity: Option<usize>,
+
     serde_graph: Option<SerdeGraph>,
 }
 impl Default for Hydroflow {
     fn default() -> Self {
         let (subgraphs, handoffs, states, task_join_handles) = Default::default();
-        let stratum_queues = vec![Default::default()]; // Always initialize stratum #0.
+        let make_scheduler: fn() -> Box<dyn Scheduler> = || Box::<FifoScheduler>::default();
+        let stratum_queues = vec![(make_scheduler)()]; // Always initialize stratum #0.
         let (event_queue_send, event_queue_recv) = mpsc::unbounded_channel();
         let context = Context {
             handoffs,
@@ -47,6 +60,8 @@ impl Default for Hydroflow {
             current_tick: 0,
 
             subgraph_id: SubgraphId(0),
+            subgraph_succs: Vec::new(),
+            subgraph_blocked: Cell::new(false),
 
             task_join_handles,
         };
@@ -55,9 +70,15 @@ impl Default for Hydroflow {
             context,
 
             stratum_queues,
+            make_scheduler,
 
             event_queue_recv,
 
+            run_budget: RunBudget::default(),
+            runs_left: None,
+            run_deadline: None,
+            handoff_capacity: None,
+
             serde_graph: None,
         }
     }
@@ -101,22 +122,60 @@ impl Hydroflow {
         self.context.current_stratum
     }
 
-    /// Runs the dataflow until the next tick begins.
+    /// Sets limits on how much work each [`Self::run_available`] or [`Self::run_tick`] call does
+    /// before returning, so that [`Self::run_async`] can yield to other tasks.
+    pub fn set_run_budget(&mut self, run_budget: RunBudget) {
+        self.run_budget = run_budget;
+    }
+
+    /// Runs the dataflow until the next tick begins, or the [`RunBudget`] is exhausted.
     pub fn run_tick(&mut self) {
+        self.start_budget();
         let tick = self.current_tick();
-        while self.next_stratum() && tick == self.current_tick() {
+        while !self.is_budget_exhausted() && self.next_stratum() && tick == self.current_tick() {
             self.run_stratum();
         }
+        self.end_budget();
     }
 
-    /// Runs the dataflow until no more work is immediately available.
-    /// If the dataflow contains loops this method may run forever.
+    /// Runs the dataflow until no more work is immediately available, or the [`RunBudget`] is
+    /// exhausted. Without a budget, if the dataflow contains loops this method may run forever.
     pub fn run_available(&mut self) {
+        self.start_budget();
         // While work is immediately available.
-        while self.next_stratum() {
+        while !self.is_budget_exhausted() && self.next_stratum() {
             // And do any work (this also receives events).
             self.run_stratum();
         }
+        self.end_budget();
+    }
+
+    /// Returns true if any subgraphs are scheduled to run, i.e. [`Self::run_available`] returned
+    /// early due to the [`RunBudget`].
+    pub fn has_available_work(&self) -> bool {
+        self.stratum_queues.iter().any(|queue| !queue.is_empty())
+    }
+
+    /// Starts tracking the budget for a `run_available` or `run_tick` call.
+    fn start_budget(&mut self) {
+        self.runs_left = self.run_budget.max_subgraph_runs;
+        self.run_deadline = self
+            .run_budget
+            .max_duration
+            .map(|duration| Instant::now() + duration);
+    }
+
+    /// Stops tracking the budget, so that `run_stratum` is unlimited when called directly.
+    fn end_budget(&mut self) {
+        self.runs_left = None;
+        self.run_deadline = None;
+    }
+
+    fn is_budget_exhausted(&self) -> bool {
+        Some(0) == self.runs_left
+            || self
+                .run_deadline
+                .map_or(false, |deadline| deadline <= Instant::now())
     }
 
     /// Runs the current stratum of the dataflow until no more work is immediately available.
@@ -124,14 +183,33 @@ impl Hydroflow {
         // Add any external jobs to ready queue.
         self.try_recv_events();
 
-        while let Some(sg_id) = self.stratum_queues[self.context.current_stratum].pop_front() {
+        while !self.is_budget_exhausted() {
+            let Some(sg_id) = self.stratum_queues[self.context.current_stratum].pop() else {
+                break;
+            };
             {
                 let sg_data = &mut self.subgraphs[sg_id.0];
                 // This must be true for the subgraph to be enqueued.
                 assert!(sg_data.is_scheduled.take());
 
                 self.context.subgraph_id = sg_id;
-                sg_data.subgraph.run(&mut self.context);
+                // Lend the subgraph's output handoffs to the context, for `Context::is_blocked()`.
+                std::mem::swap(&mut self.context.subgraph_succs, &mut sg_data.succs);
+                self.context.subgraph_blocked.set(false);
+
+                // Backpressure: wait for full output handoffs to drain before running.
+                let run = !self.context.is_blocked();
+                if run {
+                    sg_data.subgraph.run(&mut self.context);
+                    if let Some(runs_left) = &mut self.runs_left {
+                        *runs_left -= 1;
+                    }
+                }
+                sg_data.is_blocked.set(self.context.is_blocked());
+                std::mem::swap(&mut self.context.subgraph_succs, &mut sg_data.succs);
+                if !run {
+                    continue;
+                }
             }
 
             for &handoff_id in self.subgraphs[sg_id.0].succs.iter() {
@@ -139,12 +217,29 @@ impl Hydroflow {
                 if !handoff.handoff.is_bottom() {
                     for &succ_id in handoff.succs.iter() {
                         let succ_sg_data = &self.subgraphs[succ_id.0];
-                        if succ_sg_data.is_scheduled.get() {
-                            // Skip if task is already scheduled.
+                        if succ_sg_data.is_scheduled.get() || succ_sg_data.is_blocked.get() {
+                            // Skip if task is already scheduled, or blocked until it is woken.
                             continue;
                         }
                         succ_sg_data.is_scheduled.set(true);
-                        self.stratum_queues[succ_sg_data.stratum].push_back(succ_id);
+                        self.stratum_queues[succ_sg_data.stratum]
+                            .push(succ_id, succ_sg_data.priority);
+                    }
+                }
+            }
+
+            // Resume blocked predecessors of any handoffs which are no longer full.
+            for &handoff_id in self.subgraphs[sg_id.0].preds.iter() {
+                let handoff = &self.context.handoffs[handoff_id.0];
+                if !handoff.handoff.is_full() {
+                    for &pred_id in handoff.preds.iter() {
+                        let pred_sg_data = &self.subgraphs[pred_id.0];
+                        if pred_sg_data.is_blocked.replace(false)
+                            && !pred_sg_data.is_scheduled.replace(true)
+                        {
+                            self.stratum_queues[pred_sg_data.stratum]
+                                .push(pred_id, pred_sg_data.priority);
+                        }
                     }
                 }
             }
@@ -185,20 +280,30 @@ impl Hydroflow {
     pub fn run(&mut self) -> Option<!> {
         loop {
             self.run_tick();
-            self.recv_events()?;
+            if !self.has_available_work() {
+                self.recv_events()?;
+            }
         }
     }
 
     /// Runs the dataflow graph forever.
     ///
+    /// Yields to other tasks whenever the [`RunBudget`] is exhausted, so a busy graph does not
+    /// starve the rest of the runtime.
+    ///
     /// TODO(mingwei): Currently blockes forever, no notion of "completion."
     pub async fn run_async(&mut self) -> Option<!> {
         loop {
-            // Run any work which is immediately available.
+            // Run any work which is immediately available, within the budget.
             self.run_available();
-            // Only when there is absolutely no work available in any stratum.
-            // Do we yield to wait for more events.
-            self.recv_events_async().await;
+            if self.has_available_work() {
+                // Out of budget, let other tasks run before continuing.
+                tokio::task::yield_now().await;
+            } else {
+                // Only when there is absolutely no work available in any stratum.
+                // Do we yield to wait for more events.
+                self.recv_events_async().await;
+            }
         }
     }
 
@@ -208,24 +313,31 @@ impl Hydroflow {
     pub fn try_recv_events(&mut self) -> usize {
         let mut enqueued_count = 0;
         while let Ok(sg_id) = self.event_queue_recv.try_recv() {
-            let sg_data = &self.subgraphs[sg_id.0];
-            if !sg_data.is_scheduled.replace(true) {
-                self.stratum_queues[sg_data.stratum].push_back(sg_id);
+            if self.enqueue_event(sg_id) {
                 enqueued_count += 1;
             }
         }
         enqueued_count
     }
 
+    /// Enqueues a subgraph triggered by an external event, unblocking it. Returns false if it was
+    /// already scheduled.
+    fn enqueue_event(&mut self, sg_id: SubgraphId) -> bool {
+        let sg_data = &self.subgraphs[sg_id.0];
+        sg_data.is_blocked.set(false);
+        if sg_data.is_scheduled.replace(true) {
+            return false;
+        }
+        self.stratum_queues[sg_data.stratum].push(sg_id, sg_data.priority);
+        true
+    }
+
     /// Enqueues subgraphs triggered by external events, blocking until at
     /// least one subgraph is scheduled.
     pub fn recv_events(&mut self) -> Option<NonZeroUsize> {
         loop {
             let sg_id = self.event_queue_recv.blocking_recv()?;
-            let sg_data = &self.subgraphs[sg_id.0];
-            if !sg_data.is_scheduled.replace(true) {
-                self.stratum_queues[sg_data.stratum].push_back(sg_id);
-
+            if self.enqueue_event(sg_id) {
                 // Enqueue any other immediate events.
                 return Some(NonZeroUsize::new(self.try_recv_events() + 1).unwrap());
             }
@@ -237,16 +349,35 @@ impl Hydroflow {
     pub async fn recv_events_async(&mut self) -> Option<NonZeroUsize> {
         loop {
             let sg_id = self.event_queue_recv.recv().await?;
-            let sg_data = &self.subgraphs[sg_id.0];
-            if !sg_data.is_scheduled.replace(true) {
-                self.stratum_queues[sg_data.stratum].push_back(sg_id);
-
+            if self.enqueue_event(sg_id) {
                 // Enqueue any other immediate events.
                 return Some(NonZeroUsize::new(self.try_recv_events() + 1).unwrap());
             }
         }
     }
 
+    /// Drives the graph from a `poll` function, for use in async adapters such as
+    /// [`HydroflowStream`](super::adapter::HydroflowStream). Runs all available work, then
+    /// registers `cx` to be woken when external events arrive.
+    ///
+    /// Returns `Poll::Ready(())` once no work is available. Returns `Poll::Pending`, after waking
+    /// `cx`, if the [`RunBudget`] was exhausted, so the caller yields before continuing.
+    pub fn poll_run_available(&mut self, cx: &mut std::task::Context<'_>) -> Poll<()> {
+        loop {
+            self.run_available();
+            if self.has_available_work() {
+                cx.waker().wake_by_ref();
+                return Poll::Pending;
+            }
+            match self.event_queue_recv.poll_recv(cx) {
+                Poll::Ready(Some(sg_id)) => {
+                    self.enqueue_event(sg_id);
+                }
+                Poll::Ready(None) | Poll::Pending => return Poll::Ready(()),
+            }
+        }
+    }
+
     pub fn add_subgraph<Name, R, W, F>(
         &mut self,
         name: Name,
@@ -310,7 +441,7 @@ impl Hydroflow {
             true,
         ));
         self.init_stratum(stratum);
-        self.stratum_queues[stratum].push_back(sg_id);
+        self.stratum_queues[stratum].push(sg_id, Priority::default());
 
         sg_id
     }
@@ -405,7 +536,7 @@ impl Hydroflow {
             true,
         ));
         self.init_stratum(stratum);
-        self.stratum_queues[stratum].push_back(sg_id);
+        self.stratum_queues[stratum].push(sg_id, Priority::default());
 
         sg_id
     }
@@ -414,20 +545,37 @@ impl Hydroflow {
     fn init_stratum(&mut self, stratum: usize) {
         if self.stratum_queues.len() <= stratum {
             self.stratum_queues
-                .resize_with(stratum + 1, Default::default);
+                .resize_with(stratum + 1, self.make_scheduler);
         }
     }
 
     /// Creates a handoff edge and returns the corresponding send and receive ports.
     pub fn make_edge<Name, H>(&mut self, name: Name) -> (SendPort<H>, RecvPort<H>)
+    where
+        Name: Into<Cow<'static, str>>,
+        H: 'static + Handoff,
+    {
+        let handoff = H::default();
+        if self.handoff_capacity.is_some() {
+            handoff.set_capacity(self.handoff_capacity);
+        }
+        self.make_edge_with_handoff(name, handoff)
+    }
+
+    /// Creates a handoff edge from an existing handoff, e.g. a [`VecHandoff::bounded`](super::handoff::VecHandoff::bounded)
+    /// handoff, and returns the corresponding send and receive ports.
+    pub fn make_edge_with_handoff<Name, H>(
+        &mut self,
+        name: Name,
+        handoff: H,
+    ) -> (SendPort<H>, RecvPort<H>)
     where
         Name: Into<Cow<'static, str>>,
         H: 'static + Handoff,
     {
         let handoff_id = HandoffId(self.context.handoffs.len());
 
-        // Create and insert handoff.
-        let handoff = H::default();
+        // Insert handoff.
         self.context
             .handoffs
             .push(HandoffData::new(name.into(), handoff));
@@ -444,6 +592,38 @@ impl Hydroflow {
         (input_port, output_port)
     }
 
+    /// Sets the scheduling policy used to pick the next subgraph to run within each stratum,
+    /// e.g. [`PriorityScheduler`](super::scheduler::PriorityScheduler). Defaults to [`FifoScheduler`].
+    pub fn set_scheduler<S>(&mut self)
+    where
+        S: 'static + Scheduler + Default,
+    {
+        self.make_scheduler = || Box::<S>::default();
+        for queue in self.stratum_queues.iter_mut() {
+            let mut new_queue = (self.make_scheduler)();
+            while let Some(sg_id) = queue.pop() {
+                new_queue.push(sg_id, self.subgraphs[sg_id.0].priority);
+            }
+            *queue = new_queue;
+        }
+    }
+
+    /// Sets the [`Priority`] of a subgraph, used by [`PriorityScheduler`](super::scheduler::PriorityScheduler).
+    /// Takes effect the next time the subgraph is scheduled.
+    pub fn set_subgraph_priority(&mut self, sg_id: SubgraphId, priority: Priority) {
+        self.subgraphs[sg_id.0].priority = priority;
+    }
+
+    /// Sets the capacity of every handoff in the graph, `None` for unbounded, including handoffs
+    /// added later by [`Self::make_edge`]. Subgraphs with a full output handoff are not run until
+    /// it drains, which pushes back on upstream sources.
+    pub fn set_handoff_capacity(&mut self, capacity: Option<usize>) {
+        self.handoff_capacity = capacity;
+        for handoff_data in self.context.handoffs.iter() {
+            handoff_data.handoff.set_capacity(capacity);
+        }
+    }
+
     pub fn add_state<T>(&mut self, state: T) -> StateHandle<T>
     where
         T: Any,
@@ -481,6 +661,17 @@ impl Drop for Hydroflow {
     }
 }
 
+/// Limits on the work done by each [`Hydroflow::run_available`] or [`Hydroflow::run_tick`] call.
+/// Unlimited by default.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RunBudget {
+    /// Maximum number of subgraphs to run.
+    pub max_subgraph_runs: Option<usize>,
+    /// Maximum wall-clock time. Checked between subgraph runs, so a single long-running subgraph
+    /// may exceed it.
+    pub max_duration: Option<Duration>,
+}
+
 /// A handoff and its input and output [SubgraphId]s.
 ///
 /// Internal use: used to track the hydroflow graph structure.
@@ -525,9 +716,10 @@ pub(super) struct SubgraphData {
     pub(super) name: Cow<'static, str>,
     /// This subgraph's stratum number.
     pub(super) stratum: usize,
+    /// This subgraph's priority for the [`Scheduler`].
+    priority: Priority,
     /// The actual execution code of the subgraph.
     subgraph: Box<dyn Subgraph>,
-    #[allow(dead_code)]
     preds: Vec<HandoffId>,
     succs: Vec<HandoffId>,
 
@@ -536,6 +728,9 @@ pub(super) struct SubgraphData {
     /// `Self::succs`, as all `SubgraphData` are owned by the same vec
     /// `Hydroflow::subgraphs`.
     is_scheduled: Cell<bool>,
+    /// If this subgraph is waiting for a full output handoff to drain, or to be woken after
+    /// calling [`Context::block_until_woken`].
+    is_blocked: Cell<bool>,
 }
 impl SubgraphData {
     pub fn new(
@@ -549,10 +744,12 @@ impl SubgraphData {
         Self {
             name,
             stratum,
+            priority: Priority::default(),
             subgraph: Box::new(subgraph),
             preds,
             succs,
             is_scheduled: Cell::new(is_scheduled),
+            is_blocked: Cell::new(false),
         }
     }
 }
@@ -22,6 +22,16 @@ pub trait HandoffMeta: Any {
 
     // TODO(justin): more fine-grained info here.
     fn is_bottom(&self) -> bool;
+
+    /// If the handoff has reached its capacity. The scheduler will not run the handoff's
+    /// predecessors until it drains. Unbounded handoffs are never full.
+    fn is_full(&self) -> bool {
+        false
+    }
+
+    /// Sets the maximum number of items the handoff will hold, `None` for unbounded. Handoffs which
+    /// cannot be bounded ignore this.
+    fn set_capacity(&self, _capacity: Option<usize>) {}
 }
 
 pub trait Handoff: Default + HandoffMeta {
@@ -1,20 +1,36 @@
 use std::any::Any;
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 use std::collections::VecDeque;
 use std::rc::Rc;
 
 use crate::lang::collections::Iter;
 
-use super::{CanReceive, Handoff, HandoffMeta};
+use super::{CanReceive, Handoff, HandoffMeta, TryCanReceive};
 
 /**
  * A [VecDeque]-based FIFO handoff.
+ *
+ * Unbounded by default. A bounded handoff rejects items from [`TryCanReceive::try_give`] once it
+ * is full, while [`CanReceive::give`] still accepts them so a running subgraph never loses data;
+ * the scheduler then holds back the handoff's predecessors until it drains.
  */
 pub struct VecHandoff<T>
 where
     T: 'static,
 {
     pub(crate) deque: Rc<RefCell<VecDeque<T>>>,
+    capacity: Cell<Option<usize>>,
+}
+impl<T> VecHandoff<T>
+where
+    T: 'static,
+{
+    /// Creates a handoff which holds at most `capacity` items.
+    pub fn bounded(capacity: usize) -> Self {
+        let handoff = Self::default();
+        handoff.set_capacity(Some(capacity));
+        handoff
+    }
 }
 impl<T> Default for VecHandoff<T>
 where
@@ -23,6 +39,7 @@ where
     fn default() -> Self {
         Self {
             deque: Default::default(),
+            capacity: Cell::new(None),
         }
     }
 }
@@ -42,6 +59,17 @@ impl<T> CanReceive<Option<T>> for VecHandoff<T> {
         None
     }
 }
+impl<T> TryCanReceive<Option<T>> for VecHandoff<T> {
+    fn try_give(&self, mut item: Option<T>) -> Result<Option<T>, Option<T>> {
+        if item.is_some() && self.is_full() {
+            return Err(item);
+        }
+        if let Some(item) = item.take() {
+            (*self.deque).borrow_mut().push_back(item)
+        }
+        Ok(None)
+    }
+}
 impl<T, I> CanReceive<Iter<I>> for VecHandoff<T>
 where
     I: Iterator<Item = T>,
@@ -66,6 +94,17 @@ impl<T> HandoffMeta for VecHandoff<T> {
     fn is_bottom(&self) -> bool {
         (*self.deque).borrow_mut().is_empty()
     }
+
+    fn is_full(&self) -> bool {
+        self.capacity
+            .get()
+            .map_or(false, |capacity| capacity <= (*self.deque).borrow().len())
+    }
+
+    fn set_capacity(&self, capacity: Option<usize>) {
+        assert_ne!(Some(0), capacity, "Handoff capacity must be positive.");
+        self.capacity.set(capacity);
+    }
 }
 
 impl<H> HandoffMeta for Rc<RefCell<H>>
@@ -79,4 +118,12 @@ where
     fn is_bottom(&self) -> bool {
         self.borrow().is_bottom()
     }
+
+    fn is_full(&self) -> bool {
+        self.borrow().is_full()
+    }
+
+    fn set_capacity(&self, capacity: Option<usize>) {
+        self.borrow().set_capacity(capacity)
+    }
 }
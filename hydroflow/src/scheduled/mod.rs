@@ -1,7 +1,10 @@
 use serde::Serialize;
 use std::fmt::{Display, Formatter, Result};
 
+pub mod adapter;
 pub mod context;
+#[cfg(feature = "dyn_graph")]
+pub mod dyn_graph;
 pub mod graph;
 pub mod graph_ext;
 pub mod handoff;
@@ -10,13 +13,14 @@ pub mod net;
 pub mod port;
 pub mod query;
 pub mod reactor;
+pub mod scheduler;
 pub mod state;
 pub(crate) mod subgraph;
 pub mod util;
 
 /// A subgraph's ID. Invalid if used in a different [`graph::Hydroflow`]
 /// instance than the original that created it.
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
 #[repr(transparent)]
 pub struct SubgraphId(pub(crate) usize);
 impl Display for SubgraphId {
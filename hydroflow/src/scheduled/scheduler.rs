@@ -0,0 +1,100 @@
+//! Scheduling policies, deciding which of a stratum's scheduled subgraphs runs next.
+
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, VecDeque};
+
+use super::SubgraphId;
+
+/// Priority of a subgraph. Higher priorities run first under [`PriorityScheduler`]. Subgraphs
+/// default to priority `0`, so background work can use negative priorities.
+pub type Priority = i32;
+
+/// A queue of scheduled subgraphs within a single stratum.
+///
+/// Each subgraph is in the queue at most once at a time.
+pub trait Scheduler {
+    /// Adds a subgraph to the queue.
+    fn push(&mut self, sg_id: SubgraphId, priority: Priority);
+    /// Removes and returns the next subgraph to run.
+    fn pop(&mut self) -> Option<SubgraphId>;
+    /// Returns true if no subgraphs are queued.
+    fn is_empty(&self) -> bool;
+}
+
+/// Runs subgraphs in the order they were scheduled. The default.
+#[derive(Debug, Default)]
+pub struct FifoScheduler {
+    queue: VecDeque<SubgraphId>,
+}
+impl Scheduler for FifoScheduler {
+    fn push(&mut self, sg_id: SubgraphId, _priority: Priority) {
+        self.queue.push_back(sg_id);
+    }
+    fn pop(&mut self) -> Option<SubgraphId> {
+        self.queue.pop_front()
+    }
+    fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+}
+
+/// Runs the most recently scheduled subgraph first. Data is processed depth-first, while it is
+/// still in cache, rather than breadth-first.
+#[derive(Debug, Default)]
+pub struct LifoScheduler {
+    stack: Vec<SubgraphId>,
+}
+impl Scheduler for LifoScheduler {
+    fn push(&mut self, sg_id: SubgraphId, _priority: Priority) {
+        self.stack.push(sg_id);
+    }
+    fn pop(&mut self) -> Option<SubgraphId> {
+        self.stack.pop()
+    }
+    fn is_empty(&self) -> bool {
+        self.stack.is_empty()
+    }
+}
+
+/// Runs the highest [`Priority`] subgraph first, and subgraphs of equal priority in the order
+/// they were scheduled.
+#[derive(Debug, Default)]
+pub struct PriorityScheduler {
+    heap: BinaryHeap<(Priority, Reverse<usize>, SubgraphId)>,
+    /// Sequence number to break ties in FIFO order.
+    next_seq: usize,
+}
+impl Scheduler for PriorityScheduler {
+    fn push(&mut self, sg_id: SubgraphId, priority: Priority) {
+        self.heap.push((priority, Reverse(self.next_seq), sg_id));
+        self.next_seq += 1;
+    }
+    fn pop(&mut self) -> Option<SubgraphId> {
+        self.heap.pop().map(|(_priority, _seq, sg_id)| sg_id)
+    }
+    fn is_empty(&self) -> bool {
+        self.heap.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(mut scheduler: impl Scheduler, items: &[(usize, Priority)]) -> Vec<usize> {
+        for &(sg_id, priority) in items {
+            scheduler.push(SubgraphId(sg_id), priority);
+        }
+        std::iter::from_fn(|| scheduler.pop())
+            .map(|sg_id| sg_id.0)
+            .collect()
+    }
+
+    #[test]
+    fn orders() {
+        let items = [(0, 0), (1, 5), (2, -1), (3, 5)];
+        assert_eq!(vec![0, 1, 2, 3], run(FifoScheduler::default(), &items));
+        assert_eq!(vec![3, 2, 1, 0], run(LifoScheduler::default(), &items));
+        assert_eq!(vec![1, 3, 0, 2], run(PriorityScheduler::default(), &items));
+    }
+}
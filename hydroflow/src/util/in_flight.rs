@@ -0,0 +1,51 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use futures::task::AtomicWaker;
+
+use crate::scheduled::context::Context;
+
+/// Counts items handed off to an async task but not yet processed by it. Used by `dest_sink` to
+/// push back on its subgraph while the sink is busy.
+#[derive(Clone, Default)]
+pub struct InFlight(Arc<InFlightInner>);
+#[derive(Default)]
+struct InFlightInner {
+    count: AtomicUsize,
+    waker: AtomicWaker,
+}
+impl InFlight {
+    /// The number of items in flight.
+    pub fn len(&self) -> usize {
+        self.0.count.load(Ordering::Acquire)
+    }
+
+    /// If there are no items in flight.
+    pub fn is_empty(&self) -> bool {
+        0 == self.len()
+    }
+
+    /// Records that `n` items were handed off.
+    pub fn add(&self, n: usize) {
+        self.0.count.fetch_add(n, Ordering::AcqRel);
+    }
+
+    /// Records that `n` items were processed, waking the blocked subgraph if any.
+    pub fn done(&self, n: usize) {
+        self.0.count.fetch_sub(n, Ordering::AcqRel);
+        self.0.waker.wake();
+    }
+
+    /// If `limit` or more items are in flight, blocks the current subgraph until some are
+    /// processed. See [`Context::block_until_woken`].
+    pub fn block_if_full(&self, limit: usize, context: &Context) {
+        if self.len() < limit {
+            return;
+        }
+        self.0.waker.register(&context.waker());
+        // Check again in case items were processed before the waker was registered.
+        if limit <= self.len() {
+            context.block_until_woken();
+        }
+    }
+}
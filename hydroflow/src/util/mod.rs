@@ -4,6 +4,8 @@ mod udp;
 pub use udp::*;
 mod tcp;
 pub use tcp::*;
+mod in_flight;
+pub use in_flight::*;
 
 use std::net::SocketAddr;
 use std::task::{Context, Poll};
@@ -0,0 +1,8 @@
+use hydroflow::hydroflow_syntax;
+
+fn main() {
+    let mut df = hydroflow_syntax! {
+        source_iter([1, 2, 3]) -> :String -> for_each(|x| println!("{}", x));
+    };
+    df.run_available();
+}
@@ -0,0 +1,8 @@
+use hydroflow::hydroflow_syntax;
+
+fn main() {
+    let mut df = hydroflow_syntax! {
+        ports = input::<usize>() -> output();
+    };
+    df.run_available();
+}
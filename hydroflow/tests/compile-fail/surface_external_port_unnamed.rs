@@ -0,0 +1,8 @@
+use hydroflow::hydroflow_syntax;
+
+fn main() {
+    let mut df = hydroflow_syntax! {
+        source_iter(0..3) -> output();
+    };
+    df.run_available();
+}
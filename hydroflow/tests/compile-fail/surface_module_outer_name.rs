@@ -0,0 +1,17 @@
+use hydroflow::{hydroflow_module, hydroflow_syntax};
+
+hydroflow_module! {
+    leaky() {
+        input = identity();
+        output = identity();
+        input -> outer -> output;
+    }
+}
+
+fn main() {
+    let mut df = hydroflow_syntax! {
+        outer = map(|x: usize| x + 1);
+        source_iter(0..10) -> leaky!() -> for_each(std::mem::drop);
+    };
+    df.run_available();
+}
@@ -0,0 +1,18 @@
+use hydroflow::{hydroflow_module, hydroflow_syntax};
+
+hydroflow_module! {
+    passthrough() {
+        input = identity();
+        output = identity();
+        input -> output;
+    }
+}
+
+fn main() {
+    let mut df = hydroflow_syntax! {
+        pass = passthrough!();
+        source_iter(0..10) -> pass;
+        pass[outptu] -> for_each(std::mem::drop);
+    };
+    df.run_available();
+}
@@ -245,3 +245,98 @@ pub async fn test_local_constraints() {
     assert_eq!(out_recv.next().await.unwrap(), (1, 1));
     assert_eq!(out_recv.next().await, None);
 }
+
+#[tokio::test]
+pub async fn test_join_order_size_hints() {
+    let (edge_send, edge) = hydroflow::util::unbounded_channel::<(usize, usize)>();
+    let (seed_send, seed) = hydroflow::util::unbounded_channel::<(usize,)>();
+    let (out, out_recv) = hydroflow::util::unbounded_channel::<(usize, usize)>();
+
+    edge_send.send((1, 2)).unwrap();
+    edge_send.send((2, 3)).unwrap();
+    edge_send.send((3, 4)).unwrap();
+    seed_send.send((2,)).unwrap();
+
+    thread::spawn(|| {
+        let mut flow = datalog!(
+            r#"
+            .input edge
+            .input seed
+            .output out
+
+            .size edge 1000000
+            .size seed 10
+
+            out(x, z) :- edge(x, y), edge(y, z), seed(y).
+            "#
+        );
+
+        flow.run_available();
+    })
+    .join()
+    .unwrap();
+
+    assert_eq!(out_recv.collect::<Vec<_>>().await, vec![(1, 3)]);
+}
+
+#[tokio::test]
+pub async fn test_wildcard_fields() {
+    let (in1_send, in1) = hydroflow::util::unbounded_channel::<(usize, usize)>();
+    let (in2_send, in2) = hydroflow::util::unbounded_channel::<(usize, usize, usize)>();
+    let (out, out_recv) = hydroflow::util::unbounded_channel::<(usize,)>();
+
+    in1_send.send((1, 2)).unwrap();
+    in1_send.send((3, 4)).unwrap();
+    in2_send.send((5, 1, 6)).unwrap();
+    in2_send.send((4, 7, 3)).unwrap();
+
+    thread::spawn(|| {
+        let mut flow = datalog!(
+            r#"
+            .input in1
+            .input in2
+            .output out
+
+            out(x) :- in1(x, _), in2(_, x, _).
+            "#
+        );
+
+        flow.run_available();
+    })
+    .join()
+    .unwrap();
+
+    assert_eq!(out_recv.collect::<Vec<_>>().await, vec![(1,)]);
+}
+
+#[tokio::test]
+pub async fn test_shared_rule_bodies() {
+    let (in1_send, in1) = hydroflow::util::unbounded_channel::<(usize, usize)>();
+    let (in2_send, in2) = hydroflow::util::unbounded_channel::<(usize, usize)>();
+    let (out1, out1_recv) = hydroflow::util::unbounded_channel::<(usize, usize)>();
+    let (out2, out2_recv) = hydroflow::util::unbounded_channel::<(usize, usize)>();
+
+    in1_send.send((1, 2)).unwrap();
+    in2_send.send((2, 3)).unwrap();
+
+    thread::spawn(|| {
+        let mut flow = datalog!(
+            r#"
+            .input in1
+            .input in2
+            .output out1
+            .output out2
+
+            out1(x, z) :- in1(x, y), in2(y, z).
+            out2(z, x) :- in1(x, y), in2(y, z).
+            "#
+        );
+
+        flow.run_available();
+    })
+    .join()
+    .unwrap();
+
+    assert_eq!(out1_recv.collect::<Vec<_>>().await, vec![(1, 3)]);
+    assert_eq!(out2_recv.collect::<Vec<_>>().await, vec![(3, 1)]);
+}
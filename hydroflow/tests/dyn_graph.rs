@@ -0,0 +1,313 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use hydroflow::scheduled::dyn_graph::{DynArg, DynGraphBuilder, DynPort, Persistence, Value};
+use hydroflow::util::collect_ready;
+
+/// Helper: a `for_each()` argument which collects into the returned `Vec`.
+fn collect_into() -> (Rc<RefCell<Vec<Value>>>, DynArg) {
+    let output = <Rc<RefCell<Vec<Value>>>>::default();
+    let output_inner = Rc::clone(&output);
+    (
+        output,
+        DynArg::for_each(move |v| output_inner.borrow_mut().push(v)),
+    )
+}
+
+fn int(value: &Value) -> i64 {
+    match value {
+        &Value::Int(x) => x,
+        other => panic!("Expected `Value::Int`, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_dyn_tee_merge() {
+    let (output, for_each) = collect_into();
+
+    let mut builder = DynGraphBuilder::new();
+    let source = builder.add_operator(
+        "source_iter",
+        vec![DynArg::Values((0..5).map(Value::Int).collect())],
+    );
+    let tee = builder.add_operator("tee", vec![]);
+    let evens = builder.add_operator("filter", vec![DynArg::filter(|v| 0 == int(v) % 2)]);
+    let tens = builder.add_operator("map", vec![DynArg::map(|v| Value::Int(10 * int(&v)))]);
+    let merge = builder.add_operator("merge", vec![]);
+    let sink = builder.add_operator("for_each", vec![for_each]);
+    builder.add_edge(source, tee);
+    builder.add_edge(tee, evens);
+    builder.add_edge(tee, tens);
+    builder.add_edge(evens, merge);
+    builder.add_edge(tens, merge);
+    builder.add_edge(merge, sink);
+
+    let mut df = builder.build().unwrap();
+    df.run_available();
+
+    let mut output: Vec<_> = output.take().iter().map(int).collect();
+    output.sort_unstable();
+    assert_eq!(&[0, 0, 2, 4, 10, 20, 30, 40], &*output);
+}
+
+#[test]
+fn test_dyn_join() {
+    let (output, for_each) = collect_into();
+
+    let mut builder = DynGraphBuilder::new();
+    let lhs = builder.add_operator(
+        "source_iter",
+        vec![DynArg::Values(vec![
+            ("a", 1_i64).into(),
+            ("b", 2_i64).into(),
+        ])],
+    );
+    let rhs = builder.add_operator(
+        "source_iter",
+        vec![DynArg::Values(vec![("a", "x").into(), ("c", "y").into()])],
+    );
+    let join = builder.add_operator("join", vec![]);
+    let sink = builder.add_operator("for_each", vec![for_each]);
+    builder.add_edge_with_ports(lhs, DynPort::Elided, join, 0);
+    builder.add_edge_with_ports(rhs, DynPort::Elided, join, 1);
+    builder.add_edge(join, sink);
+
+    let mut df = builder.build().unwrap();
+    df.run_available();
+
+    assert_eq!(&[Value::from(("a", (1_i64, "x")))], &*output.take());
+}
+
+/// One node feeding both ports of `join()`, each edge must go to its own input.
+#[test]
+fn test_dyn_self_join() {
+    let (output, for_each) = collect_into();
+
+    let mut builder = DynGraphBuilder::new();
+    let source = builder.add_operator(
+        "source_iter",
+        vec![DynArg::Values(vec![
+            ("a", 1_i64).into(),
+            ("b", 2_i64).into(),
+        ])],
+    );
+    let tee = builder.add_operator("tee", vec![]);
+    let join = builder.add_operator("join", vec![]);
+    let sink = builder.add_operator("for_each", vec![for_each]);
+    builder.add_edge(source, tee);
+    builder.add_edge_with_ports(tee, 0, join, 0);
+    builder.add_edge_with_ports(tee, 1, join, 1);
+    builder.add_edge(join, sink);
+
+    let mut df = builder.build().unwrap();
+    df.run_available();
+
+    let mut output = output.take();
+    output.sort_unstable();
+    assert_eq!(
+        &[
+            Value::from(("a", (1_i64, 1_i64))),
+            Value::from(("b", (2_i64, 2_i64)))
+        ],
+        &*output
+    );
+}
+
+/// `join()` is `'static` by default like in the surface syntax, so it matches items from earlier
+/// ticks, unless its persistence is `'tick`.
+#[test]
+fn test_dyn_join_persistence() {
+    for (persistence, expected) in [
+        (vec![], vec![Value::from(("a", (1_i64, "x")))]),
+        (vec![Persistence::Tick], vec![]),
+    ] {
+        let (lhs_send, lhs_recv) = hydroflow::util::unbounded_channel::<Value>();
+        let (rhs_send, rhs_recv) = hydroflow::util::unbounded_channel::<Value>();
+        let (output, for_each) = collect_into();
+
+        let mut builder = DynGraphBuilder::new();
+        let lhs = builder.add_operator("source_stream", vec![DynArg::stream(lhs_recv)]);
+        let rhs = builder.add_operator("source_stream", vec![DynArg::stream(rhs_recv)]);
+        let join = builder.add_operator_with_persistence("join", persistence, vec![]);
+        let sink = builder.add_operator("for_each", vec![for_each]);
+        builder.add_edge_with_ports(lhs, DynPort::Elided, join, 0);
+        builder.add_edge_with_ports(rhs, DynPort::Elided, join, 1);
+        builder.add_edge(join, sink);
+
+        let mut df = builder.build().unwrap();
+
+        lhs_send.send(("a", 1_i64).into()).unwrap();
+        df.run_tick();
+        rhs_send.send(("a", "x").into()).unwrap();
+        df.run_tick();
+
+        assert_eq!(expected, output.take());
+    }
+}
+
+/// `unique()` is `'static` by default like in the surface syntax, so it removes items seen in
+/// earlier ticks, unless its persistence is `'tick`.
+#[test]
+fn test_dyn_unique_persistence() {
+    for (persistence, expected) in [
+        (vec![], vec![Value::from(1_i64), 2_i64.into()]),
+        (
+            vec![Persistence::Tick],
+            vec![Value::from(1_i64), 1_i64.into(), 2_i64.into()],
+        ),
+    ] {
+        let (inp_send, inp_recv) = hydroflow::util::unbounded_channel::<Value>();
+        let (output, for_each) = collect_into();
+
+        let mut builder = DynGraphBuilder::new();
+        let source = builder.add_operator("source_stream", vec![DynArg::stream(inp_recv)]);
+        let unique = builder.add_operator_with_persistence("unique", persistence, vec![]);
+        let sink = builder.add_operator("for_each", vec![for_each]);
+        builder.add_edge(source, unique);
+        builder.add_edge(unique, sink);
+
+        let mut df = builder.build().unwrap();
+
+        inp_send.send(1_i64.into()).unwrap();
+        inp_send.send(1_i64.into()).unwrap();
+        df.run_available();
+        inp_send.send(1_i64.into()).unwrap();
+        inp_send.send(2_i64.into()).unwrap();
+        df.run_available();
+
+        assert_eq!(expected, output.take());
+    }
+}
+
+/// Like `test_difference_b` in `surface_stratum.rs`, outputs items which were not in the previous
+/// tick.
+#[test]
+fn test_dyn_difference_next_tick() {
+    let (inp_send, inp_recv) = hydroflow::util::unbounded_channel::<Value>();
+    let (out_send, mut out_recv) = hydroflow::util::unbounded_channel::<Value>();
+
+    let mut builder = DynGraphBuilder::new();
+    let source = builder.add_operator("source_stream", vec![DynArg::stream(inp_recv)]);
+    let tee = builder.add_operator("tee", vec![]);
+    let next_tick = builder.add_operator("next_tick", vec![]);
+    let difference = builder.add_operator("difference", vec![]);
+    let sink = builder.add_operator(
+        "for_each",
+        vec![DynArg::for_each(move |v| out_send.send(v).unwrap())],
+    );
+    builder.add_edge(source, tee);
+    builder.add_edge_with_ports(tee, 0, difference, "pos");
+    builder.add_edge_with_ports(tee, 1, next_tick, DynPort::Elided);
+    builder.add_edge_with_ports(next_tick, DynPort::Elided, difference, "neg");
+    builder.add_edge(difference, sink);
+
+    let mut df = builder.build().unwrap();
+
+    for x in ["01", "02", "03"] {
+        inp_send.send(x.into()).unwrap();
+    }
+    df.run_tick();
+    assert_eq!(
+        &[Value::from("01"), "02".into(), "03".into()],
+        &*collect_ready::<Vec<_>, _>(&mut out_recv)
+    );
+
+    for x in ["02", "03", "04"] {
+        inp_send.send(x.into()).unwrap();
+    }
+    df.run_tick();
+    assert_eq!(
+        &[Value::from("04")],
+        &*collect_ready::<Vec<_>, _>(&mut out_recv)
+    );
+}
+
+#[test]
+fn test_dyn_errors() {
+    let messages = |builder: DynGraphBuilder| -> Vec<String> {
+        builder
+            .build()
+            .err()
+            .unwrap()
+            .iter()
+            .map(|diagnostic| diagnostic.message().to_owned())
+            .collect()
+    };
+
+    let mut builder = DynGraphBuilder::new();
+    builder.add_operator("group_by", vec![]);
+    assert_eq!(
+        vec!["Operator `group_by` is not supported in dynamic graphs."],
+        messages(builder)
+    );
+
+    let mut builder = DynGraphBuilder::new();
+    let source = builder.add_operator("source_iter", vec![DynArg::map(|v| v)]);
+    let sink = builder.add_operator("null", vec![]);
+    builder.add_edge(source, sink);
+    assert_eq!(
+        vec!["Operator `source_iter` was given the wrong kind of `DynArg`."],
+        messages(builder)
+    );
+
+    let mut builder = DynGraphBuilder::new();
+    let source = builder.add_operator("source_iter", vec![DynArg::Values(vec![])]);
+    let difference = builder.add_operator("difference", vec![]);
+    builder.add_edge_with_ports(source, DynPort::Elided, difference, "not an ident");
+    assert_eq!(
+        vec!["Port name `not an ident` is not a valid identifier."],
+        messages(builder)
+    );
+
+    let mut builder = DynGraphBuilder::new();
+    let source = builder.add_operator("source_iter", vec![DynArg::Values(vec![])]);
+    let mut other_builder = DynGraphBuilder::new();
+    other_builder.add_operator("null", vec![]);
+    builder.add_edge(source, other_builder.add_operator("null", vec![]));
+    assert_eq!(
+        vec!["Edge uses `DynNodeId(1)`, which is not from this builder."],
+        messages(builder)
+    );
+
+    let mut builder = DynGraphBuilder::new();
+    let source = builder.add_operator("source_iter", vec![DynArg::Values(vec![])]);
+    let tee = builder.add_operator("tee", vec![]);
+    let difference = builder.add_operator("difference", vec![]);
+    let sink = builder.add_operator("null", vec![]);
+    builder.add_edge(source, tee);
+    builder.add_edge_with_ports(tee, 0, difference, "neg");
+    builder.add_edge_with_ports(tee, 1, difference, "other");
+    builder.add_edge(difference, sink);
+    assert_eq!(
+        vec![
+            "Unexpected input port. Expected one of: `pos`, `neg`",
+            "Missing expected input port(s): `pos`."
+        ],
+        messages(builder)
+    );
+
+    let mut builder = DynGraphBuilder::new();
+    builder.add_operator("map", vec![DynArg::map(|v| v)]);
+    assert_eq!(
+        vec![
+            "`map` must have exactly 1 input(s), actually has 0.",
+            "`map` must have exactly 1 output(s), actually has 0."
+        ],
+        messages(builder)
+    );
+
+    let mut builder = DynGraphBuilder::new();
+    let source = builder.add_operator("source_iter", vec![DynArg::Values(vec![])]);
+    let unique = builder.add_operator_with_persistence(
+        "unique",
+        vec![Persistence::Tick, Persistence::Static],
+        vec![],
+    );
+    let sink = builder.add_operator("null", vec![]);
+    builder.add_edge(source, unique);
+    builder.add_edge(unique, sink);
+    assert_eq!(
+        vec!["Operator `unique` expects zero or one persistence lifetimes."],
+        messages(builder)
+    );
+}
@@ -1,8 +1,10 @@
 //! Surface syntax tests of asynchrony and networking.
 
+use std::cell::Cell;
 use std::collections::HashSet;
 use std::error::Error;
 use std::net::{Ipv4Addr, SocketAddr};
+use std::rc::Rc;
 use std::time::Duration;
 
 use bytes::Bytes;
@@ -282,6 +284,124 @@ async fn asynctest_dest_sink_bounded_channel() {
     assert_eq!(&[0, 1, 2, 3, 4], &*out);
 }
 
+#[tokio::test(flavor = "current_thread")]
+async fn asynctest_dest_sink_backpressure() {
+    use futures::StreamExt;
+
+    let polled = Rc::new(Cell::new(0));
+    let inner_polled = polled.clone();
+    let source = futures::stream::iter(0..100).inspect(move |_| {
+        inner_polled.set(inner_polled.get() + 1);
+    });
+
+    let (send, recv) = tokio::sync::mpsc::channel::<usize>(5);
+    let send = tokio_util::sync::PollSender::new(send);
+    let recv = tokio_stream::wrappers::ReceiverStream::new(recv);
+
+    let mut flow = hydroflow_syntax! {
+        source_stream(source) -> dest_sink(send, 10);
+    };
+    // The sink task has not run yet, so the source stops after 10 items are in flight.
+    flow.run_available();
+    assert_eq!(10, polled.get());
+
+    // Once the receiver drains the sink, every item arrives in order.
+    let out = tokio::time::timeout(Duration::from_secs(1), async {
+        tokio::select! {
+            _ = flow.run_async() => unreachable!(),
+            out = recv.take(100).collect::<Vec<_>>() => out,
+        }
+    })
+    .await
+    .expect("Timed out waiting for items.");
+    assert_eq!((0..100).collect::<Vec<_>>(), out);
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn asynctest_run_budget_yields() {
+    use hydroflow::scheduled::graph::RunBudget;
+
+    let count = Rc::new(Cell::new(0));
+    let inner_count = count.clone();
+
+    // A cycle which never runs out of work.
+    let mut flow = hydroflow_syntax! {
+        #![allow(spinning_cycles)]
+        counter = merge() -> map(|x: usize| x + 1) -> tee();
+        source_iter([0]) -> [0]counter;
+        counter[0] -> [1]counter;
+        counter[1] -> for_each(|x| inner_count.set(x));
+    };
+    flow.set_run_budget(RunBudget {
+        max_subgraph_runs: Some(100),
+        ..Default::default()
+    });
+
+    // Other tasks, such as this timeout, still get to run.
+    tokio::time::timeout(Duration::from_millis(100), flow.run_async())
+        .await
+        .expect_err("Expected time out");
+    assert!(0 < count.get());
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn asynctest_hydroflow_stream() {
+    use futures::StreamExt;
+
+    let flow = hydroflow_syntax! {
+        inp = input::<usize>();
+        out = output();
+        inp -> map(|x| 2 * x) -> out;
+    };
+    let inp = flow.inp;
+    let mut stream = flow.df.into_stream(flow.out);
+
+    inp.send(1).unwrap();
+    inp.send(2).unwrap();
+    assert_eq!(Some(2), stream.next().await);
+    assert_eq!(Some(4), stream.next().await);
+
+    // Polling the stream waits for more input.
+    let later_inp = inp.clone();
+    tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        later_inp.send(5).unwrap();
+    });
+    assert_eq!(Some(10), stream.next().await);
+
+    // Another graph can consume the stream, driving the first graph.
+    let (out_send, mut out_recv) = hydroflow::util::unbounded_channel::<usize>();
+    let mut consumer = hydroflow_syntax! {
+        source_stream(stream) -> for_each(|x| out_send.send(x).unwrap());
+    };
+    inp.send(7).unwrap();
+    tokio::time::timeout(Duration::from_millis(100), consumer.run_async())
+        .await
+        .expect_err("Expected time out");
+    assert_eq!(&[14], &*collect_ready::<Vec<_>, _>(&mut out_recv));
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn asynctest_hydroflow_sink() {
+    use futures::SinkExt;
+
+    let (out_send, mut out_recv) = hydroflow::util::unbounded_channel::<usize>();
+    let flow = hydroflow_syntax! {
+        inp = input::<usize>();
+        inp -> map(|x| x + 1) -> for_each(|x| out_send.send(x).unwrap());
+    };
+    let mut sink = flow.df.into_sink(flow.inp);
+
+    sink.send(1).await.unwrap();
+    assert_eq!(&[2], &*collect_ready::<Vec<_>, _>(&mut out_recv));
+
+    sink.feed(2).await.unwrap();
+    sink.feed(3).await.unwrap();
+    assert!(collect_ready::<Vec<_>, _>(&mut out_recv).is_empty());
+    sink.flush().await.unwrap();
+    assert_eq!(&[3, 4], &*collect_ready::<Vec<_>, _>(&mut out_recv));
+}
+
 #[tokio::test(flavor = "current_thread")]
 async fn asynctest_dest_sink_duplex() {
     use bytes::Bytes;
@@ -1,8 +1,10 @@
 use std::collections::{HashMap, HashSet};
 
-use hydroflow::hydroflow_syntax;
-use hydroflow::scheduled::graph::Hydroflow;
+use hydroflow::lang::agg;
+use hydroflow::scheduled::graph::{Hydroflow, RunBudget};
 use hydroflow::util::collect_ready;
+use hydroflow::{hydroflow_module, hydroflow_syntax};
+use hydroflow::{var_args, var_expr};
 
 // TODO(mingwei): custom operators? How to handle in syntax? How to handle state?
 
@@ -287,6 +289,30 @@ pub fn test_reduce_sum() {
     println!();
 }
 
+#[test]
+pub fn test_reduce_static() {
+    let (items_send, items_recv) = hydroflow::util::unbounded_channel::<usize>();
+
+    let (out_send, mut out_recv) = hydroflow::util::unbounded_channel::<usize>();
+
+    let mut df = hydroflow_syntax! {
+        source_stream(items_recv)
+            -> reduce::<'static>(|a, b| a + b)
+            -> for_each(|v| out_send.send(v).unwrap());
+    };
+
+    items_send.send(9).unwrap();
+    items_send.send(2).unwrap();
+    items_send.send(5).unwrap();
+    df.run_available();
+    assert_eq!(&[16], &*collect_ready::<Vec<_>, _>(&mut out_recv));
+
+    items_send.send(3).unwrap();
+    items_send.send(1).unwrap();
+    df.run_available();
+    assert_eq!(&[20], &*collect_ready::<Vec<_>, _>(&mut out_recv));
+}
+
 #[test]
 pub fn test_sort() {
     let (items_send, items_recv) = hydroflow::util::unbounded_channel::<usize>();
@@ -324,11 +350,440 @@ pub fn test_sort() {
     println!();
 }
 
+#[test]
+pub fn test_top_k() {
+    let (items_send, items_recv) = hydroflow::util::unbounded_channel::<(&str, u32)>();
+    let (tick_send, mut tick_recv) = hydroflow::util::unbounded_channel::<(&str, u32)>();
+    let (static_send, mut static_recv) = hydroflow::util::unbounded_channel::<(&str, u32)>();
+
+    let mut df = hydroflow_syntax! {
+        items = source_stream(items_recv) -> tee();
+        items[0]
+            -> top_k(2, |&(_, score): &(&str, u32)| score)
+            -> for_each(|x| tick_send.send(x).unwrap());
+        items[1]
+            -> top_k::<'static>(2, |&(_, score): &(&str, u32)| score)
+            -> for_each(|x| static_send.send(x).unwrap());
+    };
+
+    items_send.send(("alice", 20)).unwrap();
+    items_send.send(("bob", 10)).unwrap();
+    items_send.send(("carol", 5)).unwrap();
+    df.run_available();
+    assert_eq!(
+        &[("alice", 20), ("bob", 10)],
+        &*collect_ready::<Vec<_>, _>(&mut tick_recv)
+    );
+    assert_eq!(
+        &[("alice", 20), ("bob", 10)],
+        &*collect_ready::<Vec<_>, _>(&mut static_recv)
+    );
+
+    items_send.send(("dave", 15)).unwrap();
+    df.run_available();
+    assert_eq!(
+        &[("dave", 15)],
+        &*collect_ready::<Vec<_>, _>(&mut tick_recv)
+    );
+    assert_eq!(
+        &[("alice", 20), ("dave", 15)],
+        &*collect_ready::<Vec<_>, _>(&mut static_recv)
+    );
+}
+
+#[test]
+pub fn test_top_k_by_group() {
+    let (items_send, items_recv) = hydroflow::util::unbounded_channel::<(&str, (u32, &str))>();
+    let (out_send, mut out_recv) = hydroflow::util::unbounded_channel::<(&str, (u32, &str))>();
+
+    let mut df = hydroflow_syntax! {
+        source_stream(items_recv)
+            -> top_k_by_group::<'static>(2, |&(timestamp, _): &(u32, &str)| timestamp)
+            -> for_each(|x| out_send.send(x).unwrap());
+    };
+
+    items_send.send(("alice", (1, "login"))).unwrap();
+    items_send.send(("bob", (2, "login"))).unwrap();
+    items_send.send(("alice", (3, "post"))).unwrap();
+    df.run_available();
+    let out: HashSet<_> = collect_ready(&mut out_recv);
+    assert_eq!(
+        HashSet::from([
+            ("alice", (3, "post")),
+            ("alice", (1, "login")),
+            ("bob", (2, "login"))
+        ]),
+        out
+    );
+
+    items_send.send(("alice", (4, "logout"))).unwrap();
+    df.run_available();
+    let out: HashSet<_> = collect_ready(&mut out_recv);
+    assert_eq!(
+        HashSet::from([
+            ("alice", (4, "logout")),
+            ("alice", (3, "post")),
+            ("bob", (2, "login"))
+        ]),
+        out
+    );
+}
+
+#[test]
+pub fn test_limit() {
+    let (items_send, items_recv) = hydroflow::util::unbounded_channel::<usize>();
+    let (tick_send, mut tick_recv) = hydroflow::util::unbounded_channel::<usize>();
+    let (static_send, mut static_recv) = hydroflow::util::unbounded_channel::<usize>();
+
+    let mut df = hydroflow_syntax! {
+        items = source_stream(items_recv) -> tee();
+        items[0] -> limit(2) -> for_each(|x| tick_send.send(x).unwrap());
+        items[1] -> limit::<'static>(3) -> for_each(|x| static_send.send(x).unwrap());
+    };
+
+    items_send.send(1).unwrap();
+    items_send.send(2).unwrap();
+    items_send.send(3).unwrap();
+    df.run_available();
+    assert_eq!(&[1, 2], &*collect_ready::<Vec<_>, _>(&mut tick_recv));
+    assert_eq!(&[1, 2, 3], &*collect_ready::<Vec<_>, _>(&mut static_recv));
+
+    items_send.send(4).unwrap();
+    items_send.send(5).unwrap();
+    items_send.send(6).unwrap();
+    df.run_available();
+    assert_eq!(&[4, 5], &*collect_ready::<Vec<_>, _>(&mut tick_recv));
+    assert_eq!(
+        &[] as &[usize],
+        &*collect_ready::<Vec<_>, _>(&mut static_recv)
+    );
+}
+
+/// The subgraph containing `limit()` runs once for each item around the loop, all in one tick.
+#[test]
+pub fn test_limit_tick_multiple_runs() {
+    let (out_send, mut out_recv) = hydroflow::util::unbounded_channel::<usize>();
+
+    let mut df = hydroflow_syntax! {
+        loop_start = merge() -> tee();
+        source_iter([0]) -> [0]loop_start;
+        loop_start[0] -> map(|x| x + 1) -> filter(|&x| x < 10) -> [1]loop_start;
+        loop_start[1] -> limit(3) -> for_each(|x| out_send.send(x).unwrap());
+    };
+
+    df.run_tick();
+    assert_eq!(&[0, 1, 2], &*collect_ready::<Vec<_>, _>(&mut out_recv));
+}
+
+#[test]
+pub fn test_zip() {
+    let (lhs_send, lhs_recv) = hydroflow::util::unbounded_channel::<usize>();
+    let (rhs_send, rhs_recv) = hydroflow::util::unbounded_channel::<&str>();
+    let (out_send, mut out_recv) = hydroflow::util::unbounded_channel::<(usize, &str)>();
+
+    let mut df = hydroflow_syntax! {
+        my_zip = zip() -> for_each(|x| out_send.send(x).unwrap());
+        source_stream(lhs_recv) -> [0]my_zip;
+        source_stream(rhs_recv) -> [1]my_zip;
+    };
+
+    lhs_send.send(1).unwrap();
+    lhs_send.send(2).unwrap();
+    lhs_send.send(3).unwrap();
+    rhs_send.send("a").unwrap();
+    df.run_available();
+    assert_eq!(&[(1, "a")], &*collect_ready::<Vec<_>, _>(&mut out_recv));
+
+    rhs_send.send("b").unwrap();
+    rhs_send.send("c").unwrap();
+    rhs_send.send("d").unwrap();
+    df.run_available();
+    assert_eq!(
+        &[(2, "b"), (3, "c")],
+        &*collect_ready::<Vec<_>, _>(&mut out_recv)
+    );
+
+    lhs_send.send(4).unwrap();
+    df.run_available();
+    assert_eq!(&[(4, "d")], &*collect_ready::<Vec<_>, _>(&mut out_recv));
+}
+
+#[test]
+pub fn test_zip_longest() {
+    let (lhs_send, lhs_recv) = hydroflow::util::unbounded_channel::<usize>();
+    let (rhs_send, rhs_recv) = hydroflow::util::unbounded_channel::<&str>();
+    let (out_send, mut out_recv) =
+        hydroflow::util::unbounded_channel::<(Option<usize>, Option<&str>)>();
+
+    let mut df = hydroflow_syntax! {
+        my_zip = zip_longest() -> for_each(|x| out_send.send(x).unwrap());
+        source_stream(lhs_recv) -> [0]my_zip;
+        source_stream(rhs_recv) -> [1]my_zip;
+    };
+
+    lhs_send.send(1).unwrap();
+    lhs_send.send(2).unwrap();
+    rhs_send.send("a").unwrap();
+    df.run_available();
+    assert_eq!(
+        &[(Some(1), Some("a")), (Some(2), None)],
+        &*collect_ready::<Vec<_>, _>(&mut out_recv)
+    );
+
+    rhs_send.send("b").unwrap();
+    df.run_available();
+    assert_eq!(
+        &[(None, Some("b"))],
+        &*collect_ready::<Vec<_>, _>(&mut out_recv)
+    );
+}
+
+#[test]
+pub fn test_enumerate() {
+    let (items_send, items_recv) = hydroflow::util::unbounded_channel::<&str>();
+    let (tick_send, mut tick_recv) = hydroflow::util::unbounded_channel::<(usize, &str)>();
+    let (static_send, mut static_recv) = hydroflow::util::unbounded_channel::<(usize, &str)>();
+
+    let mut df = hydroflow_syntax! {
+        items = source_stream(items_recv) -> tee();
+        items[0] -> enumerate() -> for_each(|x| tick_send.send(x).unwrap());
+        items[1] -> enumerate::<'static>() -> for_each(|x| static_send.send(x).unwrap());
+    };
+
+    items_send.send("a").unwrap();
+    items_send.send("b").unwrap();
+    df.run_available();
+    assert_eq!(
+        &[(0, "a"), (1, "b")],
+        &*collect_ready::<Vec<_>, _>(&mut tick_recv)
+    );
+    assert_eq!(
+        &[(0, "a"), (1, "b")],
+        &*collect_ready::<Vec<_>, _>(&mut static_recv)
+    );
+
+    items_send.send("c").unwrap();
+    df.run_available();
+    assert_eq!(&[(0, "c")], &*collect_ready::<Vec<_>, _>(&mut tick_recv));
+    assert_eq!(&[(2, "c")], &*collect_ready::<Vec<_>, _>(&mut static_recv));
+}
+
+/// The subgraph containing `enumerate()` runs once for each item around the loop, all in one tick.
+#[test]
+pub fn test_enumerate_tick_multiple_runs() {
+    let (out_send, mut out_recv) = hydroflow::util::unbounded_channel::<(usize, usize)>();
+
+    let mut df = hydroflow_syntax! {
+        loop_start = merge() -> tee();
+        source_iter([0]) -> [0]loop_start;
+        loop_start[0] -> map(|x| x + 1) -> filter(|&x| x < 3) -> [1]loop_start;
+        loop_start[1] -> enumerate() -> for_each(|x| out_send.send(x).unwrap());
+    };
+
+    df.run_tick();
+    assert_eq!(
+        &[(0, 0), (1, 1), (2, 2)],
+        &*collect_ready::<Vec<_>, _>(&mut out_recv)
+    );
+}
+
+#[test]
+pub fn test_approx_sketches() {
+    let (items_send, items_recv) = hydroflow::util::unbounded_channel::<u32>();
+    let (distinct_send, mut distinct_recv) = hydroflow::util::unbounded_channel::<usize>();
+    let (top_k_send, mut top_k_recv) = hydroflow::util::unbounded_channel::<(u32, u64)>();
+    let (quantile_send, mut quantile_recv) = hydroflow::util::unbounded_channel::<f64>();
+
+    let mut df = hydroflow_syntax! {
+        items = source_stream(items_recv) -> tee();
+        items[0] -> approx_distinct::<'static>() -> for_each(|n| distinct_send.send(n).unwrap());
+        items[1] -> approx_top_k(2) -> for_each(|x| top_k_send.send(x).unwrap());
+        items[2] -> approx_quantile::<'static>(0.5) -> for_each(|q| quantile_send.send(q).unwrap());
+    };
+
+    for item in [1, 2, 3, 2, 3, 3] {
+        items_send.send(item).unwrap();
+    }
+    df.run_available();
+    assert_eq!(&[3], &*collect_ready::<Vec<_>, _>(&mut distinct_recv));
+    assert_eq!(
+        &[(3, 3), (2, 2)],
+        &*collect_ready::<Vec<_>, _>(&mut top_k_recv)
+    );
+    let median = collect_ready::<Vec<_>, _>(&mut quantile_recv);
+    assert_eq!(1, median.len());
+    assert!((2.9..3.1).contains(&median[0]), "{}", median[0]);
+
+    for item in [4, 4, 1] {
+        items_send.send(item).unwrap();
+    }
+    df.run_available();
+    assert_eq!(&[4], &*collect_ready::<Vec<_>, _>(&mut distinct_recv));
+    assert_eq!(
+        &[(4, 2), (1, 1)],
+        &*collect_ready::<Vec<_>, _>(&mut top_k_recv)
+    );
+    let median = collect_ready::<Vec<_>, _>(&mut quantile_recv);
+    assert!((2.9..3.1).contains(&median[0]), "{}", median[0]);
+}
+
+#[test]
+pub fn test_persist_keyed() {
+    let (upsert_send, upsert_recv) = hydroflow::util::unbounded_channel::<(&str, usize)>();
+    let (delete_send, delete_recv) = hydroflow::util::unbounded_channel::<&str>();
+    let (contents_send, mut contents_recv) = hydroflow::util::unbounded_channel::<(&str, usize)>();
+    let (changes_send, mut changes_recv) =
+        hydroflow::util::unbounded_channel::<(&str, Option<usize>)>();
+
+    let mut df = hydroflow_syntax! {
+        kvs = persist_keyed::<&str, usize>();
+        source_stream(upsert_recv) -> [upsert]kvs;
+        source_stream(delete_recv) -> [delete]kvs;
+        kvs[contents] -> for_each(|kv| contents_send.send(kv).unwrap());
+        kvs[changes] -> for_each(|kv| changes_send.send(kv).unwrap());
+    };
+
+    upsert_send.send(("a", 1)).unwrap();
+    upsert_send.send(("b", 2)).unwrap();
+    df.run_tick();
+    // Not visible until the next tick.
+    assert_eq!(
+        &[] as &[(&str, usize)],
+        &*collect_ready::<Vec<_>, _>(&mut contents_recv)
+    );
+    df.run_tick();
+    let mut contents = collect_ready::<Vec<_>, _>(&mut contents_recv);
+    contents.sort();
+    assert_eq!(&[("a", 1), ("b", 2)], &*contents);
+    let mut changes = collect_ready::<Vec<_>, _>(&mut changes_recv);
+    changes.sort();
+    assert_eq!(&[("a", Some(1)), ("b", Some(2))], &*changes);
+
+    upsert_send.send(("a", 3)).unwrap();
+    delete_send.send("b").unwrap();
+    delete_send.send("c").unwrap();
+    df.run_available();
+    assert_eq!(
+        &[("a", 3)],
+        &*collect_ready::<Vec<_>, _>(&mut contents_recv)
+    );
+    let mut changes = collect_ready::<Vec<_>, _>(&mut changes_recv);
+    changes.sort();
+    assert_eq!(&[("a", Some(3)), ("b", None)], &*changes);
+
+    delete_send.send("a").unwrap();
+    df.run_available();
+    assert_eq!(
+        &[] as &[(&str, usize)],
+        &*collect_ready::<Vec<_>, _>(&mut contents_recv)
+    );
+    assert_eq!(
+        &[("a", None)],
+        &*collect_ready::<Vec<_>, _>(&mut changes_recv)
+    );
+    // Without new input the operator does not run, so nothing is emitted.
+    upsert_send.send(("a", 4)).unwrap();
+    df.run_tick();
+    df.run_tick();
+    assert_eq!(
+        &[("a", 4)],
+        &*collect_ready::<Vec<_>, _>(&mut contents_recv)
+    );
+    df.run_tick();
+    assert_eq!(
+        &[] as &[(&str, usize)],
+        &*collect_ready::<Vec<_>, _>(&mut contents_recv)
+    );
+    assert_eq!(
+        &[("a", Some(4))],
+        &*collect_ready::<Vec<_>, _>(&mut changes_recv)
+    );
+}
+
+#[test]
+pub fn test_persist_keyed_changes_only() {
+    let (upsert_send, upsert_recv) = hydroflow::util::unbounded_channel::<(usize, usize)>();
+    let (changes_send, mut changes_recv) =
+        hydroflow::util::unbounded_channel::<(usize, Option<usize>)>();
+
+    let mut df = hydroflow_syntax! {
+        kvs = persist_keyed();
+        source_stream(upsert_recv) -> [upsert]kvs;
+        source_iter(None) -> [delete]kvs;
+        kvs[contents] -> null();
+        kvs[changes] -> for_each(|kv| changes_send.send(kv).unwrap());
+    };
+
+    upsert_send.send((1, 10)).unwrap();
+    df.run_available();
+    assert_eq!(
+        &[(1, Some(10))],
+        &*collect_ready::<Vec<_>, _>(&mut changes_recv)
+    );
+
+    upsert_send.send((1, 10)).unwrap();
+    upsert_send.send((2, 20)).unwrap();
+    df.run_available();
+    assert_eq!(
+        &[(2, Some(20))],
+        &*collect_ready::<Vec<_>, _>(&mut changes_recv)
+    );
+}
+
+#[test]
+pub fn test_buffer_until() {
+    let (write_send, write_recv) = hydroflow::util::unbounded_channel::<(usize, &str)>();
+    let (commit_send, commit_recv) = hydroflow::util::unbounded_channel::<usize>();
+    let (out_send, mut out_recv) = hydroflow::util::unbounded_channel::<(usize, &str)>();
+
+    let mut df = hydroflow_syntax! {
+        gate = buffer_until(|(txn, _write): &(usize, &str), commit: &usize| txn == commit)
+            -> for_each(|x| out_send.send(x).unwrap());
+        source_stream(write_recv) -> [input]gate;
+        source_stream(commit_recv) -> [signal]gate;
+    };
+
+    write_send.send((1, "a")).unwrap();
+    write_send.send((2, "b")).unwrap();
+    write_send.send((1, "c")).unwrap();
+    df.run_available();
+    assert_eq!(
+        &[] as &[(usize, &str)],
+        &*collect_ready::<Vec<_>, _>(&mut out_recv)
+    );
+
+    commit_send.send(1).unwrap();
+    df.run_available();
+    assert_eq!(
+        &[(1, "a"), (1, "c")],
+        &*collect_ready::<Vec<_>, _>(&mut out_recv)
+    );
+
+    // Already released items are not released again.
+    commit_send.send(1).unwrap();
+    df.run_available();
+    assert_eq!(
+        &[] as &[(usize, &str)],
+        &*collect_ready::<Vec<_>, _>(&mut out_recv)
+    );
+
+    write_send.send((3, "d")).unwrap();
+    df.run_available();
+    commit_send.send(2).unwrap();
+    commit_send.send(3).unwrap();
+    df.run_available();
+    assert_eq!(
+        &[(2, "b"), (3, "d")],
+        &*collect_ready::<Vec<_>, _>(&mut out_recv)
+    );
+}
+
 #[test]
 pub fn test_unique() {
     let (items_send, items_recv) = hydroflow::util::unbounded_channel::<usize>();
 
     let mut df = hydroflow_syntax! {
+        #![allow(unbounded_state)]
         source_stream(items_recv)
             -> unique()
             -> for_each(|v| print!("{:?}, ", v));
@@ -402,11 +857,105 @@ pub fn test_fold_sort() {
     println!();
 }
 
+#[test]
+pub fn test_fold_static() {
+    let (items_send, items_recv) = hydroflow::util::unbounded_channel::<usize>();
+
+    let (out_send, mut out_recv) = hydroflow::util::unbounded_channel::<Vec<usize>>();
+
+    let mut df = hydroflow_syntax! {
+        source_stream(items_recv)
+            -> fold::<'static>(Vec::new(), |mut v, x| {
+                v.push(x);
+                v
+            })
+            -> for_each(|v| out_send.send(v).unwrap());
+    };
+
+    items_send.send(9).unwrap();
+    items_send.send(2).unwrap();
+    df.run_available();
+    assert_eq!(&[vec![9, 2]], &*collect_ready::<Vec<_>, _>(&mut out_recv));
+
+    items_send.send(5).unwrap();
+    df.run_available();
+    assert_eq!(
+        &[vec![9, 2, 5]],
+        &*collect_ready::<Vec<_>, _>(&mut out_recv)
+    );
+}
+
+#[test]
+pub fn test_fold_agg() {
+    let (items_send, items_recv) = hydroflow::util::unbounded_channel::<u32>();
+    let (tick_send, mut tick_recv) = hydroflow::util::unbounded_channel::<(usize, u32)>();
+    let (static_send, mut static_recv) = hydroflow::util::unbounded_channel::<Option<f64>>();
+
+    let mut df = hydroflow_syntax! {
+        items = source_stream(items_recv) -> tee();
+        items[0]
+            -> fold(var_expr!(agg::Count, agg::Sum))
+            -> for_each(|var_args!(count, sum)| tick_send.send((count, sum)).unwrap());
+        items[1]
+            -> fold::<'static>(agg::Avg)
+            -> for_each(|avg| static_send.send(avg).unwrap());
+    };
+
+    items_send.send(1).unwrap();
+    items_send.send(5).unwrap();
+    df.run_available();
+    assert_eq!(&[(2, 6)], &*collect_ready::<Vec<_>, _>(&mut tick_recv));
+    assert_eq!(&[Some(3.0)], &*collect_ready::<Vec<_>, _>(&mut static_recv));
+
+    items_send.send(6).unwrap();
+    df.run_available();
+    assert_eq!(&[(1, 6)], &*collect_ready::<Vec<_>, _>(&mut tick_recv));
+    assert_eq!(&[Some(4.0)], &*collect_ready::<Vec<_>, _>(&mut static_recv));
+}
+
+#[test]
+pub fn test_group_by_agg() {
+    let (items_send, items_recv) = hydroflow::util::unbounded_channel::<(&str, u32)>();
+    let (tick_send, mut tick_recv) = hydroflow::util::unbounded_channel::<(&str, u32)>();
+    let (static_send, mut static_recv) =
+        hydroflow::util::unbounded_channel::<(&str, usize, Option<u32>)>();
+
+    let mut df = hydroflow_syntax! {
+        #![allow(unbounded_state)]
+        items = source_stream(items_recv) -> tee();
+        items[0]
+            -> group_by::<'tick>(agg::Sum)
+            -> for_each(|x| tick_send.send(x).unwrap());
+        items[1]
+            -> group_by(var_expr!(agg::CountDistinct, agg::Min))
+            -> for_each(|(k, var_args!(distinct, min))| static_send.send((k, distinct, min)).unwrap());
+    };
+
+    items_send.send(("a", 3)).unwrap();
+    items_send.send(("a", 3)).unwrap();
+    items_send.send(("b", 2)).unwrap();
+    df.run_available();
+    let mut tick_out = collect_ready::<Vec<_>, _>(&mut tick_recv);
+    tick_out.sort();
+    assert_eq!(&[("a", 6), ("b", 2)], &*tick_out);
+    let mut static_out = collect_ready::<Vec<_>, _>(&mut static_recv);
+    static_out.sort();
+    assert_eq!(&[("a", 1, Some(3)), ("b", 1, Some(2))], &*static_out);
+
+    items_send.send(("a", 1)).unwrap();
+    df.run_available();
+    assert_eq!(&[("a", 1)], &*collect_ready::<Vec<_>, _>(&mut tick_recv));
+    let mut static_out = collect_ready::<Vec<_>, _>(&mut static_recv);
+    static_out.sort();
+    assert_eq!(&[("a", 2, Some(1)), ("b", 1, Some(2))], &*static_out);
+}
+
 #[test]
 pub fn test_group_by() {
     let (items_send, items_recv) = hydroflow::util::unbounded_channel::<(u32, Vec<u32>)>();
 
     let mut df = hydroflow_syntax! {
+        #![allow(unbounded_state)]
         source_stream(items_recv)
             -> group_by(Vec::new, |old: &mut Vec<u32>, mut x: Vec<u32>| old.append(&mut x))
             -> for_each(|v| print!("{:?}, ", v));
@@ -574,6 +1123,7 @@ pub fn test_surface_syntax_reachability_generated() {
     let (pairs_send, pairs_recv) = hydroflow::util::unbounded_channel::<(usize, usize)>();
 
     let mut df: Hydroflow = hydroflow_syntax! {
+        #![allow(unbounded_state)]
         reached_vertices = merge() -> map(|v| (v, ()));
         source_iter(vec![0]) -> [0]reached_vertices;
 
@@ -622,6 +1172,7 @@ pub fn test_transitive_closure() {
     let (pairs_send, pairs_recv) = hydroflow::util::unbounded_channel::<(usize, usize)>();
 
     let mut df = hydroflow_syntax! {
+        #![allow(unbounded_state)]
         // edge(x,y) :- link(x,y)
         edge_merge_tee = merge() -> tee();
         link_tee = tee();
@@ -689,6 +1240,7 @@ pub fn test_covid_tracing() {
     let (people_send, people_recv) = unbounded_channel::<(Pid, (Name, Phone))>();
 
     let mut hydroflow = hydroflow_syntax! {
+        #![allow(unbounded_state)]
         contacts = source_stream(contacts_recv) -> flat_map(|(pid_a, pid_b, time)| [(pid_a, (pid_b, time)), (pid_b, (pid_a, time))]);
 
         exposed = merge();
@@ -779,6 +1331,7 @@ pub fn test_reduce() {
     let (pairs_send, pairs_recv) = hydroflow::util::unbounded_channel::<(usize, usize)>();
 
     let mut df = hydroflow_syntax! {
+        #![allow(unbounded_state)]
         reached_vertices = merge() -> map(|v| (v, ()));
         source_iter(vec![0]) -> [0]reached_vertices;
 
@@ -812,3 +1365,335 @@ pub fn test_reduce() {
     pairs_send.send((0, 3)).unwrap();
     df.run_available();
 }
+
+#[test]
+fn test_handoff_capacity() {
+    let (out_send, mut out_recv) = hydroflow::util::unbounded_channel::<(usize, usize)>();
+
+    let source = futures::stream::iter(0..8);
+    let mut df = hydroflow_syntax! {
+        source_stream(source)
+            -> next_stratum()
+            -> for_each(|x| out_send.send((context.current_tick(), x)).unwrap());
+    };
+    // The source stops polling once the handoff to the next stratum is full.
+    df.set_handoff_capacity(Some(3));
+    df.run_available();
+
+    let out: Vec<_> = collect_ready(&mut out_recv);
+    assert_eq!(
+        &[
+            (0, 0),
+            (0, 1),
+            (0, 2),
+            (1, 3),
+            (1, 4),
+            (1, 5),
+            (2, 6),
+            (2, 7)
+        ],
+        &*out
+    );
+}
+
+#[test]
+fn test_run_budget() {
+    let (out_send, mut out_recv) = hydroflow::util::unbounded_channel::<usize>();
+
+    // A cycle which never runs out of work.
+    let mut df = hydroflow_syntax! {
+        #![allow(spinning_cycles)]
+        counter = merge() -> map(|x: usize| x + 1) -> tee();
+        source_iter([0]) -> [0]counter;
+        counter[0] -> [1]counter;
+        counter[1] -> for_each(|x| out_send.send(x).unwrap());
+    };
+    df.set_run_budget(RunBudget {
+        max_subgraph_runs: Some(10),
+        ..Default::default()
+    });
+    df.run_available();
+    assert!(df.has_available_work());
+    let out: Vec<_> = collect_ready(&mut out_recv);
+    assert!(!out.is_empty() && out.len() <= 10, "{:?}", out);
+
+    // Picks up where it left off.
+    df.run_available();
+    let more: Vec<_> = collect_ready(&mut out_recv);
+    assert_eq!(out.last().unwrap() + 1, more[0]);
+
+    df.set_run_budget(RunBudget {
+        max_duration: Some(std::time::Duration::from_millis(10)),
+        ..Default::default()
+    });
+    df.run_available();
+    assert!(df.has_available_work());
+}
+
+#[test]
+fn test_source_stream_item_budget() {
+    let (out_send, mut out_recv) = hydroflow::util::unbounded_channel::<usize>();
+
+    let source = futures::stream::iter(0..8);
+    let mut df = hydroflow_syntax! {
+        source_stream(source, 3) -> for_each(|x| out_send.send(x).unwrap());
+    };
+    // Run a single subgraph at a time to see each batch.
+    df.set_run_budget(RunBudget {
+        max_subgraph_runs: Some(1),
+        ..Default::default()
+    });
+
+    let mut batches = Vec::new();
+    while df.has_available_work() {
+        df.run_available();
+        batches.push(collect_ready::<Vec<_>, _>(&mut out_recv));
+    }
+    assert_eq!(vec![vec![0, 1, 2], vec![3, 4, 5], vec![6, 7]], batches);
+}
+
+#[test]
+fn test_priority_scheduler() {
+    use hydroflow::scheduled::scheduler::{FifoScheduler, LifoScheduler, PriorityScheduler};
+
+    fn run_order(set_scheduler: impl FnOnce(&mut Hydroflow)) -> Vec<&'static str> {
+        let (out_send, mut out_recv) = hydroflow::util::unbounded_channel::<&'static str>();
+        let (send_a, send_b) = (out_send.clone(), out_send.clone());
+        let mut df = hydroflow_syntax! {
+            source_iter(["background"]) -> priority(-1) -> for_each(|x| send_a.send(x).unwrap());
+            source_iter(["default"]) -> for_each(|x| send_b.send(x).unwrap());
+            source_iter(["request"]) -> priority(10) -> for_each(|x| out_send.send(x).unwrap());
+        };
+        set_scheduler(&mut df);
+        df.run_available();
+        collect_ready(&mut out_recv)
+    }
+
+    assert_eq!(
+        vec!["background", "default", "request"],
+        run_order(|df| df.set_scheduler::<FifoScheduler>())
+    );
+    assert_eq!(
+        vec!["request", "default", "background"],
+        run_order(|df| df.set_scheduler::<LifoScheduler>())
+    );
+    assert_eq!(
+        vec!["request", "default", "background"],
+        run_order(|df| df.set_scheduler::<PriorityScheduler>())
+    );
+}
+
+#[test]
+fn test_external_ports() {
+    let mut flow = hydroflow_syntax! {
+        inp = input::<(u32, String)>() -> tee();
+        lengths = output();
+        words = output();
+
+        inp[0] -> map(|(n, s): (u32, String)| n + s.len() as u32) -> lengths;
+        inp[1] -> map(|(_, s)| s) -> words;
+    };
+
+    flow.inp.send((1, "hello".to_owned())).unwrap();
+    flow.inp.send((10, "world!".to_owned())).unwrap();
+    flow.df.run_available();
+
+    let lengths: Vec<u32> = collect_ready(&mut flow.lengths);
+    assert_eq!(&[6, 16], &*lengths);
+    let words: Vec<String> = collect_ready(&mut flow.words);
+    assert_eq!(&["hello", "world!"], &*words);
+}
+
+#[test]
+fn test_implicit_tee_merge() {
+    let (out_send, mut out_recv) = hydroflow::util::unbounded_channel::<usize>();
+
+    let mut df = hydroflow_syntax! {
+        nums = source_iter([1, 2]) -> map(|x| x * 10);
+        out = map(|x| x + 1) -> for_each(|v| out_send.send(v).unwrap());
+
+        nums -> out;
+        nums -> map(|x| x * 10) -> out;
+        source_iter([3]) -> out;
+    };
+    df.run_available();
+
+    let mut out: Vec<_> = collect_ready(&mut out_recv);
+    out.sort_unstable();
+    assert_eq!(&[4, 11, 21, 101, 201], &*out);
+}
+
+#[test]
+fn test_edge_type_annotation() {
+    let (out_send, mut out_recv) = hydroflow::util::unbounded_channel::<(u64, usize)>();
+
+    let mut df = hydroflow_syntax! {
+        out = merge() -> for_each(|v| out_send.send(v).unwrap());
+        pairs = source_iter([(1, "hello"), (2, "world!")]) -> :(u64, &str) -> tee();
+        pairs -> map(|(k, v): (u64, &str)| (k * 10, v.len())) -> :(u64, usize) -> out;
+        pairs -> map(|(k, v): (u64, &str)| (k, v.len())) -> out;
+    };
+    df.run_available();
+
+    let mut out: Vec<_> = collect_ready(&mut out_recv);
+    out.sort_unstable();
+    assert_eq!(&[(1, 5), (2, 6), (10, 5), (20, 6)], &*out);
+}
+
+#[test]
+fn test_optimize() {
+    let (out_send, mut out_recv) = hydroflow::util::unbounded_channel::<usize>();
+
+    let mut df = hydroflow_syntax! {
+        #![optimize]
+        nums = source_iter(0..10_usize) -> identity() -> tee();
+        nums -> map(|x| x * 3)
+            -> filter(|&x| x % 2 == 0)
+            -> filter_map(|x: usize| x.checked_sub(6))
+            -> map(|x| x + 1)
+            -> for_each(|v| out_send.send(v).unwrap());
+        nums -> null();
+    };
+    df.run_available();
+
+    assert_eq!(&[1, 7, 13, 19], &*collect_ready::<Vec<_>, _>(&mut out_recv));
+}
+
+#[test]
+fn test_optimize_push_down_filters() {
+    let (out_send, mut out_recv) = hydroflow::util::unbounded_channel::<(usize, (usize, usize))>();
+
+    let mut df = hydroflow_syntax! {
+        #![optimize]
+        my_join = join()
+            -> filter_pure(|(k, (v1, _v2)): &(usize, (usize, usize))| 1 == k % 2 && 10 < *v1)
+            -> filter_pure(|&(_k, (_v1, v2))| 0 == v2 % 2)
+            -> for_each(|v| out_send.send(v).unwrap());
+        source_iter([(1, 5), (1, 15), (2, 25), (3, 35)]) -> [0]my_join;
+        source_iter([(1, 100), (1, 101), (2, 200), (3, 300)]) -> [1]my_join;
+    };
+    df.run_available();
+
+    let mut out: Vec<_> = collect_ready(&mut out_recv);
+    out.sort_unstable();
+    assert_eq!(&[(1, (15, 100)), (3, (35, 300))], &*out);
+}
+
+#[test]
+fn test_optimize_evaluates_args_once() {
+    let (out_send, mut out_recv) = hydroflow::util::unbounded_channel::<usize>();
+    let calls = std::rc::Rc::new(std::cell::Cell::new(0));
+    let make_mapper = {
+        let calls = calls.clone();
+        move || {
+            calls.set(calls.get() + 1);
+            |x: usize| x * 2
+        }
+    };
+
+    let mut df = hydroflow_syntax! {
+        #![optimize]
+        source_iter(0..5_usize)
+            -> map(make_mapper())
+            -> map(|x| x + 1)
+            -> for_each(|v| out_send.send(v).unwrap());
+    };
+    df.run_available();
+
+    assert_eq!(
+        &[1, 3, 5, 7, 9],
+        &*collect_ready::<Vec<_>, _>(&mut out_recv)
+    );
+    assert_eq!(1, calls.get());
+}
+
+hydroflow_module! {
+    /// Dedupes, then scales by `factor`.
+    dedupe_scale(factor) {
+        input = unique();
+        output = map(|x: usize| x * factor);
+        input -> output;
+    }
+}
+
+hydroflow_module! {
+    split_parity() {
+        input = tee();
+        evens = filter(|&x: &usize| x % 2 == 0);
+        odds = filter(|&x: &usize| x % 2 == 1);
+        input -> evens;
+        input -> odds;
+    }
+}
+
+hydroflow_module! {
+    /// Adds `x`, then keeps the items below 100. The filter's closure parameter shadows `x`.
+    add_below_100(x) {
+        input = map(|y: usize| y + x);
+        output = filter(|x: &usize| *x < 100);
+        input -> output;
+    }
+}
+
+#[test]
+fn test_module() {
+    let (out_send, mut out_recv) = hydroflow::util::unbounded_channel::<usize>();
+
+    let mut df = hydroflow_syntax! {
+        // Names inside the modules don't conflict with these.
+        input = source_iter([1, 1, 2, 3, 3]);
+        output = for_each(|v| out_send.send(v).unwrap());
+        input -> dedupe_scale!(10) -> dedupe_scale!(2) -> output;
+    };
+    df.run_available();
+
+    let mut out: Vec<_> = collect_ready(&mut out_recv);
+    out.sort_unstable();
+    assert_eq!(&[20, 40, 60], &*out);
+}
+
+#[test]
+fn test_module_shadowed_param() {
+    let (out_send, mut out_recv) = hydroflow::util::unbounded_channel::<usize>();
+
+    let mut df = hydroflow_syntax! {
+        source_iter([1, 50, 95]) -> add_below_100!(5) -> for_each(|v| out_send.send(v).unwrap());
+    };
+    df.run_available();
+
+    assert_eq!(&[6, 55], &*collect_ready::<Vec<_>, _>(&mut out_recv));
+}
+
+#[test]
+fn test_module_optimize() {
+    let (out_send, mut out_recv) = hydroflow::util::unbounded_channel::<usize>();
+
+    let mut df = hydroflow_syntax! {
+        #![optimize]
+        source_iter([1, 1, 2]) -> dedupe_scale!(10) -> map(|x| x + 1) -> for_each(|v| out_send.send(v).unwrap());
+    };
+    df.run_available();
+
+    let mut out: Vec<_> = collect_ready(&mut out_recv);
+    out.sort_unstable();
+    assert_eq!(&[11, 21], &*out);
+}
+
+#[test]
+fn test_module_named_ports() {
+    let (evens_send, mut evens_recv) = hydroflow::util::unbounded_channel::<usize>();
+    let (odds_send, mut odds_recv) = hydroflow::util::unbounded_channel::<usize>();
+
+    let mut df = hydroflow_syntax! {
+        parity = split_parity!();
+        source_iter(0..6) -> parity;
+        parity[evens] -> for_each(|v| evens_send.send(v).unwrap());
+        parity[odds] -> dedupe_scale!(100) -> for_each(|v| odds_send.send(v).unwrap());
+    };
+    df.run_available();
+
+    assert_eq!(&[0, 2, 4], &*collect_ready::<Vec<_>, _>(&mut evens_recv));
+    let mut odds: Vec<_> = collect_ready(&mut odds_recv);
+    odds.sort_unstable();
+    assert_eq!(&[100, 300, 500], &*odds);
+}
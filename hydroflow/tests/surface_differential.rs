@@ -0,0 +1,116 @@
+use hydroflow::hydroflow_syntax;
+use hydroflow::util::collect_ready;
+
+/// Maintains a view `(user, city)` over a KVS of `user -> zip` joined with `zip -> city`, where
+/// KVS entries are overwritten and deleted.
+#[test]
+pub fn test_diff_join_view() {
+    let (kvs_send, kvs_recv) = hydroflow::util::unbounded_channel::<((u32, &str), i64)>();
+    let (out_send, mut out_recv) = hydroflow::util::unbounded_channel::<((&str, &str), i64)>();
+
+    let mut df = hydroflow_syntax! {
+        view = diff_join()
+            -> map(|((_zip, (city, user)), diff)| ((city, user), diff))
+            -> consolidate()
+            -> for_each(|x| out_send.send(x).unwrap());
+        source_stream(kvs_recv) -> [1]view;
+        source_iter([((94110, "sf"), 1), ((10001, "nyc"), 1)]) -> [0]view;
+    };
+
+    kvs_send.send(((94110, "alice"), 1)).unwrap();
+    kvs_send.send(((10001, "bob"), 1)).unwrap();
+    df.run_available();
+    let mut out: Vec<_> = collect_ready(&mut out_recv);
+    out.sort();
+    assert_eq!(&[(("nyc", "bob"), 1), (("sf", "alice"), 1)], &*out);
+
+    // Alice moves to NYC: the old entry is retracted and the new one inserted.
+    kvs_send.send(((94110, "alice"), -1)).unwrap();
+    kvs_send.send(((10001, "alice"), 1)).unwrap();
+    df.run_available();
+    let mut out: Vec<_> = collect_ready(&mut out_recv);
+    out.sort();
+    assert_eq!(&[(("nyc", "alice"), 1), (("sf", "alice"), -1)], &*out);
+
+    // Bob is deleted.
+    kvs_send.send(((10001, "bob"), -1)).unwrap();
+    df.run_available();
+    assert_eq!(
+        &[(("nyc", "bob"), -1)],
+        &*collect_ready::<Vec<_>, _>(&mut out_recv)
+    );
+}
+
+#[test]
+pub fn test_diff_group_by_unique() {
+    let (input_send, input_recv) = hydroflow::util::unbounded_channel::<((&str, i64), i64)>();
+    let (sum_send, mut sum_recv) = hydroflow::util::unbounded_channel::<((&str, i64), i64)>();
+    let (keys_send, mut keys_recv) = hydroflow::util::unbounded_channel::<(&str, i64)>();
+
+    let mut df = hydroflow_syntax! {
+        input = source_stream(input_recv) -> tee();
+        input[0]
+            -> diff_group_by(|| 0, |sum: &mut i64, val: i64, diff: i64| *sum += val * diff)
+            -> for_each(|x| sum_send.send(x).unwrap());
+        input[1]
+            -> map(|((key, _val), diff)| (key, diff))
+            -> diff_unique()
+            -> for_each(|x| keys_send.send(x).unwrap());
+    };
+
+    input_send.send((("toy", 1), 1)).unwrap();
+    input_send.send((("toy", 2), 1)).unwrap();
+    df.run_available();
+    assert_eq!(
+        &[(("toy", 3), 1)],
+        &*collect_ready::<Vec<_>, _>(&mut sum_recv)
+    );
+    assert_eq!(&[("toy", 1)], &*collect_ready::<Vec<_>, _>(&mut keys_recv));
+
+    input_send.send((("toy", 2), -1)).unwrap();
+    df.run_available();
+    assert_eq!(
+        &[(("toy", 3), -1), (("toy", 1), 1)],
+        &*collect_ready::<Vec<_>, _>(&mut sum_recv)
+    );
+    assert_eq!(
+        &[] as &[(&str, i64)],
+        &*collect_ready::<Vec<_>, _>(&mut keys_recv)
+    );
+
+    input_send.send((("toy", 1), -1)).unwrap();
+    df.run_available();
+    assert_eq!(
+        &[(("toy", 1), -1)],
+        &*collect_ready::<Vec<_>, _>(&mut sum_recv)
+    );
+    assert_eq!(&[("toy", -1)], &*collect_ready::<Vec<_>, _>(&mut keys_recv));
+}
+
+#[test]
+pub fn test_diff_difference() {
+    let (pos_send, pos_recv) = hydroflow::util::unbounded_channel::<(&str, i64)>();
+    let (neg_send, neg_recv) = hydroflow::util::unbounded_channel::<(&str, i64)>();
+    let (out_send, mut out_recv) = hydroflow::util::unbounded_channel::<(&str, i64)>();
+
+    let mut df = hydroflow_syntax! {
+        diff = diff_difference() -> for_each(|x| out_send.send(x).unwrap());
+        source_stream(pos_recv) -> [pos]diff;
+        source_stream(neg_recv) -> [neg]diff;
+    };
+
+    pos_send.send(("a", 1)).unwrap();
+    pos_send.send(("b", 1)).unwrap();
+    df.run_available();
+    let mut out: Vec<_> = collect_ready(&mut out_recv);
+    out.sort();
+    assert_eq!(&[("a", 1), ("b", 1)], &*out);
+
+    neg_send.send(("a", 1)).unwrap();
+    df.run_available();
+    assert_eq!(&[("a", -1)], &*collect_ready::<Vec<_>, _>(&mut out_recv));
+
+    neg_send.send(("a", -1)).unwrap();
+    df.run_available();
+    assert_eq!(&[("a", 1)], &*collect_ready::<Vec<_>, _>(&mut out_recv));
+}
@@ -3,6 +3,7 @@ use hydroflow::hydroflow_parser;
 #[test]
 pub fn test_parser_basic() {
     hydroflow_parser! {
+        #![allow(unbounded_state)]
         reached_vertices = (merge() -> map(|v| (v, ())));
         (source_iter([0]) -> [0]reached_vertices);
 
@@ -15,6 +16,7 @@ pub fn test_parser_basic() {
     }
 
     hydroflow_parser! {
+        #![allow(spinning_cycles)]
         shuffle = (merge() -> tee());
         (shuffle[0] -> [0]shuffle);
         (shuffle[1] -> [1]shuffle);
@@ -23,11 +25,13 @@ pub fn test_parser_basic() {
     }
 
     hydroflow_parser! {
+        #![allow(spinning_cycles)]
         x = (map(a) -> map(b));
         (x -> x);
     }
 
     hydroflow_parser! {
+        #![allow(spinning_cycles)]
         a = map(a); // 0
         b = (merge() -> tee()); // 1
         c = merge(); // 2
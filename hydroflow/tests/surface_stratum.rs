@@ -72,6 +72,108 @@ pub fn test_difference_b() -> Result<(), SendError<&'static str>> {
     Ok(())
 }
 
+/// Difference with a `'static` `neg` input, items subtracted in an earlier tick stay subtracted.
+#[test]
+pub fn test_difference_static_neg() -> Result<(), SendError<&'static str>> {
+    let (pos_send, pos_recv) = hydroflow::util::unbounded_channel::<&'static str>();
+    let (neg_send, neg_recv) = hydroflow::util::unbounded_channel::<&'static str>();
+
+    let output = <Rc<RefCell<Vec<&'static str>>>>::default();
+    let output_inner = Rc::clone(&output);
+
+    let mut df: Hydroflow = hydroflow_syntax! {
+        #![allow(unbounded_state)]
+        a = difference::<'tick, 'static>();
+        source_stream(pos_recv) -> [pos]a;
+        source_stream(neg_recv) -> [neg]a;
+        a -> for_each(|x| output_inner.borrow_mut().push(x));
+    };
+
+    neg_send.send("02")?;
+    df.run_available();
+    assert_eq!(&[] as &[&str], &*output.take());
+
+    pos_send.send("01")?;
+    pos_send.send("02")?;
+    df.run_available();
+    assert_eq!(&["01"], &*output.take());
+
+    neg_send.send("01")?;
+    df.run_available();
+    pos_send.send("01")?;
+    pos_send.send("02")?;
+    pos_send.send("03")?;
+    df.run_available();
+    assert_eq!(&["03"], &*output.take());
+
+    Ok(())
+}
+
+/// Difference with a `'static` `pos` input, remembered items are re-emitted each tick unless
+/// subtracted in that tick.
+#[test]
+pub fn test_difference_static_pos() -> Result<(), SendError<&'static str>> {
+    let (pos_send, pos_recv) = hydroflow::util::unbounded_channel::<&'static str>();
+    let (neg_send, neg_recv) = hydroflow::util::unbounded_channel::<&'static str>();
+
+    let output = <Rc<RefCell<Vec<&'static str>>>>::default();
+    let output_inner = Rc::clone(&output);
+
+    let mut df: Hydroflow = hydroflow_syntax! {
+        #![allow(unbounded_state)]
+        a = difference::<'static, 'tick>();
+        source_stream(pos_recv) -> [pos]a;
+        source_stream(neg_recv) -> [neg]a;
+        a -> for_each(|x| output_inner.borrow_mut().push(x));
+    };
+
+    let take_sorted = || {
+        let mut output = output.take();
+        output.sort_unstable();
+        output
+    };
+
+    pos_send.send("01")?;
+    pos_send.send("02")?;
+    pos_send.send("01")?;
+    df.run_available();
+    assert_eq!(&["01", "02"], &*take_sorted());
+
+    neg_send.send("02")?;
+    df.run_available();
+    assert_eq!(&["01"], &*take_sorted());
+
+    pos_send.send("03")?;
+    pos_send.send("01")?;
+    df.run_available();
+    assert_eq!(&["01", "02", "03"], &*take_sorted());
+
+    Ok(())
+}
+
+/// Difference with a `'static` `pos` and `'tick` `neg` whose subgraph runs once for each item
+/// around the loop, all in one tick. Remembered items are not re-emitted on each run.
+#[test]
+pub fn test_difference_static_pos_multiple_runs() {
+    let output = <Rc<RefCell<Vec<usize>>>>::default();
+    let output_inner = Rc::clone(&output);
+
+    let mut df: Hydroflow = hydroflow_syntax! {
+        #![allow(unbounded_state)]
+        a = difference::<'static, 'tick>() -> tee();
+        loop_start = merge() -> [pos]a;
+        source_iter([0]) -> [0]loop_start;
+        source_iter([3]) -> [neg]a;
+        a[0] -> map(|x| x + 1) -> filter(|&x| x < 5) -> [1]loop_start;
+        a[1] -> for_each(|x| output_inner.borrow_mut().push(x));
+    };
+
+    df.run_tick();
+    let mut output = output.take();
+    output.sort_unstable();
+    assert_eq!(&[0, 1, 2], &*output);
+}
+
 #[test]
 pub fn test_tick_loop_1() {
     let output = <Rc<RefCell<Vec<usize>>>>::default();
@@ -170,6 +272,7 @@ pub fn test_surface_syntax_graph_unreachability() {
 
     #[allow(clippy::map_identity)]
     let mut df = hydroflow_syntax! {
+        #![allow(unbounded_state)]
         reached_vertices = merge() -> map(|v| (v, ()));
         source_iter(vec![0]) -> [0]reached_vertices;
 
@@ -98,7 +98,7 @@ pub fn test_warped_diamond_2() {
         nodes -> [0]init;
         new_node[1] -> map(|n| (n, 'b')) -> [1]init;
 
-        ntwk = source_iter([4, 5, 6]) -> tee();
+        _ntwk = source_iter([4, 5, 6]) -> tee();
     };
     df.run_available();
 }
@@ -6,7 +6,7 @@ use std::sync::mpsc;
 use hydroflow::scheduled::graph::Hydroflow;
 use hydroflow::scheduled::graph_ext::GraphExt;
 use hydroflow::scheduled::handoff::VecHandoff;
-use hydroflow::scheduled::port::{RecvCtx, SendCtx};
+use hydroflow::scheduled::port::{RecvCtx, RecvPort, SendCtx, SendPort};
 use hydroflow::{var_args, var_expr};
 
 #[test]
@@ -76,6 +76,80 @@ fn map_filter() {
     assert_eq!((*outputs).borrow().clone(), vec![4, 10]);
 }
 
+/// Helper: runs a source which offers the items `0..10` to a sink through the handoff `source` ->
+/// `sink`. Returns the batches the sink received and how many times the source ran.
+fn run_backpressure(
+    mut df: Hydroflow,
+    source: SendPort<VecHandoff<usize>>,
+    sink: RecvPort<VecHandoff<usize>>,
+) -> (Vec<Vec<usize>>, usize) {
+    let source_runs = Rc::new(Cell::new(0));
+    let inner_source_runs = source_runs.clone();
+    let mut data = (0..10).peekable();
+    df.add_subgraph(
+        "source",
+        var_expr!(),
+        var_expr!(source),
+        move |_ctx, var_args!(), var_args!(send)| {
+            inner_source_runs.set(inner_source_runs.get() + 1);
+            while let Some(&x) = data.peek() {
+                if send.try_give(Some(x)).is_err() {
+                    break;
+                }
+                data.next();
+            }
+        },
+    );
+
+    let batches = Rc::new(RefCell::new(Vec::new()));
+    let inner_batches = batches.clone();
+    df.add_subgraph(
+        "sink",
+        var_expr!(sink),
+        var_expr!(),
+        move |_ctx, var_args!(recv), var_args!()| {
+            let batch: Vec<_> = recv.take_inner().into_iter().collect();
+            (*inner_batches).borrow_mut().push(batch);
+        },
+    );
+
+    df.run_available();
+
+    let batches = batches.take();
+    (batches, source_runs.get())
+}
+
+#[test]
+fn test_bounded_handoff_backpressure() {
+    // The source offers items until the bounded handoff is full, then is not run again until the
+    // sink drains the handoff.
+    let mut df = Hydroflow::new();
+    let (source, sink) =
+        df.make_edge_with_handoff("source -> sink", VecHandoff::<usize>::bounded(3));
+
+    let (batches, source_runs) = run_backpressure(df, source, sink);
+    assert_eq!(
+        vec![vec![0, 1, 2], vec![3, 4, 5], vec![6, 7, 8], vec![9]],
+        batches
+    );
+    assert_eq!(4, source_runs);
+}
+
+#[test]
+fn test_handoff_capacity_later_edges() {
+    // The capacity also applies to handoffs added after it is set.
+    let mut df = Hydroflow::new();
+    df.set_handoff_capacity(Some(4));
+    let (source, sink) = df.make_edge::<_, VecHandoff<usize>>("source -> sink");
+
+    let (batches, source_runs) = run_backpressure(df, source, sink);
+    assert_eq!(
+        vec![vec![0, 1, 2, 3], vec![4, 5, 6, 7], vec![8, 9]],
+        batches
+    );
+    assert_eq!(3, source_runs);
+}
+
 #[test]
 fn test_basic_variadic() {
     let mut df = Hydroflow::new();
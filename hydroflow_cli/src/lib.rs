@@ -0,0 +1,125 @@
+//! Compiles, visualizes, and lints Hydroflow surface syntax files (`.hf`) outside of the
+//! `hydroflow_syntax!` macro. A `.hf` file contains the body of a `hydroflow_syntax!` invocation.
+//!
+//! Used by the `hydroflow` binary, and from `build.rs` scripts to generate code:
+//!
+//! ```ignore
+//! // build.rs
+//! let code = hydroflow_cli::codegen("src/graph.hf".as_ref(), "hydroflow").unwrap();
+//! let out_dir = std::path::PathBuf::from(std::env::var("OUT_DIR").unwrap());
+//! std::fs::write(out_dir.join("graph.rs"), code).unwrap();
+//! println!("cargo:rerun-if-changed=src/graph.hf");
+//!
+//! // src/main.rs
+//! let mut df = include!(concat!(env!("OUT_DIR"), "/graph.rs"));
+//! ```
+//!
+//! Diagnostics are printed to stderr as `path:line:column: level: message`. Functions return
+//! `Err(())` if there were any errors.
+
+use std::path::Path;
+
+use clap::ValueEnum;
+use hydroflow_lang::diagnostic::{Diagnostic, Level};
+use hydroflow_lang::graph::flat_graph::FlatGraph;
+use hydroflow_lang::graph::partitioned_graph::PartitionedGraph;
+use hydroflow_lang::parse::HfCode;
+use proc_macro2::TokenStream;
+
+/// Output formats for [`graph`].
+#[derive(Clone, Copy, ValueEnum, Debug)]
+pub enum GraphType {
+    /// Partitioned graph as a [mermaid](https://mermaid-js.github.io/) flowchart.
+    Mermaid,
+    /// Partitioned graph in graphviz dot.
+    Dot,
+    /// Partitioned graph as `SerdeGraph` JSON.
+    Json,
+    /// Graph before partitioning, as a mermaid flowchart.
+    FlatMermaid,
+}
+
+/// Checks the file at `path`, printing any errors and lint warnings.
+#[allow(clippy::result_unit_err)]
+pub fn check(path: &Path) -> Result<(), ()> {
+    partition(path, load(path)?).map(|_| ())
+}
+
+/// Returns the graph in the file at `path` in the `graph_type` format.
+#[allow(clippy::result_unit_err)]
+pub fn graph(path: &Path, graph_type: GraphType) -> Result<String, ()> {
+    let flat_graph = load(path)?;
+    if let GraphType::FlatMermaid = graph_type {
+        return Ok(flat_graph.mermaid_string());
+    }
+    let part_graph = partition(path, flat_graph)?;
+    Ok(match graph_type {
+        GraphType::Mermaid => part_graph.to_serde_graph().to_mermaid(),
+        GraphType::Dot => part_graph.to_serde_graph().to_dot(),
+        GraphType::Json => part_graph.serde_string(),
+        GraphType::FlatMermaid => unreachable!(),
+    })
+}
+
+/// Returns Rust code for the graph in the file at `path`, an expression evaluating to a
+/// `Hydroflow` instance, like `hydroflow_syntax!`. `root` is the path of the `hydroflow` crate.
+///
+/// The code may refer to variables in the scope where it is `include!`d.
+#[allow(clippy::result_unit_err)]
+pub fn codegen(path: &Path, root: &str) -> Result<String, ()> {
+    let root: TokenStream = root.parse().map_err(|err| {
+        eprintln!("error: invalid root path `{}`: {}", root, err);
+    })?;
+    let part_graph = partition(path, load(path)?)?;
+    Ok(format!("{}\n", part_graph.as_code(root, true)))
+}
+
+/// Helper: reads and parses the file at `path`, printing its diagnostics.
+fn load(path: &Path) -> Result<FlatGraph, ()> {
+    let source = std::fs::read_to_string(path).map_err(|err| {
+        eprintln!("error: failed to read `{}`: {}", path.display(), err);
+    })?;
+    let tokens: TokenStream = source.parse().map_err(|err| {
+        eprintln!("{}: error: {}", path.display(), err);
+    })?;
+    let hf_code: HfCode = syn::parse2(tokens).map_err(|err| {
+        print_diagnostic(path, &err.into());
+    })?;
+
+    let flat_graph = FlatGraph::from_hfcode(hf_code);
+    for diagnostic in flat_graph.diagnostics() {
+        print_diagnostic(path, diagnostic);
+    }
+    if flat_graph.diagnostics().iter().any(Diagnostic::is_error) {
+        return Err(());
+    }
+    Ok(flat_graph)
+}
+
+/// Helper: partitions `flat_graph`, printing the diagnostic if it fails.
+fn partition(path: &Path, flat_graph: FlatGraph) -> Result<PartitionedGraph, ()> {
+    flat_graph
+        .into_partitioned_graph()
+        .map_err(|diagnostic| print_diagnostic(path, &diagnostic))
+}
+
+/// Helper: prints `diagnostic` to stderr as `path:line:column: level: message`. The column is
+/// one-based, like rustc's.
+fn print_diagnostic(path: &Path, diagnostic: &Diagnostic) {
+    let start = diagnostic.span().start();
+    let level = match diagnostic.level() {
+        Level::Error => "error",
+        Level::Warning => "warning",
+        Level::Note => "note",
+        Level::Help => "help",
+        _ => "note",
+    };
+    eprintln!(
+        "{}:{}:{}: {}: {}",
+        path.display(),
+        start.line,
+        start.column + 1,
+        level,
+        diagnostic.message(),
+    );
+}
@@ -0,0 +1,59 @@
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use clap::{Parser, Subcommand};
+use hydroflow_cli::GraphType;
+
+/// Compiles, visualizes, and lints Hydroflow surface syntax (`.hf`) files.
+#[derive(Parser, Debug)]
+struct Opts {
+    #[clap(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Prints any errors and lint warnings.
+    Check { file: PathBuf },
+    /// Prints the graph as a diagram or as JSON.
+    Graph {
+        file: PathBuf,
+        #[clap(value_enum, long, default_value = "mermaid")]
+        format: GraphType,
+    },
+    /// Prints Rust code for the graph, which can be `include!`d.
+    Codegen {
+        file: PathBuf,
+        /// Path of the `hydroflow` crate in the generated code.
+        #[clap(long, default_value = "hydroflow")]
+        root: String,
+        /// Write the code to this file instead of stdout.
+        #[clap(long, short)]
+        output: Option<PathBuf>,
+    },
+}
+
+fn main() -> ExitCode {
+    let opts = Opts::parse();
+    let result = match opts.command {
+        Command::Check { file } => hydroflow_cli::check(&file),
+        Command::Graph { file, format } => {
+            hydroflow_cli::graph(&file, format).map(|graph| println!("{}", graph))
+        }
+        Command::Codegen { file, root, output } => {
+            hydroflow_cli::codegen(&file, &root).and_then(|code| match output {
+                Some(output) => std::fs::write(&output, code).map_err(|err| {
+                    eprintln!("error: failed to write `{}`: {}", output.display(), err);
+                }),
+                None => {
+                    print!("{}", code);
+                    Ok(())
+                }
+            })
+        }
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(()) => ExitCode::FAILURE,
+    }
+}
@@ -0,0 +1,54 @@
+use std::path::Path;
+use std::process::Command;
+
+use hydroflow_cli::GraphType;
+
+const GRAPH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/graph.hf");
+const ERROR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/error.hf");
+
+#[test]
+fn test_check() {
+    assert_eq!(Ok(()), hydroflow_cli::check(Path::new(GRAPH)));
+    assert_eq!(Err(()), hydroflow_cli::check(Path::new(ERROR)));
+}
+
+#[test]
+fn test_graph() {
+    let mermaid = hydroflow_cli::graph(Path::new(GRAPH), GraphType::Mermaid).unwrap();
+    assert!(mermaid.contains("flowchart"));
+    assert!(mermaid.contains("filter (| &amp; x | x &gt; 4)"));
+
+    let dot = hydroflow_cli::graph(Path::new(GRAPH), GraphType::Dot).unwrap();
+    assert!(dot.starts_with("digraph {"));
+
+    let json = hydroflow_cli::graph(Path::new(GRAPH), GraphType::Json).unwrap();
+    assert!(json.starts_with("{\"nodes\":"));
+
+    let flat_mermaid = hydroflow_cli::graph(Path::new(GRAPH), GraphType::FlatMermaid).unwrap();
+    assert!(flat_mermaid.contains("2:31 <tt>map (| x | x * 2)</tt>"));
+}
+
+#[test]
+fn test_codegen() {
+    let code = hydroflow_cli::codegen(Path::new(GRAPH), "::hydroflow").unwrap();
+    let expr: syn::Expr = syn::parse_str(&code).unwrap();
+    assert!(matches!(expr, syn::Expr::Block(_)));
+}
+
+#[test]
+fn test_diagnostic_locations() {
+    let output = Command::new(env!("CARGO_BIN_EXE_hydroflow"))
+        .args(["check", ERROR])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.contains(&format!(
+            "{}:2:12: error: Unknown operator `unknown_operator`",
+            ERROR
+        )),
+        "{}",
+        stderr,
+    );
+}
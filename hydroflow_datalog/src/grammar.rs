@@ -11,6 +11,7 @@ pub mod datalog {
     pub enum Declaration {
         Input(#[rust_sitter::leaf(text = ".input")] (), Ident),
         Output(#[rust_sitter::leaf(text = ".output")] (), Ident),
+        Size(#[rust_sitter::leaf(text = ".size")] (), Ident, IntLiteral),
         Rule(Rule),
     }
 
@@ -49,6 +50,12 @@ pub mod datalog {
         pub name: String,
     }
 
+    #[derive(Clone, PartialEq, Eq, Debug)]
+    pub struct IntLiteral {
+        #[rust_sitter::leaf(pattern = r"[0-9]+", transform = |s| s.to_string())]
+        pub value: String,
+    }
+
     #[rust_sitter::extra]
     struct Whitespace {
         #[rust_sitter::leaf(pattern = r"\s")]
@@ -1,9 +1,9 @@
-use std::collections::{btree_map::Entry, BTreeMap, HashMap};
-
-use hydroflow_lang::{
-    graph::flat_graph::FlatGraph,
-    parse::{ArrowConnector, IndexInt, Indexing, Pipeline, PipelineLink, PortIndex},
+use std::{
+    cmp::Reverse,
+    collections::{btree_map::Entry, BTreeMap, BTreeSet, HashMap},
 };
+
+use hydroflow_lang::graph::flat_graph::FlatGraph;
 use proc_macro2::Span;
 use syn::{self, parse_quote};
 
@@ -17,13 +17,102 @@ pub enum JoinPlan<'a> {
     Join(Box<JoinPlan<'a>>, Box<JoinPlan<'a>>),
 }
 
+/// The identifier used in atoms for fields whose values are ignored.
+pub const WILDCARD: &str = "_";
+
+/// Returns the names of the variables bound by an atom, skipping wildcards.
+fn atom_variables(atom: &Atom) -> impl Iterator<Item = &str> {
+    atom.fields
+        .iter()
+        .map(|field| &*field.name)
+        .filter(|name| *name != WILDCARD)
+}
+
+/// Cardinality assumed for relations that have no `.size` hint.
+const DEFAULT_CARDINALITY: u64 = 1_000;
+
+impl<'a> JoinPlan<'a> {
+    /// Builds a left-deep join plan for the atoms in the body of a rule.
+    ///
+    /// The plan starts from the smallest relation and then greedily joins in the atom that shares
+    /// the most variables with the atoms joined so far, so a cross product is only emitted when no
+    /// remaining atom shares a variable with the current intermediate result. Ties are broken by
+    /// the estimated cardinality from `size_hints` (relations without a hint are assumed to have
+    /// [`DEFAULT_CARDINALITY`] rows), and then by the order the atoms are written in the rule.
+    pub fn plan(sources: &'a [Atom], size_hints: &HashMap<String, u64>) -> Self {
+        let cardinality = |atom: &Atom| {
+            size_hints
+                .get(&atom.name.name)
+                .copied()
+                .unwrap_or(DEFAULT_CARDINALITY)
+        };
+
+        let mut remaining = sources.iter().collect::<Vec<_>>();
+        let first_idx = remaining
+            .iter()
+            .enumerate()
+            .min_by_key(|(i, atom)| (cardinality(atom), *i))
+            .map(|(i, _)| i)
+            .expect("Rule must have at least one source");
+
+        let first = remaining.remove(first_idx);
+        let mut bound_variables = atom_variables(first).collect::<BTreeSet<_>>();
+        let mut plan = JoinPlan::Source(first);
+
+        while !remaining.is_empty() {
+            let next_idx = remaining
+                .iter()
+                .enumerate()
+                .min_by_key(|(i, atom)| {
+                    let shared_variables = atom_variables(atom)
+                        .filter(|name| bound_variables.contains(name))
+                        .collect::<BTreeSet<_>>()
+                        .len();
+                    (Reverse(shared_variables), cardinality(atom), *i)
+                })
+                .map(|(i, _)| i)
+                .unwrap();
+
+            let next = remaining.remove(next_idx);
+            bound_variables.extend(atom_variables(next));
+            plan = JoinPlan::Join(Box::new(plan), Box::new(JoinPlan::Source(next)));
+        }
+
+        plan
+    }
+
+    /// A string that uniquely identifies this plan, used to find subplans that are shared
+    /// between rules.
+    pub fn key(&self) -> String {
+        match self {
+            JoinPlan::Source(atom) => format!(
+                "{}({})",
+                atom.name.name,
+                atom.fields
+                    .iter()
+                    .map(|field| &*field.name)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            JoinPlan::Join(lhs, rhs) => format!("({} * {})", lhs.key(), rhs.key()),
+        }
+    }
+
+    /// Counts how many times each subplan (including this plan) is used, keyed by [`Self::key`].
+    pub fn count_subplans(&self, counts: &mut HashMap<String, usize>) {
+        *counts.entry(self.key()).or_default() += 1;
+        if let JoinPlan::Join(lhs, rhs) = self {
+            lhs.count_subplans(counts);
+            rhs.count_subplans(counts);
+        }
+    }
+}
+
 /// Tracks the Hydroflow node that corresponds to a subtree of a join plan.
+#[derive(Clone)]
 pub struct IntermediateJoinNode {
     /// The name of the Hydroflow node that this join outputs to.
     pub name: syn::Ident,
-    /// If this join node outputs data through a `tee()` operator, this is the index to consume the node with.
-    /// (this is only used for cases where we are directly reading a relation)
-    pub tee_idx: Option<isize>,
     /// A mapping from variables in the rule to the index of the corresponding element in the flattened tuples this node emits.
     pub variable_mapping: BTreeMap<syn::Ident, usize>,
     /// The type of the flattened tuples this node emits.
@@ -74,31 +163,16 @@ fn emit_join_input_pipeline(
 
     let source_name = &source_expanded.name;
     let source_type = &source_expanded.tuple_type;
-    flat_graph.add_statement(hydroflow_lang::parse::HfStatement::Pipeline(
-        Pipeline::Link(PipelineLink {
-            lhs: Box::new(parse_quote!(#source_name)),
-            connector: ArrowConnector {
-                src: source_expanded.tee_idx.map(|i| Indexing {
-                    bracket_token: syn::token::Bracket::default(),
-                    index: PortIndex::Int(IndexInt {
-                        value: i,
-                        span: Span::call_site(),
-                    }),
-                }),
-                arrow: parse_quote!(->),
-                dst: None,
-            },
-            rhs: Box::new(parse_quote! {
-                map(|v: #source_type| ((#(#hash_keys, )*), v)) -> [#out_index] #join_node
-            }),
-        }),
-    ));
+    flat_graph.add_statement(parse_quote! {
+        #source_name -> map(|v: #source_type| ((#(#hash_keys, )*), v)) -> [#out_index] #join_node
+    });
 }
 
 /// Creates a mapping from variable names to the indices where that variable appears in `fields`.
 ///
-/// Only return entries for variables that appear more than once. Those correspond to additional
-/// constraints: the relation is only true when the values at those indices are equal.
+/// Only return entries for variables that appear more than once, which correspond to additional
+/// constraints: the relation is only true when the values at those indices are equal. Wildcards
+/// are never constrained.
 ///
 /// For example, `rel(a, b, a) := ...` requires that the values in the 0th and 2nd slots be the
 /// same, so we would return a map `{ "a" => [0, 2] }`. Note that since `b` is not repeated, it is
@@ -108,6 +182,10 @@ fn find_relation_local_constraints(
 ) -> BTreeMap<String, Vec<usize>> {
     let mut indices_grouped_by_var = BTreeMap::new();
     for (i, ident) in fields.iter().enumerate() {
+        if ident.name == WILDCARD {
+            continue;
+        }
+
         let entry = indices_grouped_by_var
             // TODO(shadaj): Can we avoid cloning here?
             .entry(ident.name.clone())
@@ -146,14 +224,45 @@ fn build_local_constraint_conditions(constraints: &BTreeMap<String, Vec<usize>>)
         .unwrap()
 }
 
+/// Tracks the join subplans that are used by more than one rule, so that they are only computed
+/// once and then shared between their consumers.
+#[derive(Default)]
+pub struct SharedSubplans {
+    /// The number of times each subplan is used, keyed by [`JoinPlan::key`].
+    pub counts: HashMap<String, usize>,
+    /// The already-expanded shared subplans.
+    pub expanded: HashMap<String, IntermediateJoinNode>,
+}
+
 /// Generates a Hydroflow pipeline that computes the output to a given [`JoinPlan`].
+///
+/// Subplans that are used more than once according to `shared` are only expanded the first time
+/// they are seen, later uses read from the first expansion. The graph inserts the needed `tee()`s.
 pub fn expand_join_plan(
     // The plan we are converting to a Hydroflow pipeline.
     plan: &JoinPlan,
     // The Hydroflow graph to emit the pipeline to.
     flat_graph: &mut FlatGraph,
-    tee_counter: &mut HashMap<String, Counter>,
     next_join_idx: &mut Counter,
+    shared: &mut SharedSubplans,
+) -> IntermediateJoinNode {
+    let key = plan.key();
+    if let Some(node) = shared.expanded.get(&key) {
+        return node.clone();
+    }
+
+    let expanded = expand_join_plan_unshared(plan, flat_graph, next_join_idx, shared);
+    if shared.counts.get(&key).copied().unwrap_or(0) > 1 {
+        shared.expanded.insert(key, expanded.clone());
+    }
+    expanded
+}
+
+fn expand_join_plan_unshared(
+    plan: &JoinPlan,
+    flat_graph: &mut FlatGraph,
+    next_join_idx: &mut Counter,
+    shared: &mut SharedSubplans,
 ) -> IntermediateJoinNode {
     match plan {
         JoinPlan::Source(target) => {
@@ -165,24 +274,20 @@ pub fn expand_join_plan(
             for (i, ident) in target.fields.iter().enumerate() {
                 row_types.push(parse_quote!(_));
 
+                if ident.name == WILDCARD {
+                    continue;
+                }
+
                 let variable_ident = syn::Ident::new(&ident.name, Span::call_site());
                 if let Entry::Vacant(e) = variable_mapping.entry(variable_ident) {
                     e.insert(i);
                 }
             }
 
-            // Because this is a node corresponding to some Datalog relation, we need to tee from it.
-            let tee_index = tee_counter
-                .entry(target.name.name.clone())
-                .or_insert_with(|| 0..)
-                .next()
-                .expect("Out of tee indices");
-
             let row_type = parse_quote!((#(#row_types, )*));
 
             if !local_constraints.is_empty() {
                 let relation_node = syn::Ident::new(&target.name.name, Span::call_site());
-                let relation_idx = syn::LitInt::new(&tee_index.to_string(), Span::call_site());
 
                 let filter_node = syn::Ident::new(
                     &format!(
@@ -195,27 +300,25 @@ pub fn expand_join_plan(
                 let conditions = build_local_constraint_conditions(&local_constraints);
 
                 flat_graph.add_statement(parse_quote! {
-                    #filter_node = #relation_node [#relation_idx] -> filter(|&row: &#row_type| #conditions)
+                    #filter_node = #relation_node -> filter_pure(|&row: &#row_type| #conditions)
                 });
 
                 IntermediateJoinNode {
                     name: filter_node,
-                    tee_idx: None,
                     variable_mapping,
                     tuple_type: row_type,
                 }
             } else {
                 IntermediateJoinNode {
                     name: syn::Ident::new(&target.name.name, Span::call_site()),
-                    tee_idx: Some(tee_index),
                     variable_mapping,
                     tuple_type: row_type,
                 }
             }
         }
         JoinPlan::Join(lhs, rhs) => {
-            let left_expanded = expand_join_plan(lhs, flat_graph, tee_counter, next_join_idx);
-            let right_expanded = expand_join_plan(rhs, flat_graph, tee_counter, next_join_idx);
+            let left_expanded = expand_join_plan(lhs, flat_graph, next_join_idx, shared);
+            let right_expanded = expand_join_plan(rhs, flat_graph, next_join_idx, shared);
 
             let identifiers_to_join = right_expanded
                 .variable_mapping
@@ -293,7 +396,6 @@ pub fn expand_join_plan(
 
             IntermediateJoinNode {
                 name: join_node,
-                tee_idx: None,
                 variable_mapping: flattened_mapping,
                 tuple_type: parse_quote!((#(#output_types, )*)),
             }
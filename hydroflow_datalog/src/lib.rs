@@ -1,9 +1,7 @@
 use std::collections::{HashMap, HashSet};
 
-use hydroflow_lang::{
-    graph::flat_graph::FlatGraph,
-    parse::{ArrowConnector, IndexInt, Indexing, Pipeline, PipelineLink},
-};
+use hydroflow_lang::diagnostic::{Diagnostic, Level};
+use hydroflow_lang::graph::flat_graph::FlatGraph;
 use proc_macro2::{Span, TokenStream};
 use quote::{quote, ToTokens};
 use syn::parse_quote;
@@ -16,7 +14,8 @@ use grammar::datalog::*;
 use join_plan::*;
 use util::Counter;
 
-fn gen_hydroflow_graph(literal: proc_macro2::Literal) -> FlatGraph {
+fn gen_hydroflow_graph(literal: proc_macro2::Literal) -> Result<FlatGraph, Vec<Diagnostic>> {
+    let span = literal.span();
     let str_node: syn::LitStr = parse_quote!(#literal);
     let actual_str = str_node.value();
     let program: Program = grammar::datalog::parse(&actual_str).unwrap();
@@ -24,24 +23,40 @@ fn gen_hydroflow_graph(literal: proc_macro2::Literal) -> FlatGraph {
     let mut inputs = Vec::new();
     let mut outputs = Vec::new();
     let mut rules = Vec::new();
+    let mut size_hints = HashMap::new();
+    let mut diagnostics = Vec::new();
 
     for stmt in &program.rules {
         match stmt {
             Declaration::Input(_, ident) => inputs.push(ident),
             Declaration::Output(_, ident) => outputs.push(ident),
+            Declaration::Size(_, ident, size) => match size.value.parse::<u64>() {
+                Ok(value) => {
+                    size_hints.insert(ident.name.clone(), value);
+                }
+                Err(_) => diagnostics.push(Diagnostic::spanned(
+                    span,
+                    Level::Error,
+                    format!(
+                        "Size of `{}` is too large, expected at most `{}`: `{}`.",
+                        ident.name,
+                        u64::MAX,
+                        size.value
+                    ),
+                )),
+            },
             Declaration::Rule(rule) => rules.push(rule),
         }
     }
 
     let mut flat_graph = FlatGraph::default();
-    let mut tee_counter = HashMap::new();
-    let mut merge_counter = HashMap::new();
 
     let mut created_rules = HashSet::new();
     for decl in &program.rules {
         let target_ident = match decl {
             Declaration::Input(_, ident) => ident.clone(),
             Declaration::Output(_, ident) => ident.clone(),
+            Declaration::Size(..) => continue,
             Declaration::Rule(rule) => rule.target.name.clone(),
         };
 
@@ -55,50 +70,51 @@ fn gen_hydroflow_graph(literal: proc_macro2::Literal) -> FlatGraph {
     for target in inputs {
         let target_ident = syn::Ident::new(&target.name, Span::call_site());
 
-        let my_merge_index = merge_counter
-            .entry(target.name.clone())
-            .or_insert_with(|| 0..)
-            .next()
-            .expect("Out of merge indices");
-
-        let my_merge_index_lit =
-            syn::LitInt::new(&format!("{}", my_merge_index), Span::call_site());
-        let name = syn::Ident::new(&target.name, Span::call_site());
-
         flat_graph.add_statement(parse_quote! {
-            source_stream(#target_ident) -> [#my_merge_index_lit] #name
+            source_stream(#target_ident) -> #target_ident
         });
     }
 
     for target in outputs {
-        let my_tee_index = tee_counter
-            .entry(target.name.clone())
-            .or_insert_with(|| 0..)
-            .next()
-            .expect("Out of tee indices");
-
-        let out_send_ident = syn::Ident::new(&target.name, Span::call_site());
-
-        let my_tee_index_lit = syn::LitInt::new(&format!("{}", my_tee_index), Span::call_site());
         let target_ident = syn::Ident::new(&target.name, Span::call_site());
 
         flat_graph.add_statement(parse_quote! {
-            #target_ident [#my_tee_index_lit] -> for_each(|v| #out_send_ident.send(v).unwrap())
+            #target_ident -> for_each(|v| #target_ident.send(v).unwrap())
         });
     }
 
+    let plans = rules
+        .iter()
+        .map(|rule| JoinPlan::plan(&rule.sources, &size_hints))
+        .collect::<Vec<_>>();
+
+    // Rules with identical bodies (or common join prefixes) share the corresponding subplans.
+    let mut shared = SharedSubplans::default();
+    for plan in &plans {
+        plan.count_subplans(&mut shared.counts);
+    }
+
     let mut next_join_idx = 0..;
-    for rule in rules {
+    for (rule, plan) in rules.into_iter().zip(plans) {
         generate_rule(
             rule,
+            &plan,
             &mut flat_graph,
-            &mut tee_counter,
-            &mut merge_counter,
             &mut next_join_idx,
+            &mut shared,
+            span,
+            &mut diagnostics,
         );
     }
+    if !diagnostics.is_empty() {
+        return Err(diagnostics);
+    }
 
-    flat_graph
+    flat_graph.insert_implicit_tee_merge();
+    // Datalog relations are sets, so duplicates dropped by projections don't matter.
+    flat_graph.push_down_projections();
+    flat_graph.optimize();
+    Ok(flat_graph)
 }
 
 fn hydroflow_graph_to_program(flat_graph: FlatGraph, root: TokenStream) -> syn::Stmt {
@@ -114,24 +130,34 @@ fn hydroflow_graph_to_program(flat_graph: FlatGraph, root: TokenStream) -> syn::
 
 fn generate_rule(
     rule: &Rule,
+    plan: &JoinPlan,
     flat_graph: &mut FlatGraph,
-    tee_counter: &mut HashMap<String, Counter>,
-    merge_counter: &mut HashMap<String, Counter>,
     next_join_idx: &mut Counter,
+    shared: &mut SharedSubplans,
+    span: Span,
+    diagnostics: &mut Vec<Diagnostic>,
 ) {
     let target = &rule.target.name;
     let target_ident = syn::Ident::new(&target.name, Span::call_site());
 
-    let sources: Vec<Atom> = rule.sources.to_vec();
-
-    // TODO(shadaj): smarter plans
-    let plan = sources
+    if rule
+        .target
+        .fields
         .iter()
-        .map(JoinPlan::Source)
-        .reduce(|a, b| JoinPlan::Join(Box::new(a), Box::new(b)))
-        .unwrap();
+        .any(|field| field.name == WILDCARD)
+    {
+        diagnostics.push(Diagnostic::spanned(
+            span,
+            Level::Error,
+            format!(
+                "Wildcards cannot be used in the head of a rule: `{}`.",
+                target.name
+            ),
+        ));
+        return;
+    }
 
-    let out_expanded = expand_join_plan(&plan, flat_graph, tee_counter, next_join_idx);
+    let out_expanded = expand_join_plan(plan, flat_graph, next_join_idx, shared);
 
     let output_tuple_elems = rule
         .target
@@ -152,39 +178,10 @@ fn generate_rule(
     let after_join_map: syn::Expr =
         parse_quote!(|row: #flattened_tuple_type| (#(#output_tuple_elems, )*));
 
-    let my_merge_index = merge_counter
-        .entry(target.name.clone())
-        .or_insert_with(|| 0..)
-        .next()
-        .expect("Out of merge indices");
-
-    let my_merge_index_lit = syn::LitInt::new(&format!("{}", my_merge_index), Span::call_site());
-
-    let after_join: Pipeline = parse_quote! {
-        map(#after_join_map) -> [#my_merge_index_lit] #target_ident
-    };
-
     let out_name = out_expanded.name;
-    flat_graph.add_statement(hydroflow_lang::parse::HfStatement::Pipeline(
-        Pipeline::Link(PipelineLink {
-            lhs: Box::new(parse_quote!(#out_name)),
-            connector: ArrowConnector {
-                // if the output comes with a tee index, we must read with that
-                // this only happens when we are directly outputting a transformation
-                // of a single relation on the RHS
-                src: out_expanded.tee_idx.map(|i| Indexing {
-                    bracket_token: syn::token::Bracket::default(),
-                    index: hydroflow_lang::parse::PortIndex::Int(IndexInt {
-                        value: i,
-                        span: Span::call_site(),
-                    }),
-                }),
-                arrow: parse_quote!(->),
-                dst: None,
-            },
-            rhs: Box::new(after_join),
-        }),
-    ));
+    flat_graph.add_statement(parse_quote! {
+        #out_name -> map(#after_join_map) -> #target_ident
+    });
 }
 
 #[proc_macro]
@@ -204,9 +201,16 @@ pub fn datalog(item: proc_macro::TokenStream) -> proc_macro::TokenStream {
         }
     };
 
-    let graph = gen_hydroflow_graph(literal);
-    let program = hydroflow_graph_to_program(graph, root);
-    proc_macro::TokenStream::from(program.to_token_stream())
+    match gen_hydroflow_graph(literal) {
+        Ok(graph) => {
+            let program = hydroflow_graph_to_program(graph, root);
+            proc_macro::TokenStream::from(program.to_token_stream())
+        }
+        Err(diagnostics) => {
+            diagnostics.iter().for_each(Diagnostic::emit);
+            proc_macro::TokenStream::from(quote! { #root::scheduled::graph::Hydroflow::new() })
+        }
+    }
 }
 
 #[cfg(test)]
@@ -218,14 +222,14 @@ mod tests {
 
     macro_rules! test_snapshots {
         ($program:literal) => {
-            let graph = gen_hydroflow_graph(parse_quote!($program));
+            let graph = gen_hydroflow_graph(parse_quote!($program)).unwrap();
 
             insta::with_settings!({snapshot_suffix => "surface_graph"}, {
                 insta::assert_display_snapshot!(graph.surface_syntax_string());
             });
 
             // Have to make a new graph as the above closure borrows.
-            let graph2 = gen_hydroflow_graph(parse_quote!($program));
+            let graph2 = gen_hydroflow_graph(parse_quote!($program)).unwrap();
             let out = &hydroflow_graph_to_program(graph2, quote::quote! { hydroflow });
             let wrapped: syn::File = parse_quote! {
                 fn main() {
@@ -339,4 +343,102 @@ mod tests {
             "#
         );
     }
+
+    #[test]
+    fn join_order_avoids_cross_product() {
+        test_snapshots!(
+            r#"
+            .input in1
+            .input in2
+            .input in3
+            .output out
+
+            out(a, b, c, d) :- in1(a, b), in3(c, d), in2(b, c).
+            "#
+        );
+    }
+
+    #[test]
+    fn wildcard_fields() {
+        test_snapshots!(
+            r#"
+            .input in1
+            .input in2
+            .output out
+
+            out(x) :- in1(x, _), in2(_, x, _).
+            "#
+        );
+    }
+
+    #[test]
+    fn shared_rule_bodies() {
+        test_snapshots!(
+            r#"
+            .input in1
+            .input in2
+            .output out1
+            .output out2
+
+            out1(x, z) :- in1(x, y), in2(y, z).
+            out2(z, x) :- in1(x, y), in2(y, z).
+            "#
+        );
+    }
+
+    #[test]
+    fn join_order_size_hints() {
+        test_snapshots!(
+            r#"
+            .input edge
+            .input seed
+            .output out
+
+            .size edge 1000000
+            .size seed 10
+
+            out(x, z) :- edge(x, y), edge(y, z), seed(y).
+            "#
+        );
+    }
+
+    #[test]
+    fn wildcard_in_head() {
+        let Err(diagnostics) = gen_hydroflow_graph(parse_quote!(
+            r#"
+            .input in1
+            .output out
+
+            out(x, _) :- in1(x, y).
+            "#
+        )) else {
+            panic!("expected an error");
+        };
+        assert_eq!(1, diagnostics.len());
+        assert_eq!(
+            "Wildcards cannot be used in the head of a rule: `out`.",
+            diagnostics[0].message()
+        );
+    }
+
+    #[test]
+    fn size_hint_overflow() {
+        let Err(diagnostics) = gen_hydroflow_graph(parse_quote!(
+            r#"
+            .input in1
+            .output out
+
+            .size in1 99999999999999999999
+
+            out(x) :- in1(x).
+            "#
+        )) else {
+            panic!("expected an error");
+        };
+        assert_eq!(1, diagnostics.len());
+        assert_eq!(
+            "Size of `in1` is too large, expected at most `18446744073709551615`: `99999999999999999999`.",
+            diagnostics[0].message()
+        );
+    }
 }
@@ -0,0 +1,61 @@
+//! An example of a crate which defines its own operators, usable from its own
+//! `hydroflow_syntax!` macro along with the built-in operators. See
+//! [`hydroflow_lang::hydroflow_syntax_with_operators`].
+
+use hydroflow_lang::graph::ops::{
+    OperatorConstraints, OperatorWriteOutput, WriteContextArgs, WriteIteratorArgs, RANGE_0, RANGE_1,
+};
+use hydroflow_lang::hydroflow_syntax_with_operators;
+use quote::{quote, quote_spanned};
+
+/// > 0 input streams, 1 output stream
+///
+/// > Arguments: A [`std::sync::mpsc::Receiver`].
+///
+/// Outputs the items which are ready in the receiver each time the subgraph runs, like a
+/// message bus. Unlike `source_stream`, new items do not schedule the subgraph.
+const SOURCE_BUS: OperatorConstraints = OperatorConstraints {
+    name: "source_bus",
+    hard_range_inn: RANGE_0,
+    soft_range_inn: RANGE_0,
+    hard_range_out: RANGE_1,
+    soft_range_out: RANGE_1,
+    ports_inn: None,
+    ports_out: None,
+    num_args: RANGE_1,
+    input_delaytype_fn: &|_| None,
+    write_fn: &(|wc @ &WriteContextArgs { op_span, .. },
+                 &WriteIteratorArgs {
+                     ident, arguments, ..
+                 },
+                 _| {
+        let receiver_ident = wc.make_ident("receiver");
+        let write_prologue = quote_spanned! {op_span=>
+            let #receiver_ident: ::std::sync::mpsc::Receiver<_> = #arguments;
+        };
+        let write_iterator = quote_spanned! {op_span=>
+            let #ident = #receiver_ident.try_iter();
+        };
+        // `OperatorWriteOutput` is `#[non_exhaustive]`, so it is built from its default outside
+        // of `hydroflow_lang`.
+        let mut output = OperatorWriteOutput::default();
+        output.write_prologue = write_prologue;
+        output.write_iterator = write_iterator;
+        Ok(output)
+    }),
+};
+
+/// The operators defined by this crate.
+const OPERATORS: &[OperatorConstraints] = &[SOURCE_BUS];
+
+/// `hydroflow_syntax!` with the operators of this crate.
+#[proc_macro]
+pub fn hydroflow_syntax(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    hydroflow_syntax_with_operators(
+        input.into(),
+        quote! { hydroflow },
+        quote! { hydroflow_example_ops::hydroflow_syntax },
+        OPERATORS,
+    )
+    .into()
+}
@@ -0,0 +1,20 @@
+use hydroflow::util::collect_ready;
+use hydroflow_example_ops::hydroflow_syntax;
+
+/// Runs a graph with the custom `source_bus()` and built-in operators.
+#[test]
+fn test_source_bus() {
+    let (bus_send, bus_recv) = std::sync::mpsc::channel::<usize>();
+    let (out_send, mut out_recv) = hydroflow::util::unbounded_channel::<usize>();
+
+    let mut df = hydroflow_syntax! {
+        source_bus(bus_recv)
+            -> map(|x| x * 10)
+            -> for_each(|x| out_send.send(x).unwrap());
+    };
+
+    bus_send.send(1).unwrap();
+    bus_send.send(2).unwrap();
+    df.run_available();
+    assert_eq!(&[10, 20], &*collect_ready::<Vec<_>, _>(&mut out_recv));
+}
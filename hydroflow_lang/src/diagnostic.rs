@@ -32,6 +32,15 @@ impl Diagnostic {
     pub fn is_error(&self) -> bool {
         self.level.is_error()
     }
+    pub fn span(&self) -> Span {
+        self.span
+    }
+    pub fn level(&self) -> Level {
+        self.level
+    }
+    pub fn message(&self) -> &str {
+        &self.message
+    }
     pub fn emit(&self) {
         let pm_diag = match self.level {
             Level::Error => self.span.unwrap().error(&*self.message),
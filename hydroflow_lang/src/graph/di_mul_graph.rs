@@ -102,6 +102,16 @@ where
         e
     }
 
+    /// Remove an edge, returning its source and destination nodes.
+    ///
+    /// Returns None if the edge doesn't exist.
+    pub fn remove_edge(&mut self, edge: E) -> Option<(V, V)> {
+        let (src, dst) = self.edges.remove(edge)?;
+        self.succs[src].retain(|&e| e != edge);
+        self.preds[dst].retain(|&e| e != edge);
+        Some((src, dst))
+    }
+
     /// For an edge E from A -> B, insert a new node NODE along that edge to
     /// create A -> NODE -> B. Returns the edge ID into and out of NODE
     /// respectively.
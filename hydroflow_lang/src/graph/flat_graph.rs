@@ -6,10 +6,10 @@ use proc_macro2::Span;
 use quote::ToTokens;
 use slotmap::{Key, SecondaryMap, SlotMap};
 use syn::spanned::Spanned;
-use syn::Ident;
+use syn::{parse_quote_spanned, Ident};
 
 use crate::diagnostic::{Diagnostic, Level};
-use crate::graph::ops::{PortListSpec, RangeTrait, OPERATORS};
+use crate::graph::ops::{find_operator, OperatorConstraints, PortListSpec, RangeTrait, OPERATORS};
 use crate::parse::{HfCode, HfStatement, Operator, Pipeline};
 use crate::pretty_span::{PrettyRowCol, PrettySpan};
 
@@ -33,9 +33,23 @@ pub struct FlatGraph {
     pub(crate) ports: SecondaryMap<GraphEdgeId, (PortIndexValue, PortIndexValue)>,
     /// Spanned error/warning/etc diagnostics to emit.
     pub(crate) diagnostics: Vec<Diagnostic>,
+    /// Operators defined outside of this crate, in addition to the built-in operators.
+    pub(crate) custom_operators: &'static [OperatorConstraints],
 
     /// Variable names, used as [`HfStatement::Named`] are added.
     names: BTreeMap<Ident, Ends>,
+    /// Variable names of the enclosing scopes, while adding the statements of a module. These are
+    /// not visible inside the module.
+    outer_names: Vec<BTreeMap<Ident, Ends>>,
+    /// The names in each module, which are its ports.
+    modules: Vec<BTreeMap<Ident, Ends>>,
+    /// The variable name of the nodes at the ends of each named pipeline. Used to name the
+    /// handles of external port operators.
+    pub(crate) node_names: SecondaryMap<GraphNodeId, Ident>,
+    /// Top-level variable names which are used, for linting unused names.
+    pub(crate) used_names: BTreeSet<Ident>,
+    /// Description of each rewrite made by [`Self::optimize`].
+    pub(crate) optimization_log: Vec<String>,
 }
 
 impl FlatGraph {
@@ -43,13 +57,55 @@ impl FlatGraph {
     ///
     /// TODO(mingwei): better error/diagnostic handling. Maybe collect all diagnostics before emitting.
     pub fn from_hfcode(input: HfCode) -> Self {
-        let mut graph = Self::default();
+        Self::from_hfcode_with_operators(input, &[])
+    }
+
+    /// Creates a new `FlatGraph` instance based on the [`HfCode`] AST, which may use the
+    /// `custom_operators` in addition to the built-in operators.
+    pub fn from_hfcode_with_operators(
+        input: HfCode,
+        custom_operators: &'static [OperatorConstraints],
+    ) -> Self {
+        let mut graph = Self::with_operators(custom_operators);
 
+        let optimize = input.optimize();
+        let allowed_lints = input.allowed_lints();
         for stmt in input.statements {
             graph.add_statement(stmt);
         }
 
+        graph.insert_implicit_tee_merge();
         graph.check_operator_errors();
+        if !graph.diagnostics.iter().any(Diagnostic::is_error) {
+            graph.check_lints(&allowed_lints);
+        }
+        if optimize {
+            graph.optimize();
+        }
+        graph
+    }
+
+    /// Creates an empty `FlatGraph` which may use the `custom_operators` in addition to the
+    /// built-in operators.
+    pub fn with_operators(custom_operators: &'static [OperatorConstraints]) -> Self {
+        let mut graph = Self {
+            custom_operators,
+            ..Default::default()
+        };
+        // Built-in operators are found first, so a custom operator with the same name would be
+        // silently ignored.
+        for custom_operator in custom_operators {
+            if OPERATORS.iter().any(|op| custom_operator.name == op.name) {
+                graph.diagnostics.push(Diagnostic::spanned(
+                    Span::call_site(),
+                    Level::Error,
+                    format!(
+                        "Custom operator `{}` has the same name as a built-in operator.",
+                        custom_operator.name
+                    ),
+                ));
+            }
+        }
         graph
     }
 
@@ -59,6 +115,11 @@ impl FlatGraph {
         match stmt {
             HfStatement::Named(named) => {
                 let ends = self.add_pipeline(named.pipeline);
+                for node_id in [ends.inn, ends.out].into_iter().flatten() {
+                    if !self.node_names.contains_key(node_id) {
+                        self.node_names.insert(node_id, named.name.clone());
+                    }
+                }
                 match self.names.entry(named.name) {
                     Entry::Vacant(vacant_entry) => {
                         vacant_entry.insert(ends);
@@ -102,9 +163,13 @@ impl FlatGraph {
                 let connector = pipeline_link.connector;
                 let rhs_ends = self.add_pipeline(*pipeline_link.rhs);
 
-                if let (Some(src), Some(dst)) = (lhs_ends.out, rhs_ends.inn) {
-                    let (src_port, dst_port) = PortIndexValue::from_arrow_connector(connector);
+                let (src_port, dst_port) = PortIndexValue::from_arrow_connector(connector);
+                let (src, src_port) =
+                    self.resolve_module_port(lhs_ends.out, lhs_ends.out_module, src_port, false);
+                let (dst, dst_port) =
+                    self.resolve_module_port(rhs_ends.inn, rhs_ends.inn_module, dst_port, true);
 
+                if let (Some(src), Some(dst)) = (src, dst) {
                     {
                         /// Helper to emit conflicts when a port is used twice.
                         fn emit_conflict(
@@ -174,29 +239,193 @@ impl FlatGraph {
                 Ends {
                     inn: lhs_ends.inn,
                     out: rhs_ends.out,
+                    inn_module: lhs_ends.inn_module,
+                    out_module: rhs_ends.out_module,
                 }
             }
-            Pipeline::Name(ident) => self.names.get(&ident).copied().unwrap_or_else(|| {
-                self.diagnostics.push(Diagnostic::spanned(
-                    ident.span(),
-                    Level::Error,
-                    format!("Cannot find name `{}`", ident),
-                ));
-                Ends {
-                    inn: None,
-                    out: None,
+            Pipeline::Name(ident) => {
+                // Names are resolved only in the current scope, names in a module can't refer to
+                // names outside of it.
+                if let Some(ends) = self.names.get(&ident).copied() {
+                    // Only the top-level names are linted, module names are its ports.
+                    if self.outer_names.is_empty() {
+                        self.used_names.insert(ident);
+                    }
+                    ends
+                } else {
+                    self.diagnostics.push(Diagnostic::spanned(
+                        ident.span(),
+                        Level::Error,
+                        format!("Cannot find name `{}`", ident),
+                    ));
+                    Ends::default()
+                }
+            }
+            Pipeline::Operator(mut operator) => {
+                if !self.outer_names.is_empty() {
+                    // Operators in modules may come from a `macro_rules!` expansion, resolve
+                    // their code at the call site so it can see `df` and `context`.
+                    operator.resolve_at_call_site();
                 }
-            }),
-            Pipeline::Operator(operator) => {
                 let key = self.nodes.insert(Node::Operator(operator));
                 Ends {
                     inn: Some(key),
                     out: Some(key),
+                    ..Default::default()
+                }
+            }
+            Pipeline::Type(pipeline_type) => {
+                // Checks the type of the items with an `identity::<Type>()` operator.
+                let ty = pipeline_type.ty;
+                let operator = parse_quote_spanned!(ty.span()=> identity::<#ty>());
+                self.add_pipeline(Pipeline::Operator(operator))
+            }
+            Pipeline::Module(pipeline_module) => {
+                // Names inside the module are scoped to it.
+                self.outer_names.push(std::mem::take(&mut self.names));
+                for stmt in pipeline_module.statements {
+                    self.add_statement(stmt);
+                }
+                let ports = std::mem::replace(&mut self.names, self.outer_names.pop().unwrap());
+
+                let find_port = |name: &str| ports.iter().find(|&(ident, _)| ident == name);
+                let inn = find_port("input").and_then(|(_, ends)| ends.inn);
+                let out = find_port("output").and_then(|(_, ends)| ends.out);
+                let module = Some(self.modules.len());
+                self.modules.push(ports);
+                Ends {
+                    inn,
+                    out,
+                    inn_module: module,
+                    out_module: module,
+                }
+            }
+        }
+    }
+
+    /// Helper: If `node` is the end of a module, resolves the named `port` to the node of the
+    /// module's port. Otherwise returns `node` and `port` unchanged.
+    fn resolve_module_port(
+        &mut self,
+        node: Option<GraphNodeId>,
+        module: Option<usize>,
+        port: PortIndexValue,
+        is_in: bool,
+    ) -> (Option<GraphNodeId>, PortIndexValue) {
+        let Some(module) = module else {
+            return (node, port);
+        };
+        let span = port.span();
+        let input_output = if is_in { "input" } else { "output" };
+
+        let PortIndexValue::Path(path) = &port else {
+            if port.is_specified() {
+                self.diagnostics.push(Diagnostic::spanned(
+                    span,
+                    Level::Error,
+                    format!("Module {} ports must be named.", input_output),
+                ));
+            } else if node.is_none() {
+                self.diagnostics.push(Diagnostic::spanned(
+                    span,
+                    Level::Error,
+                    format!(
+                        "Module has no `{0}`, use a named {0} port instead.",
+                        input_output
+                    ),
+                ));
+            }
+            return (node, port);
+        };
+
+        let name = path.path.get_ident();
+        let ends = name.and_then(|name| self.modules[module].get(name));
+        let Some(ends) = ends else {
+            self.diagnostics.push(Diagnostic::spanned(
+                span,
+                Level::Error,
+                format!("Module has no port `{}`.", path.to_token_stream()),
+            ));
+            return (None, port);
+        };
+        let node = if is_in { ends.inn } else { ends.out };
+        (node, PortIndexValue::Elided(span))
+    }
+
+    /// Inserts `tee()` and `merge()` operators where a single-output or single-input operator is
+    /// connected multiple times, i.e. when a named variable is used more than once. Only applies
+    /// if all those connections have elided ports.
+    ///
+    /// Called by [`Self::from_hfcode`], must be called manually if using [`Self::add_statement`].
+    pub fn insert_implicit_tee_merge(&mut self) {
+        /// Returns true if an operator allowing exactly one connection has `degree` connections.
+        fn needs_implicit(range: &dyn RangeTrait<usize>, degree: usize) -> bool {
+            1 < degree && range.contains(&1) && !range.contains(&degree)
+        }
+
+        let node_ids: Vec<_> = self.nodes.keys().collect();
+        for node_id in node_ids {
+            let Node::Operator(operator) = &self.nodes[node_id] else {
+                continue;
+            };
+            let op_name = &*operator.name_string();
+            let Some(op_constraints) = find_operator(op_name, self.custom_operators) else {
+                continue;
+            };
+            let span = operator.span();
+
+            // Outputs: `node -> tee() -> succs`.
+            let succ_edges: Vec<_> = self.graph.successor_edges(node_id).collect();
+            if needs_implicit(op_constraints.hard_range_out, succ_edges.len())
+                && succ_edges.iter().all(|&e| !self.ports[e].0.is_specified())
+            {
+                let tee: Operator = parse_quote_spanned!(span=> tee());
+                let tee_id = self.nodes.insert(Node::Operator(tee));
+                for edge_id in succ_edges {
+                    let (_node_id, dst) = self.graph.remove_edge(edge_id).unwrap();
+                    let ports = self.ports.remove(edge_id).unwrap();
+                    let new_edge_id = self.graph.insert_edge(tee_id, dst);
+                    self.ports.insert(new_edge_id, ports);
+                }
+                let edge_id = self.graph.insert_edge(node_id, tee_id);
+                self.ports.insert(
+                    edge_id,
+                    (PortIndexValue::Elided(span), PortIndexValue::Elided(span)),
+                );
+            }
+
+            // Inputs: `preds -> merge() -> node`.
+            let pred_edges: Vec<_> = self.graph.predecessor_edges(node_id).collect();
+            if needs_implicit(op_constraints.hard_range_inn, pred_edges.len())
+                && pred_edges.iter().all(|&e| !self.ports[e].1.is_specified())
+            {
+                let merge: Operator = parse_quote_spanned!(span=> merge());
+                let merge_id = self.nodes.insert(Node::Operator(merge));
+                for edge_id in pred_edges {
+                    let (src, _node_id) = self.graph.remove_edge(edge_id).unwrap();
+                    let ports = self.ports.remove(edge_id).unwrap();
+                    let new_edge_id = self.graph.insert_edge(src, merge_id);
+                    self.ports.insert(new_edge_id, ports);
                 }
+                let edge_id = self.graph.insert_edge(merge_id, node_id);
+                self.ports.insert(
+                    edge_id,
+                    (PortIndexValue::Elided(span), PortIndexValue::Elided(span)),
+                );
             }
         }
     }
 
+    /// Top-level names which are never used, with the nodes at their input and output ends.
+    pub(crate) fn unused_names(
+        &self,
+    ) -> impl '_ + Iterator<Item = (&Ident, [Option<GraphNodeId>; 2])> {
+        self.names
+            .iter()
+            .filter(|(name, _)| !self.used_names.contains(*name))
+            .map(|(name, ends)| (name, [ends.inn, ends.out]))
+    }
+
     /// Validates that operators have valid number of inputs, outputs, & arguments.
     /// Adds errors (and warnings) to `self.diagnostics`.
     /// TODO(mingwei): Clean this up, make it do more than just arity? Do no overlapping edge ports.
@@ -205,16 +434,16 @@ impl FlatGraph {
             match node {
                 Node::Operator(operator) => {
                     let op_name = &*operator.name_string();
-                    match OPERATORS.iter().find(|&op| op_name == op.name) {
+                    match find_operator(op_name, self.custom_operators) {
                         Some(op_constraints) => {
                             // Check numer of args
-                            if op_constraints.num_args != operator.args.len() {
+                            if !op_constraints.num_args.contains(&operator.args.len()) {
                                 self.diagnostics.push(Diagnostic::spanned(
                                     operator.span(),
                                     Level::Error,
                                     format!(
                                         "expected {} argument(s), found {}",
-                                        op_constraints.num_args,
+                                        op_constraints.num_args.human_string(),
                                         operator.args.len()
                                     ),
                                 ));
@@ -393,6 +622,11 @@ impl FlatGraph {
         }
     }
 
+    /// The diagnostics found while building the graph.
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+
     /// Emits diagnostics, returns true if there are errors.
     pub fn emit_diagnostics(&self) -> bool {
         self.diagnostics.iter().for_each(Diagnostic::emit);
@@ -467,8 +701,94 @@ impl FlatGraph {
     }
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Default)]
 struct Ends {
     inn: Option<GraphNodeId>,
     out: Option<GraphNodeId>,
+    /// If the input end is a module, its index in [`FlatGraph::modules`].
+    inn_module: Option<usize>,
+    /// If the output end is a module, its index in [`FlatGraph::modules`].
+    out_module: Option<usize>,
+}
+
+#[cfg(test)]
+mod tests {
+    use quote::quote_spanned;
+    use syn::parse_quote;
+
+    use super::*;
+    use crate::graph::ops::{OperatorWriteOutput, WriteIteratorArgs, RANGE_0, RANGE_1};
+
+    /// An operator as it would be defined by another crate.
+    const SOURCE_BUS: OperatorConstraints = OperatorConstraints {
+        name: "source_bus",
+        hard_range_inn: RANGE_0,
+        soft_range_inn: RANGE_0,
+        hard_range_out: RANGE_1,
+        soft_range_out: RANGE_1,
+        ports_inn: None,
+        ports_out: None,
+        num_args: RANGE_1,
+        input_delaytype_fn: &|_| None,
+        write_fn: &(|wc,
+                     &WriteIteratorArgs {
+                         ident, arguments, ..
+                     },
+                     _| {
+            let op_span = wc.op_span;
+            let write_iterator = quote_spanned! {op_span=>
+                let #ident = #arguments.drain_bus();
+            };
+            Ok(OperatorWriteOutput {
+                write_iterator,
+                ..Default::default()
+            })
+        }),
+    };
+    const CUSTOM_OPERATORS: &[OperatorConstraints] = &[SOURCE_BUS];
+
+    #[test]
+    fn custom_operators() {
+        let code = || -> HfCode {
+            parse_quote! {
+                source_bus(bus) -> for_each(std::mem::drop);
+            }
+        };
+
+        let flat_graph = FlatGraph::from_hfcode(code());
+        assert!(flat_graph.diagnostics.iter().any(Diagnostic::is_error));
+
+        let flat_graph = FlatGraph::from_hfcode_with_operators(code(), CUSTOM_OPERATORS);
+        assert!(flat_graph.diagnostics.is_empty());
+        let code = flat_graph
+            .into_partitioned_graph()
+            .unwrap()
+            .as_code(quote::quote! { hydroflow }, true)
+            .to_string();
+        assert!(code.contains("drain_bus"));
+    }
+
+    #[test]
+    fn custom_operators_shadowing() {
+        const SOURCE_ITER: OperatorConstraints = OperatorConstraints {
+            name: "source_iter",
+            ..SOURCE_BUS
+        };
+        let flat_graph = FlatGraph::from_hfcode_with_operators(
+            parse_quote! {
+                source_iter(bus) -> for_each(std::mem::drop);
+            },
+            &[SOURCE_ITER],
+        );
+        let errors: Vec<_> = flat_graph
+            .diagnostics
+            .iter()
+            .filter(|diagnostic| diagnostic.is_error())
+            .map(Diagnostic::message)
+            .collect();
+        assert_eq!(
+            vec!["Custom operator `source_iter` has the same name as a built-in operator."],
+            errors
+        );
+    }
 }
@@ -1,4 +1,5 @@
-use std::collections::{BTreeMap, BTreeSet};
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BTreeSet, BinaryHeap};
 
 use proc_macro2::Span;
 use slotmap::{Key, SecondaryMap, SlotMap, SparseSecondaryMap};
@@ -10,7 +11,7 @@ use crate::union_find::UnionFind;
 
 use super::di_mul_graph::DiMulGraph;
 use super::flat_graph::FlatGraph;
-use super::ops::{DelayType, OPERATORS};
+use super::ops::{find_operator, DelayType, OperatorConstraints};
 use super::partitioned_graph::PartitionedGraph;
 use super::{
     graph_algorithms, node_color, Color, GraphEdgeId, GraphNodeId, GraphSubgraphId, Node,
@@ -21,6 +22,7 @@ fn find_barrier_crossers(
     nodes: &SlotMap<GraphNodeId, Node>,
     ports: &SecondaryMap<GraphEdgeId, (PortIndexValue, PortIndexValue)>,
     graph: &DiMulGraph<GraphNodeId, GraphEdgeId>,
+    custom_operators: &'static [OperatorConstraints],
 ) -> SecondaryMap<GraphEdgeId, DelayType> {
     graph
         .edges()
@@ -28,9 +30,7 @@ fn find_barrier_crossers(
             let (_src_idx, dst_idx) = &ports[edge_id];
             if let Node::Operator(dst_operator) = &nodes[dst] {
                 let dst_name = &*dst_operator.name_string();
-                OPERATORS
-                    .iter()
-                    .find(|&op| dst_name == op.name)
+                find_operator(dst_name, custom_operators)
                     .and_then(|op_constraints| (op_constraints.input_delaytype_fn)(dst_idx))
                     .map(|input_barrier| (edge_id, input_barrier))
             } else {
@@ -40,6 +40,53 @@ fn find_barrier_crossers(
         .collect()
 }
 
+/// Relative cost of a handoff on an edge within a cycle, compared to an edge outside of any cycle.
+/// Items may go around a cycle many times within a tick, so they cross its handoffs many times.
+const CYCLE_EDGE_COST: usize = 4;
+
+/// Estimates the cost of a handoff on each edge, for choosing which edges to fuse into subgraphs
+/// first. An edge costs the number of edges in the linear pipeline (chain of one-in one-out
+/// operators) it belongs to, so long pipelines are kept together, times [`CYCLE_EDGE_COST`] if it
+/// is within a cycle.
+fn find_edge_costs(
+    nodes: &SlotMap<GraphNodeId, Node>,
+    graph: &DiMulGraph<GraphNodeId, GraphEdgeId>,
+) -> SecondaryMap<GraphEdgeId, usize> {
+    let mut pipelines: UnionFind<GraphEdgeId> = UnionFind::new();
+    for node_id in nodes.keys() {
+        if 1 == graph.degree_in(node_id) && 1 == graph.degree_out(node_id) {
+            let pred_edge = graph.predecessor_edges(node_id).next().unwrap();
+            let succ_edge = graph.successor_edges(node_id).next().unwrap();
+            pipelines.union(pred_edge, succ_edge);
+        }
+    }
+    let mut pipeline_lens: SecondaryMap<GraphEdgeId, usize> = SecondaryMap::new();
+    for (edge_id, _) in graph.edges() {
+        *pipeline_lens
+            .entry(pipelines.find(edge_id))
+            .unwrap()
+            .or_default() += 1;
+    }
+
+    let scc = graph_algorithms::scc_kosaraju(
+        nodes.keys(),
+        |v| graph.predecessor_nodes(v),
+        |u| graph.successor_nodes(u),
+    );
+
+    graph
+        .edges()
+        .map(|(edge_id, (src, dst))| {
+            let cost = pipeline_lens[pipelines.find(edge_id)];
+            if scc[&src] == scc[&dst] {
+                (edge_id, CYCLE_EDGE_COST * cost)
+            } else {
+                (edge_id, cost)
+            }
+        })
+        .collect()
+}
+
 fn find_subgraph_unionfind(
     nodes: &SlotMap<GraphNodeId, Node>,
     graph: &DiMulGraph<GraphNodeId, GraphEdgeId>,
@@ -51,42 +98,92 @@ fn find_subgraph_unionfind(
     // we remove from this set as we construct subgraphs.
     let mut handoff_edges: BTreeSet<GraphEdgeId> =
         graph.edges().map(|(edge_id, _)| edge_id).collect();
-    // Would sort edges here for priority (for now, no sort/priority).
-
-    // Each edge gets looked at in order. However we may not know if a linear
-    // chain of operators is PUSH vs PULL until we look at the ends. A fancier
-    // algorithm would know to handle linear chains from the outside inward.
-    // But instead we just run through the edges in a loop until no more
-    // progress is made. Could have some sort of O(N^2) pathological worst
-    // case.
-    let mut progress = true;
-    while progress {
-        progress = false;
-        for (edge_id, (src, dst)) in graph.edges() {
-            // Ignore (1) already added edges as well as (2) new self-cycles.
-            if subgraph_unionfind.same_set(src, dst) {
-                // Note this might be triggered even if the edge (src, dst) is not in the subgraph (not case 1).
-                // This prevents self-loops which would violate the in-out tree structure (case 2).
-                // Handoffs will be inserted later for this self-loop.
+
+    // For each subgraph's representative node, the nodes across barrier crossers from the
+    // subgraph. Subgraphs containing the two ends of a barrier crosser cannot be joined.
+    let mut barrier_partners: SecondaryMap<GraphNodeId, Vec<GraphNodeId>> = SecondaryMap::new();
+    for (edge_id, _) in barrier_crossers.iter() {
+        let (src, dst) = graph.edge(edge_id).unwrap();
+        barrier_partners.entry(src).unwrap().or_default().push(dst);
+        barrier_partners.entry(dst).unwrap().or_default().push(src);
+    }
+
+    // Edges are looked at in order of decreasing cost, so the costliest edges are the least
+    // likely to become handoffs. However we may not know if a linear chain of operators is PUSH vs
+    // PULL until we look at the ends. So edges between two uncolored nodes are deferred, and
+    // looked at again once either end gets colored. Each node is colored at most once, so each
+    // edge is looked at most three times.
+    let edge_costs = find_edge_costs(nodes, graph);
+    let mut queue: BinaryHeap<(usize, Reverse<GraphEdgeId>)> = graph
+        .edges()
+        .map(|(edge_id, _)| (edge_costs[edge_id], Reverse(edge_id)))
+        .collect();
+    while let Some((_cost, Reverse(edge_id))) = queue.pop() {
+        let (src, dst) = graph.edge(edge_id).unwrap();
+        let src_repr = subgraph_unionfind.find(src);
+        let dst_repr = subgraph_unionfind.find(dst);
+
+        // Ignore (1) already added edges as well as (2) new self-cycles.
+        if src_repr == dst_repr {
+            // Note this might be triggered even if the edge (src, dst) is not in the subgraph (not case 1).
+            // This prevents self-loops which would violate the in-out tree structure (case 2).
+            // Handoffs will be inserted later for this self-loop.
+            continue;
+        }
+
+        // Ignore if would join stratum crossers (next edges).
+        {
+            let src_partners = barrier_partners
+                .get(src_repr)
+                .map_or(&[][..], Vec::as_slice);
+            let dst_partners = barrier_partners
+                .get(dst_repr)
+                .map_or(&[][..], Vec::as_slice);
+            // Check the shorter list.
+            let (partners, other_repr) = if src_partners.len() <= dst_partners.len() {
+                (src_partners, dst_repr)
+            } else {
+                (dst_partners, src_repr)
+            };
+            if partners
+                .iter()
+                .any(|&partner| other_repr == subgraph_unionfind.find(partner))
+            {
                 continue;
             }
+        }
 
-            // Ignore if would join stratum crossers (next edges).
-            if barrier_crossers.iter().any(|(edge_id, _)| {
-                let (x_src, x_dst) = graph.edge(edge_id).unwrap();
-                (subgraph_unionfind.same_set(x_src, src) && subgraph_unionfind.same_set(x_dst, dst))
-                    || (subgraph_unionfind.same_set(x_src, dst)
-                        && subgraph_unionfind.same_set(x_dst, src))
-            }) {
-                continue;
+        let uncolored: Vec<GraphNodeId> = [src, dst]
+            .into_iter()
+            .filter(|&node_id| !node_color_map.contains_key(node_id))
+            .collect();
+        if can_connect_colorize(node_color_map, src, dst) {
+            // At this point we have selected this edge and its src & dst to be
+            // within a single subgraph.
+            subgraph_unionfind.union(src, dst);
+            assert!(handoff_edges.remove(&edge_id));
+
+            // Move the barrier partners to the new representative, appending the shorter list.
+            let repr = subgraph_unionfind.find(src);
+            let other_repr = if repr == src_repr { dst_repr } else { src_repr };
+            if let Some(mut moved) = barrier_partners.remove(other_repr) {
+                let partners = barrier_partners.entry(repr).unwrap().or_default();
+                if partners.len() < moved.len() {
+                    std::mem::swap(partners, &mut moved);
+                }
+                partners.extend(moved);
             }
 
-            if can_connect_colorize(node_color_map, src, dst) {
-                // At this point we have selected this edge and its src & dst to be
-                // within a single subgraph.
-                subgraph_unionfind.union(src, dst);
-                assert!(handoff_edges.remove(&edge_id));
-                progress = true;
+            // Deferred edges of newly colored nodes can now be looked at.
+            for node_id in uncolored {
+                for edge_id in graph
+                    .predecessor_edges(node_id)
+                    .chain(graph.successor_edges(node_id))
+                {
+                    if handoff_edges.contains(&edge_id) {
+                        queue.push((edge_costs[edge_id], Reverse(edge_id)));
+                    }
+                }
             }
         }
     }
@@ -455,11 +552,13 @@ impl TryFrom<FlatGraph> for PartitionedGraph {
             mut nodes,
             mut graph,
             mut ports,
+            node_names,
+            custom_operators,
             ..
         } = flat_graph;
 
         // Pairs of node IDs which cross stratums or ticks and therefore cannot be in the same subgraph.
-        let mut barrier_crossers = find_barrier_crossers(&nodes, &ports, &graph);
+        let mut barrier_crossers = find_barrier_crossers(&nodes, &ports, &graph, custom_operators);
         let mut node_color = nodes
             .keys()
             .map(|node_id| {
@@ -515,7 +614,7 @@ impl TryFrom<FlatGraph> for PartitionedGraph {
                                     subgraph_internal_handoffs
                                         .entry(*inbound_src_subgraph)
                                         .unwrap()
-                                        .or_insert(Vec::new())
+                                        .or_default()
                                         .push(src);
                                 }
                             }
@@ -536,6 +635,8 @@ impl TryFrom<FlatGraph> for PartitionedGraph {
             subgraph_send_handoffs,
             subgraph_internal_handoffs,
             node_color_map: node_color,
+            node_names,
+            custom_operators,
         })
     }
 }
@@ -563,3 +664,127 @@ fn insert_intermediate_node(
 
     (node_id, e1)
 }
+
+#[cfg(test)]
+mod test {
+    use quote::{format_ident, quote};
+
+    use super::*;
+
+    /// Helper: the operator names of each subgraph, in order.
+    fn subgraph_operators(part_graph: &PartitionedGraph) -> Vec<Vec<String>> {
+        part_graph
+            .subgraph_nodes
+            .values()
+            .map(|node_ids| {
+                node_ids
+                    .iter()
+                    .filter_map(|&node_id| match &part_graph.nodes[node_id] {
+                        Node::Operator(operator) => Some(operator.name_string()),
+                        Node::Handoff { .. } => None,
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Helper: the operator names before and after each handoff.
+    fn handoff_ends(part_graph: &PartitionedGraph) -> Vec<(String, String)> {
+        let name = |node_id| match &part_graph.nodes[node_id] {
+            Node::Operator(operator) => operator.name_string(),
+            Node::Handoff { .. } => panic!("Handoff next to a handoff."),
+        };
+        part_graph
+            .handoff_ids()
+            .map(|handoff_id| {
+                let pred = part_graph
+                    .graph
+                    .predecessor_nodes(handoff_id)
+                    .next()
+                    .unwrap();
+                let succ = part_graph.graph.successor_nodes(handoff_id).next().unwrap();
+                (name(pred), name(succ))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn partition_long_pipeline() {
+        // Statements from the sink to the source, so edges are listed in that order.
+        let idents: Vec<_> = (0..=500_usize).map(|i| format_ident!("n{}", i)).collect();
+        let maps = idents.windows(2).map(|pair| {
+            let (prev, next) = (&pair[0], &pair[1]);
+            quote! { #next = map(|x: usize| x + 1) -> #prev; }
+        });
+        let last = idents.last().unwrap();
+        let hf_code = syn::parse2(quote! {
+            n0 = for_each(std::mem::drop);
+            #( #maps )*
+            source_iter(0..10_usize) -> #last;
+        })
+        .unwrap();
+        let flat_graph = FlatGraph::from_hfcode(hf_code);
+        assert!(flat_graph.diagnostics.is_empty());
+
+        let part_graph = flat_graph.into_partitioned_graph().unwrap();
+        let mut expected = vec!["source_iter".to_owned()];
+        expected.extend(std::iter::repeat("map".to_owned()).take(500));
+        expected.push("for_each".to_owned());
+        assert_eq!(vec![expected], subgraph_operators(&part_graph));
+        assert!(handoff_ends(&part_graph).is_empty());
+    }
+
+    #[test]
+    fn partition_cycle() {
+        let flat_graph = FlatGraph::from_hfcode(parse_quote! {
+            looped = merge() -> map(|x: usize| x + 1) -> map(|x| 2 * x) -> filter(|&x| x < 100) -> tee();
+            source_iter([1]) -> looped;
+            looped -> looped;
+            looped -> for_each(std::mem::drop);
+        });
+        assert!(!flat_graph.diagnostics.iter().any(Diagnostic::is_error));
+
+        // The whole pipeline is in one subgraph, with a handoff only on the back edge.
+        let part_graph = flat_graph.into_partitioned_graph().unwrap();
+        assert_eq!(
+            vec![vec![
+                "source_iter",
+                "merge",
+                "map",
+                "map",
+                "filter",
+                "tee",
+                "for_each"
+            ]],
+            subgraph_operators(&part_graph)
+        );
+        assert_eq!(
+            vec![("tee".to_owned(), "merge".to_owned())],
+            handoff_ends(&part_graph)
+        );
+    }
+
+    #[test]
+    fn partition_all_push() {
+        let flat_graph = FlatGraph::from_hfcode(parse_quote! {
+            #![allow(spinning_cycles)]
+            looped = tee();
+            looped -> looped;
+            looped -> for_each(std::mem::drop);
+        });
+        assert!(flat_graph.diagnostics.is_empty());
+
+        // The subgraph starts at the push `tee()`, which is pivoted from the back edge handoff.
+        let part_graph = flat_graph.into_partitioned_graph().unwrap();
+        assert_eq!(
+            vec![vec!["tee", "for_each"]],
+            subgraph_operators(&part_graph)
+        );
+        assert_eq!(
+            vec![("tee".to_owned(), "tee".to_owned())],
+            handoff_ends(&part_graph)
+        );
+        let code = part_graph.as_code(quote! { hydroflow }, true).to_string();
+        assert!(code.contains("check_pivot_run"), "{}", code);
+    }
+}
@@ -0,0 +1,537 @@
+//! Lints for common mistakes in surface graphs. Each lint adds a warning, followed by a help
+//! message suggesting a fix. A lint can be silenced for a whole graph with
+//! `#![allow(lint_name)]`.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use proc_macro2::Span;
+use quote::ToTokens;
+use syn::spanned::Spanned;
+use syn::{Expr, GenericArgument, Ident, Lit, ReturnType, Type};
+
+use super::flat_graph::FlatGraph;
+use super::graph_algorithms::scc_kosaraju;
+use super::{GraphNodeId, PortIndexValue};
+use crate::diagnostic::{Diagnostic, Level};
+use crate::parse::Operator;
+
+/// Each lint, with its name used to allow it with `#![allow(lint_name)]`.
+#[allow(clippy::type_complexity)]
+const LINTS: &[(&str, fn(&mut FlatGraph))] = &[
+    ("unused_names", FlatGraph::lint_unused_names),
+    ("unconsumed_sources", FlatGraph::lint_unconsumed_sources),
+    ("unbounded_state", FlatGraph::lint_unbounded_state),
+    ("spinning_cycles", FlatGraph::lint_spinning_cycles),
+    ("join_key_types", FlatGraph::lint_join_key_types),
+];
+/// Sources which may produce items forever.
+const UNBOUNDED_SOURCES: &[&str] = &[
+    "source_stream",
+    "source_stream_serde",
+    "source_stdin",
+    "input",
+];
+/// Operators which keep all their input in their state when `'static`, and if they are `'static`
+/// when no lifetime is given.
+const UNBOUNDED_STATE: &[(&str, bool)] = &[
+    ("join", true),
+    ("unique", true),
+    ("difference", false),
+    ("group_by", true),
+    ("sort", false),
+];
+/// Operators which output every item they receive, so a cycle of only these never stops.
+const NON_DROPPING: &[&str] = &["identity", "map", "inspect", "merge", "tee"];
+/// Operators whose output has the same type as their (first) input.
+const TYPE_PRESERVING: &[&str] = &[
+    "identity",
+    "inspect",
+    "filter",
+    "filter_pure",
+    "merge",
+    "tee",
+    "unique",
+    "next_tick",
+    "next_stratum",
+];
+
+impl FlatGraph {
+    /// Adds warnings for likely mistakes which are not errors, except for the `allowed` lints.
+    pub(crate) fn check_lints(&mut self, allowed: &[Ident]) {
+        for name in allowed {
+            if !LINTS.iter().any(|&(lint_name, _)| name == lint_name) {
+                let lint_names: Vec<_> = LINTS.iter().map(|&(lint_name, _)| lint_name).collect();
+                self.lint(
+                    name.span(),
+                    format!("Unknown lint `{}`.", name),
+                    format!("Expected one of: {}.", lint_names.join(", ")),
+                );
+            }
+        }
+        for &(lint_name, lint_fn) in LINTS {
+            if !allowed.iter().any(|name| name == lint_name) {
+                (lint_fn)(self);
+            }
+        }
+    }
+
+    /// Helper: adds a warning and a help message.
+    fn lint(&mut self, span: Span, warning: String, help: String) {
+        self.diagnostics
+            .push(Diagnostic::spanned(span, Level::Warning, warning));
+        self.diagnostics
+            .push(Diagnostic::spanned(span, Level::Help, help));
+    }
+
+    /// Helper: the name of the operator at `node_id`, if it is an operator.
+    fn op_name(&self, node_id: GraphNodeId) -> Option<String> {
+        self.operator(node_id).map(Operator::name_string)
+    }
+
+    /// Helper: all nodes reachable from `start` by following `next`, including `start`.
+    fn reachable<I>(
+        &self,
+        start: impl IntoIterator<Item = GraphNodeId>,
+        mut next: impl FnMut(GraphNodeId) -> I,
+    ) -> BTreeSet<GraphNodeId>
+    where
+        I: IntoIterator<Item = GraphNodeId>,
+    {
+        let mut seen = BTreeSet::new();
+        let mut stack: Vec<_> = start.into_iter().collect();
+        while let Some(node_id) = stack.pop() {
+            if seen.insert(node_id) {
+                stack.extend(next(node_id));
+            }
+        }
+        seen
+    }
+
+    /// Names which are defined but never used.
+    fn lint_unused_names(&mut self) {
+        let unused: Vec<_> = self
+            .unused_names()
+            .filter(|(name, ends)| {
+                // Named external ports are used through their handles.
+                !name.to_string().starts_with('_')
+                    && !ends.iter().flatten().any(|&node_id| {
+                        matches!(self.op_name(node_id).as_deref(), Some("input" | "output"))
+                    })
+            })
+            .map(|(name, _)| name.clone())
+            .collect();
+        for name in unused {
+            self.lint(
+                name.span(),
+                format!("Name `{}` is defined but never used.", name),
+                format!(
+                    "Remove the name, or rename it to `_{}` if it is intentionally unused.",
+                    name
+                ),
+            );
+        }
+    }
+
+    /// Sources whose items never reach a sink, other than `null()`, or an `inspect()`.
+    fn lint_unconsumed_sources(&mut self) {
+        let sinks = self.nodes.keys().filter(|&node_id| {
+            let name = self.op_name(node_id);
+            Some("inspect") == name.as_deref()
+                || (0 == self.graph.degree_out(node_id) && Some("null") != name.as_deref())
+        });
+        let consumed = self.reachable(sinks, |node_id| self.graph.predecessor_nodes(node_id));
+        let unconsumed: Vec<_> = self
+            .nodes
+            .keys()
+            .filter(|&node_id| {
+                0 == self.graph.degree_in(node_id)
+                    && 0 < self.graph.degree_out(node_id)
+                    && !consumed.contains(&node_id)
+            })
+            .filter_map(|node_id| Some((self.op_name(node_id)?, self.nodes[node_id].span())))
+            .collect();
+        for (name, span) in unconsumed {
+            self.lint(
+                span,
+                format!("Output of `{}` is never consumed.", name),
+                "Connect it to a sink such as `for_each`, or remove it.".to_owned(),
+            );
+        }
+    }
+
+    /// `'static` operators which keep all the items from an unbounded source.
+    fn lint_unbounded_state(&mut self) {
+        let mut lints = Vec::new();
+        for node_id in self.nodes.keys() {
+            let Some(operator) = self.operator(node_id) else {
+                continue;
+            };
+            let name = operator.name_string();
+            let Some(&(_, default_static)) = UNBOUNDED_STATE
+                .iter()
+                .find(|&&(state_name, _)| name == state_name)
+            else {
+                continue;
+            };
+            let lifetimes: Vec<_> = operator
+                .type_arguments()
+                .into_iter()
+                .flatten()
+                .filter_map(|arg| match arg {
+                    GenericArgument::Lifetime(lifetime) => Some(lifetime),
+                    _ => None,
+                })
+                .collect();
+            let is_explicit = !lifetimes.is_empty();
+            let is_static = if is_explicit {
+                lifetimes.iter().any(|lifetime| lifetime.ident == "static")
+            } else {
+                default_static
+            };
+            if !is_static {
+                continue;
+            }
+            let upstream =
+                self.reachable([node_id], |node_id| self.graph.predecessor_nodes(node_id));
+            let source = upstream.into_iter().find_map(|node_id| {
+                self.op_name(node_id)
+                    .filter(|name| UNBOUNDED_SOURCES.contains(&&**name))
+            });
+            if let Some(source) = source {
+                let name = if is_explicit {
+                    format!("`{}::<'static>`", name)
+                } else {
+                    format!("`{}`, which is `'static` by default,", name)
+                };
+                lints.push((operator.span(), name, source));
+            }
+        }
+        for (span, name, source) in lints {
+            self.lint(
+                span,
+                format!(
+                    "{} keeps the items from unbounded `{}` forever, so its state grows without bound.",
+                    name, source
+                ),
+                "Use `'tick` to only keep state within each tick, or bound the input. Add `#![allow(unbounded_state)]` if this is intended.".to_owned(),
+            );
+        }
+    }
+
+    /// Cycles which output every item they receive, and have no `next_tick()` or
+    /// `next_stratum()`, so items cycle forever within a tick.
+    fn lint_spinning_cycles(&mut self) {
+        let components = scc_kosaraju(
+            self.nodes.keys(),
+            |node_id| self.graph.predecessor_nodes(node_id),
+            |node_id| self.graph.successor_nodes(node_id),
+        );
+        let mut cycles: BTreeMap<GraphNodeId, Vec<GraphNodeId>> = BTreeMap::new();
+        for (node_id, root) in components {
+            cycles.entry(root).or_default().push(node_id);
+        }
+        let spinning: Vec<_> = cycles
+            .into_values()
+            .filter(|cycle| {
+                let is_cycle = 1 < cycle.len()
+                    || self
+                        .graph
+                        .successor_nodes(cycle[0])
+                        .any(|succ| succ == cycle[0]);
+                is_cycle
+                    && cycle.iter().all(|&node_id| {
+                        self.op_name(node_id)
+                            .map_or(false, |name| NON_DROPPING.contains(&&*name))
+                    })
+            })
+            .map(|cycle| self.nodes[cycle[0]].span())
+            .collect();
+        for span in spinning {
+            self.lint(
+                span,
+                "This cycle has no `next_tick()` or `next_stratum()`, and outputs every item it receives, so items will cycle forever within a tick.".to_owned(),
+                "Add `next_tick()` to send items around the cycle in the next tick, or an operator such as `unique()` or `filter()` to stop them. Add `#![allow(spinning_cycles)]` if this is intended.".to_owned(),
+            );
+        }
+    }
+
+    /// `join()`s whose inputs have different key types.
+    fn lint_join_key_types(&mut self) {
+        let mut lints = Vec::new();
+        for node_id in self.nodes.keys() {
+            if Some("join") != self.op_name(node_id).as_deref() {
+                continue;
+            }
+            let key_types: Vec<_> = [0, 1]
+                .into_iter()
+                .filter_map(|port| {
+                    let pred = self.graph.predecessors(node_id).find_map(|(edge_id, pred)| {
+                        matches!(&self.ports[edge_id].1, PortIndexValue::Int(idx) if port == idx.value)
+                            .then_some(pred)
+                    })?;
+                    self.key_type(pred)
+                })
+                .collect();
+            if let [key_0, key_1] = &*key_types {
+                if !key_0.compatible(key_1) {
+                    lints.push((self.nodes[node_id].span(), key_0.clone(), key_1.clone()));
+                }
+            }
+        }
+        for (span, key_0, key_1) in lints {
+            self.lint(
+                span,
+                format!(
+                    "`join` inputs have different key types, `{}` in port 0 and `{}` in port 1.",
+                    key_0.0, key_1.0
+                ),
+                "Both inputs of `join` must be `(K, _)` tuples with the same key type `K`."
+                    .to_owned(),
+            );
+        }
+    }
+
+    /// Helper: the key type of the `(K, V)` items output by `node_id`, if it can be determined.
+    fn key_type(&self, mut node_id: GraphNodeId) -> Option<KeyType> {
+        for _ in 0..self.nodes.len() {
+            let operator = self.operator(node_id)?;
+            let name = operator.name_string();
+            // Type annotation, `identity::<(K, V)>()`.
+            if let Some(GenericArgument::Type(Type::Tuple(tuple))) = operator
+                .type_arguments()
+                .and_then(|type_args| type_args.first())
+                .filter(|_| "identity" == name)
+            {
+                return KeyType::of_type(tuple.elems.first()?);
+            }
+            if TYPE_PRESERVING.contains(&&*name) {
+                node_id = self.graph.predecessor_nodes(node_id).next()?;
+                continue;
+            }
+            let arg = operator.args.first()?;
+            return match &*name {
+                "source_iter" | "repeat_iter" => {
+                    let first = match arg {
+                        Expr::Array(array) => array.elems.first()?.clone(),
+                        Expr::Macro(mac) if mac.mac.path.is_ident("vec") => mac
+                            .mac
+                            .parse_body_with(
+                                syn::punctuated::Punctuated::<Expr, syn::Token![,]>::parse_terminated,
+                            )
+                            .ok()?
+                            .first()?
+                            .clone(),
+                        _ => return None,
+                    };
+                    KeyType::of_tuple_expr(&first)
+                }
+                "map" => {
+                    let Expr::Closure(closure) = arg else {
+                        return None;
+                    };
+                    if let ReturnType::Type(_, ty) = &closure.output {
+                        let Type::Tuple(tuple) = &**ty else {
+                            return None;
+                        };
+                        return KeyType::of_type(tuple.elems.first()?);
+                    }
+                    match &*closure.body {
+                        Expr::Block(block) => match block.block.stmts.last()? {
+                            syn::Stmt::Expr(expr) => KeyType::of_tuple_expr(expr),
+                            _ => None,
+                        },
+                        expr => KeyType::of_tuple_expr(expr),
+                    }
+                }
+                _ => None,
+            };
+        }
+        None
+    }
+}
+
+/// A key type for [`FlatGraph::lint_join_key_types`], only for primitive and `String` types, as
+/// other types may be aliases. `{integer}` and `{float}` are unsuffixed literals.
+#[derive(Clone, Debug)]
+struct KeyType(String);
+impl KeyType {
+    const INTEGERS: &'static [&'static str] = &[
+        "i8", "i16", "i32", "i64", "i128", "isize", "u8", "u16", "u32", "u64", "u128", "usize",
+    ];
+    const FLOATS: &'static [&'static str] = &["f32", "f64"];
+    const OTHERS: &'static [&'static str] = &["bool", "char", "&str", "String"];
+
+    /// The key type of a tuple expression `(k, v)`, if `k` is a literal.
+    fn of_tuple_expr(expr: &Expr) -> Option<Self> {
+        let Expr::Tuple(tuple) = expr else {
+            return None;
+        };
+        let Expr::Lit(lit) = tuple.elems.first()? else {
+            return None;
+        };
+        let ty = match &lit.lit {
+            Lit::Str(_) => "&str".to_owned(),
+            Lit::Char(_) => "char".to_owned(),
+            Lit::Bool(_) => "bool".to_owned(),
+            Lit::Int(lit_int) if lit_int.suffix().is_empty() => "{integer}".to_owned(),
+            Lit::Int(lit_int) => lit_int.suffix().to_owned(),
+            Lit::Float(lit_float) if lit_float.suffix().is_empty() => "{float}".to_owned(),
+            Lit::Float(lit_float) => lit_float.suffix().to_owned(),
+            _ => return None,
+        };
+        Some(Self(ty))
+    }
+
+    /// The key type of a type annotation, if it is a known type.
+    fn of_type(ty: &Type) -> Option<Self> {
+        let ty = match ty {
+            // Ignore lifetimes, e.g. `&'static str`.
+            Type::Reference(type_ref) => format!("&{}", type_ref.elem.to_token_stream()),
+            ty => ty.to_token_stream().to_string(),
+        };
+        [Self::INTEGERS, Self::FLOATS, Self::OTHERS]
+            .iter()
+            .any(|types| types.contains(&&*ty))
+            .then_some(Self(ty))
+    }
+
+    /// If the two key types may be the same.
+    fn compatible(&self, other: &Self) -> bool {
+        let literal_matches = |literal: &str, ty: &str| match literal {
+            "{integer}" => Self::INTEGERS.contains(&ty),
+            "{float}" => Self::FLOATS.contains(&ty),
+            _ => false,
+        };
+        self.0 == other.0
+            || literal_matches(&self.0, &other.0)
+            || literal_matches(&other.0, &self.0)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use syn::parse_quote;
+
+    use super::*;
+
+    /// Helper: the warning messages, checking each is followed by a help message.
+    fn warnings(flat_graph: &FlatGraph) -> Vec<&str> {
+        let diagnostics = &flat_graph.diagnostics;
+        assert!(diagnostics.len() % 2 == 0);
+        diagnostics
+            .chunks(2)
+            .map(|pair| {
+                assert_eq!(Level::Warning, pair[0].level());
+                assert_eq!(Level::Help, pair[1].level());
+                pair[0].message()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn unused_names() {
+        let flat_graph = FlatGraph::from_hfcode(parse_quote! {
+            unused = source_iter([1]) -> for_each(std::mem::drop);
+            _unused = source_iter([2]) -> for_each(std::mem::drop);
+        });
+        assert_eq!(
+            vec!["Name `unused` is defined but never used."],
+            warnings(&flat_graph)
+        );
+    }
+
+    #[test]
+    fn unconsumed_sources() {
+        let flat_graph = FlatGraph::from_hfcode(parse_quote! {
+            source_iter([1]) -> map(|x| x + 1) -> null();
+            source_iter([2]) -> inspect(|x| println!("{}", x)) -> null();
+        });
+        assert_eq!(
+            vec!["Output of `source_iter` is never consumed."],
+            warnings(&flat_graph)
+        );
+    }
+
+    #[test]
+    fn unbounded_state() {
+        let flat_graph = FlatGraph::from_hfcode(parse_quote! {
+            my_join = join::<'static>() -> for_each(std::mem::drop);
+            source_stream(recv) -> map(|x: u32| (x, ())) -> [0]my_join;
+            source_iter([(1, ())]) -> [1]my_join;
+            source_stream(recv2) -> unique::<'tick>() -> for_each(std::mem::drop);
+        });
+        assert_eq!(
+            vec!["`join::<'static>` keeps the items from unbounded `source_stream` forever, so its state grows without bound."],
+            warnings(&flat_graph)
+        );
+    }
+
+    #[test]
+    fn unbounded_state_default() {
+        let flat_graph = FlatGraph::from_hfcode(parse_quote! {
+            source_stream(recv) -> unique() -> for_each(std::mem::drop);
+            my_join = join() -> for_each(std::mem::drop);
+            source_stream(recv2) -> [0]my_join;
+            source_iter([(1, ())]) -> [1]my_join;
+            // `'tick` by default.
+            my_diff = difference() -> for_each(std::mem::drop);
+            source_stream(recv3) -> [pos]my_diff;
+            source_iter([1]) -> [neg]my_diff;
+        });
+        assert_eq!(
+            vec![
+                "`unique`, which is `'static` by default, keeps the items from unbounded `source_stream` forever, so its state grows without bound.",
+                "`join`, which is `'static` by default, keeps the items from unbounded `source_stream` forever, so its state grows without bound.",
+            ],
+            warnings(&flat_graph)
+        );
+    }
+
+    #[test]
+    fn spinning_cycles() {
+        let flat_graph = FlatGraph::from_hfcode(parse_quote! {
+            spin = merge() -> map(|x| x + 1) -> tee();
+            source_iter([1]) -> spin;
+            spin -> spin;
+            spin -> for_each(std::mem::drop);
+            stops = merge() -> filter(|&x| x < 10) -> tee();
+            source_iter([1]) -> stops;
+            stops -> stops;
+            stops -> for_each(std::mem::drop);
+        });
+        assert_eq!(
+            vec!["This cycle has no `next_tick()` or `next_stratum()`, and outputs every item it receives, so items will cycle forever within a tick."],
+            warnings(&flat_graph)
+        );
+    }
+
+    #[test]
+    fn join_key_types() {
+        let flat_graph = FlatGraph::from_hfcode(parse_quote! {
+            my_join = join::<'tick>() -> for_each(std::mem::drop);
+            source_iter([1]) -> map(|x| -> (u32, ()) { (x, ()) }) -> [0]my_join;
+            source_iter([("a", 1)]) -> [1]my_join;
+            same_join = join::<'tick>() -> for_each(std::mem::drop);
+            source_iter([(1_u32, 2)]) -> [0]same_join;
+            source_iter([(2_u32, 3)]) -> [1]same_join;
+        });
+        assert_eq!(
+            vec!["`join` inputs have different key types, `u32` in port 0 and `&str` in port 1."],
+            warnings(&flat_graph)
+        );
+    }
+
+    #[test]
+    fn lints_allowed() {
+        let flat_graph = FlatGraph::from_hfcode(parse_quote! {
+            #![allow(spinning_cycles, unused_names)]
+            #![allow(not_a_lint)]
+            unused = source_iter([1]) -> for_each(std::mem::drop);
+            spin = merge() -> map(|x| x + 1) -> tee();
+            source_iter([4]) -> spin;
+            spin -> spin;
+            spin -> for_each(std::mem::drop);
+        });
+        assert_eq!(vec!["Unknown lint `not_a_lint`."], warnings(&flat_graph));
+    }
+}
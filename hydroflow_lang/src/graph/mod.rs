@@ -16,8 +16,11 @@ pub mod di_mul_graph;
 pub mod flat_graph;
 pub mod flat_to_partitioned;
 pub mod graph_algorithms;
+pub mod lints;
 pub mod ops;
+pub mod optimize;
 pub mod partitioned_graph;
+pub mod pushdown;
 pub mod serde_graph;
 
 new_key_type! {
@@ -0,0 +1,74 @@
+use super::{
+    parse_single_persistence_lifetime, write_aggregate, AggregateOutput, DelayType,
+    OperatorConstraints, Persistence, WriteContextArgs, WriteIteratorArgs, RANGE_0, RANGE_1,
+};
+
+use quote::quote_spanned;
+
+/// > 1 input stream of type `T: Hash`, 1 output stream of type `usize`
+///
+/// Estimates the number of distinct items using a HyperLogLog sketch, outputting a single estimate.
+/// Uses a fixed 4 KiB of memory regardless of the number of distinct items, with a standard error
+/// of about 1.6%. See `hydroflow::lang::sketch` for the underlying sketch.
+///
+/// ```hydroflow
+/// // should print about 1000
+/// source_iter((0..1000).chain(0..500))
+///     -> approx_distinct()
+///     -> for_each(|n| println!("{}", n));
+/// ```
+///
+/// `approx_distinct` can also be provided with one generic lifetime persistence argument, either
+/// `'tick` or `'static`, to specify how data persists. The default is `'tick`. With `'static` the
+/// sketch is kept across ticks, so the estimate counts the distinct items across all ticks, a
+/// bounded-memory replacement for counting the output of `unique::<'static>()`.
+///
+/// ```rustbook
+/// let (input_send, input_recv) = hydroflow::util::unbounded_channel::<&str>();
+/// let mut flow = hydroflow::hydroflow_syntax! {
+///     source_stream(input_recv)
+///         -> approx_distinct::<'static>()
+///         -> for_each(|n| println!("{}", n));
+/// };
+///
+/// input_send.send("a").unwrap();
+/// input_send.send("b").unwrap();
+/// flow.run_available();
+/// // 2
+///
+/// input_send.send("a").unwrap();
+/// input_send.send("c").unwrap();
+/// flow.run_available();
+/// // 3
+/// ```
+#[hydroflow_internalmacro::operator_docgen]
+pub const APPROX_DISTINCT: OperatorConstraints = OperatorConstraints {
+    name: "approx_distinct",
+    hard_range_inn: RANGE_1,
+    soft_range_inn: RANGE_1,
+    hard_range_out: RANGE_1,
+    soft_range_out: RANGE_1,
+    ports_inn: None,
+    ports_out: None,
+    num_args: RANGE_0,
+    input_delaytype_fn: &|_| Some(DelayType::Stratum),
+    write_fn: &(|wc @ &WriteContextArgs { root, op_span, .. },
+                 wi @ &WriteIteratorArgs { is_pull, .. },
+                 diagnostics| {
+        assert!(is_pull);
+
+        let persistence =
+            parse_single_persistence_lifetime(wi, op_span, Persistence::Tick, diagnostics);
+
+        let aggregator = quote_spanned! {op_span=>
+            #root::lang::sketch::ApproxDistinct::default()
+        };
+        Ok(write_aggregate(
+            wc,
+            wi,
+            persistence,
+            aggregator,
+            AggregateOutput::Single,
+        ))
+    }),
+};
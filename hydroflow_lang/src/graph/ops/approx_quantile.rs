@@ -0,0 +1,61 @@
+use super::{
+    parse_single_persistence_lifetime, write_aggregate, AggregateOutput, DelayType,
+    OperatorConstraints, Persistence, WriteContextArgs, WriteIteratorArgs, RANGE_1,
+};
+
+use quote::quote_spanned;
+
+/// > 1 input stream of type `T: Into<f64>`, 1 output stream of type `f64`
+///
+/// > Arguments: `q`, the quantile to estimate, between `0.0` and `1.0` (e.g. `0.5` for the median
+/// or `0.99` for p99).
+///
+/// Estimates the `q`-quantile of the input values, outputting a single estimate (or nothing if
+/// there is no input). Values are counted in logarithmically sized buckets (DDSketch), so the
+/// estimate is within 1% of the value of that rank and memory is bounded. See
+/// `hydroflow::lang::sketch` for the underlying sketch.
+///
+/// ```hydroflow
+/// // should print about 50
+/// source_iter(1..=100)
+///     -> approx_quantile(0.5)
+///     -> for_each(|median| println!("{}", median));
+/// ```
+///
+/// `approx_quantile` can also be provided with one generic lifetime persistence argument, either
+/// `'tick` or `'static`, to specify how data persists. The default is `'tick`. With `'static` the
+/// sketch is kept across ticks and the quantile of all values so far is output each tick.
+#[hydroflow_internalmacro::operator_docgen]
+pub const APPROX_QUANTILE: OperatorConstraints = OperatorConstraints {
+    name: "approx_quantile",
+    hard_range_inn: RANGE_1,
+    soft_range_inn: RANGE_1,
+    hard_range_out: RANGE_1,
+    soft_range_out: RANGE_1,
+    ports_inn: None,
+    ports_out: None,
+    num_args: RANGE_1,
+    input_delaytype_fn: &|_| Some(DelayType::Stratum),
+    write_fn: &(|wc @ &WriteContextArgs { root, op_span, .. },
+                 wi @ &WriteIteratorArgs {
+                     arguments, is_pull, ..
+                 },
+                 diagnostics| {
+        assert!(is_pull);
+
+        let persistence =
+            parse_single_persistence_lifetime(wi, op_span, Persistence::Tick, diagnostics);
+
+        let q = &arguments[0];
+        let aggregator = quote_spanned! {op_span=>
+            #root::lang::sketch::ApproxQuantile::new(#q)
+        };
+        Ok(write_aggregate(
+            wc,
+            wi,
+            persistence,
+            aggregator,
+            AggregateOutput::Items,
+        ))
+    }),
+};
@@ -0,0 +1,60 @@
+use super::{
+    parse_single_persistence_lifetime, write_aggregate, AggregateOutput, DelayType,
+    OperatorConstraints, Persistence, WriteContextArgs, WriteIteratorArgs, RANGE_1,
+};
+
+use quote::quote_spanned;
+
+/// > 1 input stream of type `T: Hash + Eq + Clone`, 1 output stream of type `(T, u64)`
+///
+/// > Arguments: `k`, the number of items to output.
+///
+/// Outputs the approximately `k` most frequent items with their estimated counts, most frequent
+/// first. Counts are estimated with a Count-Min sketch, so memory is bounded by the sketch size
+/// plus `k` items regardless of the number of distinct items. Estimated counts may overcount but
+/// never undercount. See `hydroflow::lang::sketch` for the underlying sketch.
+///
+/// ```hydroflow
+/// // should print ("b", 3), ("a", 2)
+/// source_iter(vec!["a", "b", "c", "b", "a", "b"])
+///     -> approx_top_k(2)
+///     -> for_each(|x| println!("{:?}", x));
+/// ```
+///
+/// `approx_top_k` can also be provided with one generic lifetime persistence argument, either
+/// `'tick` or `'static`, to specify how data persists. The default is `'tick`. With `'static` the
+/// sketch is kept across ticks and the top `k` of all items so far is output each tick.
+#[hydroflow_internalmacro::operator_docgen]
+pub const APPROX_TOP_K: OperatorConstraints = OperatorConstraints {
+    name: "approx_top_k",
+    hard_range_inn: RANGE_1,
+    soft_range_inn: RANGE_1,
+    hard_range_out: RANGE_1,
+    soft_range_out: RANGE_1,
+    ports_inn: None,
+    ports_out: None,
+    num_args: RANGE_1,
+    input_delaytype_fn: &|_| Some(DelayType::Stratum),
+    write_fn: &(|wc @ &WriteContextArgs { root, op_span, .. },
+                 wi @ &WriteIteratorArgs {
+                     arguments, is_pull, ..
+                 },
+                 diagnostics| {
+        assert!(is_pull);
+
+        let persistence =
+            parse_single_persistence_lifetime(wi, op_span, Persistence::Tick, diagnostics);
+
+        let k = &arguments[0];
+        let aggregator = quote_spanned! {op_span=>
+            #root::lang::sketch::ApproxTopK::new(#k)
+        };
+        Ok(write_aggregate(
+            wc,
+            wi,
+            persistence,
+            aggregator,
+            AggregateOutput::Items,
+        ))
+    }),
+};
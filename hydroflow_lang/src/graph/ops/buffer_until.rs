@@ -0,0 +1,108 @@
+use super::{
+    DelayType, OperatorConstraints, OperatorWriteOutput, WriteContextArgs, WriteIteratorArgs,
+    RANGE_1,
+};
+
+use crate::graph::PortIndexValue;
+
+use quote::{quote_spanned, ToTokens};
+use syn::parse_quote;
+
+/// > 2 input streams, `input` of type `T` and `signal` of type `S`, 1 output stream of type `T`
+///
+/// A gated buffer: accumulates the items from the `input` port across ticks, holding them until an
+/// item arrives on the `signal` port. The argument is a predicate `|item: &T, signal: &S| -> bool`;
+/// when signals arrive, each buffered item for which the predicate returns `true` for any of the
+/// signals is released to the output (in arrival order) and removed from the buffer. Items that
+/// don't match stay buffered for later signals. Use `|_, _| true` to release everything.
+///
+/// The `signal` input is a stratum barrier, so all signals in a tick are seen together.
+///
+/// ```hydroflow
+/// // should print 1, 3
+/// gate = buffer_until(|item: &usize, _signal: &()| item % 2 == 1);
+/// source_iter(vec![1, 2, 3]) -> [input]gate;
+/// source_iter(vec![()]) -> [signal]gate;
+/// gate -> for_each(|x| println!("{}", x));
+/// ```
+///
+/// For example, writes can be batched per transaction until the transaction is committed:
+///
+/// ```rustbook
+/// let (write_send, write_recv) = hydroflow::util::unbounded_channel::<(usize, &str)>();
+/// let (commit_send, commit_recv) = hydroflow::util::unbounded_channel::<usize>();
+/// let mut flow = hydroflow::hydroflow_syntax! {
+///     gate = buffer_until(|(txn, _write): &(usize, &str), commit: &usize| txn == commit);
+///     source_stream(write_recv) -> [input]gate;
+///     source_stream(commit_recv) -> [signal]gate;
+///     gate -> for_each(|(txn, write)| println!("txn {} wrote {}", txn, write));
+/// };
+/// write_send.send((1, "x")).unwrap();
+/// write_send.send((2, "y")).unwrap();
+/// flow.run_available();
+/// // nothing yet
+///
+/// commit_send.send(2).unwrap();
+/// flow.run_available();
+/// // txn 2 wrote y
+/// ```
+#[hydroflow_internalmacro::operator_docgen]
+pub const BUFFER_UNTIL: OperatorConstraints = OperatorConstraints {
+    name: "buffer_until",
+    hard_range_inn: &(2..=2),
+    soft_range_inn: &(2..=2),
+    hard_range_out: RANGE_1,
+    soft_range_out: RANGE_1,
+    ports_inn: Some(&|| super::PortListSpec::Fixed(parse_quote! { input, signal })),
+    ports_out: None,
+    num_args: RANGE_1,
+    input_delaytype_fn: &|idx| match idx {
+        PortIndexValue::Path(path) if "signal" == path.to_token_stream().to_string() => {
+            Some(DelayType::Stratum)
+        }
+        _else => None,
+    },
+    write_fn: &(|wc @ &WriteContextArgs { op_span, .. },
+                 &WriteIteratorArgs {
+                     ident,
+                     inputs,
+                     arguments,
+                     is_pull,
+                     ..
+                 },
+                 _| {
+        assert!(is_pull);
+
+        let input = &inputs[0];
+        let signal = &inputs[1];
+        let predicate = &arguments[0];
+
+        let bufferdata_ident = wc.make_ident("bufferdata");
+        let write_prologue = quote_spanned! {op_span=>
+            let #bufferdata_ident = df.add_state(::std::cell::RefCell::new(::std::vec::Vec::new()));
+        };
+        let write_iterator = quote_spanned! {op_span=>
+            let #ident = {
+                let mut buffer = context.state_ref(#bufferdata_ident).borrow_mut();
+                buffer.extend(#input);
+                let signals = #signal.collect::<::std::vec::Vec<_>>();
+                let mut released = ::std::vec::Vec::new();
+                if !signals.is_empty() {
+                    let predicate = #predicate;
+                    let (matched, held) = ::std::mem::take(&mut *buffer)
+                        .into_iter()
+                        .partition(|item| signals.iter().any(|signal| (predicate)(item, signal)));
+                    released = matched;
+                    *buffer = held;
+                }
+                released.into_iter()
+            };
+        };
+
+        Ok(OperatorWriteOutput {
+            write_prologue,
+            write_iterator,
+            ..Default::default()
+        })
+    }),
+};
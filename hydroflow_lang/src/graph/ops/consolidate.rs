@@ -0,0 +1,49 @@
+use super::{
+    DelayType, OperatorConstraints, OperatorWriteOutput, WriteContextArgs, WriteIteratorArgs,
+    RANGE_0, RANGE_1,
+};
+
+use quote::quote_spanned;
+
+/// > 1 input stream of type <(T, i64)>, 1 output stream of type <(T, i64)>
+///
+/// Sums the diffs of equal items within a tick, dropping items whose changes cancel out. Useful
+/// before sending the output of the differential operators (such as [`diff_join`](#diff_join))
+/// out of the graph.
+///
+/// ```hydroflow
+/// // should print `("b", 2)`
+/// source_iter(vec![("a", 1), ("b", 1), ("a", -1), ("b", 1)])
+///     -> consolidate()
+///     -> for_each(|x| println!("{:?}", x));
+/// ```
+#[hydroflow_internalmacro::operator_docgen]
+pub const CONSOLIDATE: OperatorConstraints = OperatorConstraints {
+    name: "consolidate",
+    hard_range_inn: RANGE_1,
+    soft_range_inn: RANGE_1,
+    hard_range_out: RANGE_1,
+    soft_range_out: RANGE_1,
+    ports_inn: None,
+    ports_out: None,
+    num_args: RANGE_0,
+    input_delaytype_fn: &|_| Some(DelayType::Stratum),
+    write_fn: &(|&WriteContextArgs { root, op_span, .. },
+                 &WriteIteratorArgs {
+                     ident,
+                     inputs,
+                     is_pull,
+                     ..
+                 },
+                 _| {
+        assert!(is_pull);
+        let input = &inputs[0];
+        let write_iterator = quote_spanned! {op_span=>
+            let #ident = #root::compiled::pull::consolidate(#input).into_iter();
+        };
+        Ok(OperatorWriteOutput {
+            write_iterator,
+            ..Default::default()
+        })
+    }),
+};
@@ -1,4 +1,4 @@
-use super::{OperatorConstraints, WriteContextArgs, WriteIteratorArgs, RANGE_1};
+use super::{OperatorConstraints, WriteContextArgs, WriteIteratorArgs, RANGE_0, RANGE_1};
 
 use quote::quote_spanned;
 use syn::parse_quote;
@@ -43,7 +43,7 @@ pub const CROSS_JOIN: OperatorConstraints = OperatorConstraints {
     soft_range_out: RANGE_1,
     ports_inn: Some(&(|| super::PortListSpec::Fixed(parse_quote! { 0, 1 }))),
     ports_out: None,
-    num_args: 0,
+    num_args: RANGE_0,
     input_delaytype_fn: &|_| None,
     write_fn: &(|wc @ &WriteContextArgs { op_span, .. },
                  wi @ &WriteIteratorArgs { ident, inputs, .. },
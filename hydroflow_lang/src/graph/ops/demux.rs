@@ -50,7 +50,7 @@ pub const DEMUX: OperatorConstraints = OperatorConstraints {
     soft_range_out: &(2..),
     ports_inn: None,
     ports_out: Some(&(|| PortListSpec::Variadic)),
-    num_args: 1,
+    num_args: RANGE_1,
     input_delaytype_fn: &|_| None,
     write_fn: &(|&WriteContextArgs { root, op_span, .. },
                  &WriteIteratorArgs {
@@ -4,7 +4,8 @@ use super::{
 
 use quote::quote_spanned;
 
-/// > Arguments: An [async `Sink`](https://docs.rs/futures/latest/futures/sink/trait.Sink.html).
+/// > Arguments: An [async `Sink`](https://docs.rs/futures/latest/futures/sink/trait.Sink.html),
+/// and optionally the maximum number of items in flight.
 ///
 /// Consumes items by sending them to an [async `Sink`](https://docs.rs/futures/latest/futures/sink/trait.Sink.html).
 /// A `Sink` is a thing into which values can be sent, asynchronously. For example, sending items
@@ -12,6 +13,12 @@ use quote::quote_spanned;
 ///
 /// Note this operator must be used within a Tokio runtime.
 ///
+/// Items are buffered until the `Sink` accepts them. If a maximum number of items in flight is
+/// given, e.g. `dest_sink(sink, 1024)`, then once that many items are buffered the subgraph stops
+/// pulling from its sources (such as `source_stream`) until the `Sink` catches up. Combined with
+/// bounded handoffs ([`Hydroflow::set_handoff_capacity`](crate::scheduled::graph::Hydroflow::set_handoff_capacity))
+/// this pushes back on sources in other subgraphs too.
+///
 /// ```rustbook
 /// # #[tokio::main(flavor = "current_thread")]
 /// # async fn main() {
@@ -89,7 +96,7 @@ pub const DEST_SINK: OperatorConstraints = OperatorConstraints {
     soft_range_out: RANGE_0,
     ports_inn: None,
     ports_out: None,
-    num_args: 1,
+    num_args: &(1..=2),
     input_delaytype_fn: &|_| None,
     write_fn: &(|wc @ &WriteContextArgs { root, op_span, .. },
                  &WriteIteratorArgs {
@@ -100,15 +107,25 @@ pub const DEST_SINK: OperatorConstraints = OperatorConstraints {
 
         let send_ident = wc.make_ident("item_send");
         let recv_ident = wc.make_ident("item_recv");
+        let in_flight_ident = wc.make_ident("in_flight");
+
+        // Optional backpressure: stop pulling once too many items are waiting on the sink.
+        let block_if_full = arguments.iter().nth(1).map(|max_in_flight| {
+            quote_spanned! {op_span=>
+                #in_flight_ident.block_if_full(#max_in_flight, context);
+            }
+        });
 
         let write_prologue = quote_spanned! {op_span=>
             let (#send_ident, #recv_ident) = #root::tokio::sync::mpsc::unbounded_channel();
+            let #in_flight_ident = #root::util::InFlight::default();
             {
                 /// Function is needed so `Item` is so no ambiguity for what `Item` is used
                 /// when calling `.flush()`.
                 async fn sink_feed_flush<Sink, Item>(
                     mut recv: #root::tokio::sync::mpsc::UnboundedReceiver<Item>,
                     mut sink: Sink,
+                    in_flight: #root::util::InFlight,
                 ) where
                     Sink: ::std::marker::Unpin + #root::futures::Sink<Item>,
                     Sink::Error: ::std::fmt::Debug,
@@ -118,23 +135,28 @@ pub const DEST_SINK: OperatorConstraints = OperatorConstraints {
                         sink.feed(item)
                             .await
                             .expect("Error processing async sink item.");
+                        let mut count = 1;
                         while let Ok(item) = recv.try_recv() {
                             sink.feed(item)
                                 .await
                                 .expect("Error processing async sink item.");
+                            count += 1;
                         }
                         sink.flush().await.expect("Failed to flush sink.");
+                        in_flight.done(count);
                     }
                 }
                 df
-                    .spawn_task(sink_feed_flush(#recv_ident, #sink_arg))
+                    .spawn_task(sink_feed_flush(#recv_ident, #sink_arg, #in_flight_ident.clone()))
                     .expect("dest_sink() must be used within a tokio runtime");
             }
         };
 
         let write_iterator = quote_spanned! {op_span=>
             let #ident = #root::pusherator::for_each::ForEach::new(|item| {
+                #in_flight_ident.add(1);
                 #send_ident.send(item).expect("Failed to send async write item for processing.");
+                #block_if_full
             });
         };
 
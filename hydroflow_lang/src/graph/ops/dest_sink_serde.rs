@@ -4,11 +4,14 @@ use super::{
 
 use quote::quote_spanned;
 
-/// > Arguments: A [serializing async `Sink`](https://docs.rs/futures/latest/futures/sink/trait.Sink.html).
+/// > Arguments: A [serializing async `Sink`](https://docs.rs/futures/latest/futures/sink/trait.Sink.html),
+/// and optionally the maximum number of items in flight.
 ///
 /// Consumes (payload, addr) pairs by serializing the payload and sending the resulting pair to an [async `Sink`](https://docs.rs/futures/latest/futures/sink/trait.Sink.html).
 ///
-/// Note this operator must be used within a Tokio runtime.
+/// Note this operator must be used within a Tokio runtime. Like `dest_sink`, the optional maximum
+/// number of items in flight makes a slow `Sink` push back on upstream sources.
+///
 /// ```rustbook
 /// async fn serde_out() {
 ///     let addr = hydroflow::util::ipv4_resolve("localhost:9000".into()).unwrap();
@@ -30,7 +33,7 @@ pub const DEST_SINK_SERDE: OperatorConstraints = OperatorConstraints {
     soft_range_out: RANGE_0,
     ports_inn: None,
     ports_out: None,
-    num_args: 1,
+    num_args: &(1..=2),
     input_delaytype_fn: &|_| None,
     write_fn: &(|wc @ &WriteContextArgs { root, op_span, .. },
                  &WriteIteratorArgs {
@@ -41,24 +44,39 @@ pub const DEST_SINK_SERDE: OperatorConstraints = OperatorConstraints {
 
         let send_ident = wc.make_ident("item_send");
         let recv_ident = wc.make_ident("item_recv");
+        let in_flight_ident = wc.make_ident("in_flight");
+
+        // Optional backpressure: stop pulling once too many items are waiting on the sink.
+        let block_if_full = arguments.iter().nth(1).map(|max_in_flight| {
+            quote_spanned! {op_span=>
+                #in_flight_ident.block_if_full(#max_in_flight, context);
+            }
+        });
 
         let write_prologue = quote_spanned! {op_span=>
             let (#send_ident, #recv_ident) = #root::tokio::sync::mpsc::unbounded_channel();
+            let #in_flight_ident = #root::util::InFlight::default();
             df
-                .spawn_task(async move {
-                    use #root::futures::sink::SinkExt;
+                .spawn_task({
+                    let in_flight = #in_flight_ident.clone();
+                    async move {
+                        use #root::futures::sink::SinkExt;
 
-                    let mut recv = #recv_ident;
-                    let mut sink = #sink_arg;
-                    while let Some((payload, addr)) = recv.recv().await {
-                        let item = (#root::util::serialize_to_bytes(payload), addr);
-                        sink.feed(item).await.expect("Error processing async sink item.");
-                        // Receive as many items synchronously as possible before flushing.
-                        while let Ok((payload, addr)) = recv.try_recv() {
+                        let mut recv = #recv_ident;
+                        let mut sink = #sink_arg;
+                        while let Some((payload, addr)) = recv.recv().await {
                             let item = (#root::util::serialize_to_bytes(payload), addr);
                             sink.feed(item).await.expect("Error processing async sink item.");
+                            let mut count = 1;
+                            // Receive as many items synchronously as possible before flushing.
+                            while let Ok((payload, addr)) = recv.try_recv() {
+                                let item = (#root::util::serialize_to_bytes(payload), addr);
+                                sink.feed(item).await.expect("Error processing async sink item.");
+                                count += 1;
+                            }
+                            sink.flush().await.expect("Failed to flush async sink.");
+                            in_flight.done(count);
                         }
-                        sink.flush().await.expect("Failed to flush async sink.");
                     }
                 })
                 .expect("dest_sink() must be used within a tokio runtime");
@@ -66,7 +84,9 @@ pub const DEST_SINK_SERDE: OperatorConstraints = OperatorConstraints {
 
         let write_iterator = quote_spanned! {op_span=>
             let #ident = #root::pusherator::for_each::ForEach::new(|item| {
+                #in_flight_ident.add(1);
                 #send_ident.send(item).expect("Failed to send async write item for processing.");
+                #block_if_full
             });
         };
 
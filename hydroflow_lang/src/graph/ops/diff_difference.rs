@@ -0,0 +1,69 @@
+use crate::graph::PortIndexValue;
+
+use super::{
+    DelayType, OperatorConstraints, OperatorWriteOutput, WriteContextArgs, WriteIteratorArgs,
+    RANGE_0, RANGE_1,
+};
+
+use quote::{quote_spanned, ToTokens};
+use syn::parse_quote;
+
+/// > 2 input streams of the same type <(T, i64)>, 1 output stream of type <(T, i64)>
+///
+/// Differential version of [`difference`](#difference). Both inputs carry items paired with their
+/// change in multiplicity. An item is in the output set while it is present (has a positive
+/// accumulated multiplicity) in `pos` and absent from `neg`. The operator emits `(item, 1)` when an
+/// item enters the output set and `(item, -1)` when it leaves, for example when it is retracted
+/// from `pos` or inserted into `neg`.
+///
+/// State is always kept across ticks.
+///
+/// ```hydroflow
+/// // should print `("elephant", 1)`
+/// diff = diff_difference();
+/// source_iter(vec![("dog", 1), ("cat", 1), ("elephant", 1), ("cat", -1)]) -> [pos]diff;
+/// source_iter(vec![("dog", 1), ("gorilla", 1)]) -> [neg]diff;
+/// diff -> for_each(|v| println!("{:?}", v));
+/// ```
+#[hydroflow_internalmacro::operator_docgen]
+pub const DIFF_DIFFERENCE: OperatorConstraints = OperatorConstraints {
+    name: "diff_difference",
+    hard_range_inn: &(2..=2),
+    soft_range_inn: &(2..=2),
+    hard_range_out: RANGE_1,
+    soft_range_out: RANGE_1,
+    ports_inn: Some(&|| super::PortListSpec::Fixed(parse_quote! { pos, neg })),
+    ports_out: None,
+    num_args: RANGE_0,
+    input_delaytype_fn: &|idx| match idx {
+        PortIndexValue::Path(path) if "neg" == path.to_token_stream().to_string() => {
+            Some(DelayType::Stratum)
+        }
+        _else => None,
+    },
+    write_fn: &(|wc @ &WriteContextArgs { root, op_span, .. },
+                 &WriteIteratorArgs { ident, inputs, .. },
+                 _| {
+        let diffdata_ident = wc.make_ident("diffdifferencedata");
+        let write_prologue = quote_spanned! {op_span=>
+            let #diffdata_ident = df.add_state(std::cell::RefCell::new(
+                #root::compiled::pull::DiffDifferenceState::default()
+            ));
+        };
+
+        let input_neg = &inputs[0]; // N before P
+        let input_pos = &inputs[1];
+        let write_iterator = quote_spanned! {op_span=>
+            let #ident = {
+                let mut state = context.state_ref(#diffdata_ident).borrow_mut();
+                state.difference(#input_pos, #input_neg).into_iter()
+            };
+        };
+
+        Ok(OperatorWriteOutput {
+            write_prologue,
+            write_iterator,
+            ..Default::default()
+        })
+    }),
+};
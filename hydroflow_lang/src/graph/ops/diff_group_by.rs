@@ -0,0 +1,86 @@
+use super::{
+    DelayType, OperatorConstraints, OperatorWriteOutput, WriteContextArgs, WriteIteratorArgs,
+    RANGE_1,
+};
+
+use quote::quote_spanned;
+
+/// > 1 input stream of type <((K, V1), i64)>, 1 output stream of type <((K, V2), i64)>
+///
+/// > Arguments: two Rust closures. The first generates an initial value per group. The second
+/// itself takes three arguments: an 'accumulator', an element, and the element's change in
+/// multiplicity, which it applies to the accumulator.
+///
+/// Differential version of [`group_by`](#group_by). Each input key-value pair is paired with its
+/// change in multiplicity. Like `group_by::<'static>`, the operator keeps the accumulator of each
+/// group across ticks, and only the changed values are passed to the second closure, so a
+/// retraction must undo the corresponding insertion (e.g. `*sum += val * diff`). For each group
+/// whose aggregate changed, the previous aggregate is retracted with `((key, old), -1)` and the
+/// new one inserted with `((key, new), 1)`. A group whose values are all retracted only has its
+/// old aggregate retracted.
+///
+/// Because aggregates are compared to avoid emitting no-op changes, the accumulator type must
+/// implement `Clone` and `Eq`.
+///
+/// ```rustbook
+/// let (input_send, input_recv) = hydroflow::util::unbounded_channel::<((&str, i64), i64)>();
+/// let mut flow = hydroflow::hydroflow_syntax! {
+///     source_stream(input_recv)
+///         -> diff_group_by(|| 0, |sum: &mut i64, val: i64, diff: i64| *sum += val * diff)
+///         -> for_each(|x| println!("{:?}", x));
+/// };
+///
+/// input_send.send((("toy", 1), 1)).unwrap();
+/// input_send.send((("toy", 2), 1)).unwrap();
+/// flow.run_available();
+/// // (("toy", 3), 1)
+///
+/// input_send.send((("toy", 2), -1)).unwrap();
+/// flow.run_available();
+/// // (("toy", 3), -1)
+/// // (("toy", 1), 1)
+/// ```
+#[hydroflow_internalmacro::operator_docgen]
+pub const DIFF_GROUP_BY: OperatorConstraints = OperatorConstraints {
+    name: "diff_group_by",
+    hard_range_inn: RANGE_1,
+    soft_range_inn: RANGE_1,
+    hard_range_out: RANGE_1,
+    soft_range_out: RANGE_1,
+    ports_inn: None,
+    ports_out: None,
+    num_args: &(2..=2),
+    input_delaytype_fn: &|_| Some(DelayType::Stratum),
+    write_fn: &(|wc @ &WriteContextArgs { root, op_span, .. },
+                 &WriteIteratorArgs {
+                     ident,
+                     inputs,
+                     arguments,
+                     is_pull,
+                     ..
+                 },
+                 _| {
+        assert!(is_pull);
+
+        let groupbydata_ident = wc.make_ident("diffgroupbydata");
+        let write_prologue = quote_spanned! {op_span=>
+            let #groupbydata_ident = df.add_state(::std::cell::RefCell::new(::std::collections::HashMap::new()));
+        };
+
+        let input = &inputs[0];
+        let initfn = &arguments[0];
+        let aggfn = &arguments[1];
+        let write_iterator = quote_spanned! {op_span=>
+            let #ident = {
+                let mut groups = context.state_ref(#groupbydata_ident).borrow_mut();
+                #root::compiled::pull::diff_group_by_into(&mut *groups, #input, #initfn, #aggfn).into_iter()
+            };
+        };
+
+        Ok(OperatorWriteOutput {
+            write_prologue,
+            write_iterator,
+            ..Default::default()
+        })
+    }),
+};
@@ -0,0 +1,61 @@
+use super::{
+    OperatorConstraints, OperatorWriteOutput, WriteContextArgs, WriteIteratorArgs, RANGE_0, RANGE_1,
+};
+
+use quote::quote_spanned;
+use syn::parse_quote;
+
+/// > 2 input streams of type <((K, V1), i64)> and <((K, V2), i64)>, 1 output stream of type <((K, (V1, V2)), i64)>
+///
+/// Differential version of [`join`](#join). Each item is paired with a diff, the change in its
+/// multiplicity: positive for insertions and negative for retractions. The output diffs are the
+/// changes to the equijoin of the accumulated inputs, so retracting a pair on one side retracts
+/// every joined pair it contributed to.
+///
+/// State is always kept across ticks (like `join::<'static>()`), and keys whose multiplicities
+/// cancel out are removed from the state.
+///
+/// ```hydroflow
+/// // should print `(("hello", ("world", "cleveland")), 1)` then `(("hello", ("world", "cleveland")), -1)`
+/// my_join = diff_join();
+/// source_iter(vec![(("hello", "world"), 1), (("hello", "world"), -1)]) -> [0]my_join;
+/// source_iter(vec![(("hello", "cleveland"), 1)]) -> [1]my_join;
+/// my_join -> for_each(|x| println!("{:?}", x));
+/// ```
+#[hydroflow_internalmacro::operator_docgen]
+pub const DIFF_JOIN: OperatorConstraints = OperatorConstraints {
+    name: "diff_join",
+    hard_range_inn: &(2..=2),
+    soft_range_inn: &(2..=2),
+    hard_range_out: RANGE_1,
+    soft_range_out: RANGE_1,
+    ports_inn: Some(&(|| super::PortListSpec::Fixed(parse_quote! { 0, 1 }))),
+    ports_out: None,
+    num_args: RANGE_0,
+    input_delaytype_fn: &|_| None,
+    write_fn: &(|wc @ &WriteContextArgs { root, op_span, .. },
+                 &WriteIteratorArgs { ident, inputs, .. },
+                 _| {
+        let joindata_ident = wc.make_ident("diffjoindata");
+        let write_prologue = quote_spanned! {op_span=>
+            let #joindata_ident = df.add_state(std::cell::RefCell::new(
+                #root::compiled::pull::DiffJoinState::default()
+            ));
+        };
+
+        let lhs = &inputs[0];
+        let rhs = &inputs[1];
+        let write_iterator = quote_spanned! {op_span=>
+            let #ident = {
+                let mut state = context.state_ref(#joindata_ident).borrow_mut();
+                state.join(#lhs, #rhs).into_iter()
+            };
+        };
+
+        Ok(OperatorWriteOutput {
+            write_prologue,
+            write_iterator,
+            ..Default::default()
+        })
+    }),
+};
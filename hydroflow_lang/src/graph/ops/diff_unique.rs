@@ -0,0 +1,80 @@
+use super::{
+    DelayType, OperatorConstraints, OperatorWriteOutput, WriteContextArgs, WriteIteratorArgs,
+    RANGE_0, RANGE_1,
+};
+
+use quote::quote_spanned;
+
+/// > 1 input stream of type <(T, i64)>, 1 output stream of type <(T, i64)>
+///
+/// Differential version of [`unique`](#unique). Takes a stream of items paired with their change
+/// in multiplicity and emits `(item, 1)` when an item's accumulated multiplicity becomes positive
+/// and `(item, -1)` when it drops back to zero or below. The output therefore describes the set of
+/// distinct items currently present in the input.
+///
+/// State is always kept across ticks (like `unique::<'static>()`).
+///
+/// ```rustbook
+/// let (input_send, input_recv) = hydroflow::util::unbounded_channel::<(usize, i64)>();
+/// let mut flow = hydroflow::hydroflow_syntax! {
+///     source_stream(input_recv)
+///         -> diff_unique()
+///         -> for_each(|x| println!("{:?}", x));
+/// };
+///
+/// input_send.send((3, 1)).unwrap();
+/// input_send.send((3, 1)).unwrap();
+/// flow.run_available();
+/// // (3, 1)
+///
+/// input_send.send((3, -1)).unwrap();
+/// flow.run_available();
+/// // Nothing, 3 is still present once.
+///
+/// input_send.send((3, -1)).unwrap();
+/// flow.run_available();
+/// // (3, -1)
+/// ```
+#[hydroflow_internalmacro::operator_docgen]
+pub const DIFF_UNIQUE: OperatorConstraints = OperatorConstraints {
+    name: "diff_unique",
+    hard_range_inn: RANGE_1,
+    soft_range_inn: RANGE_1,
+    hard_range_out: RANGE_1,
+    soft_range_out: RANGE_1,
+    ports_inn: None,
+    ports_out: None,
+    num_args: RANGE_0,
+    input_delaytype_fn: &|_| Some(DelayType::Stratum),
+    write_fn: &(|wc @ &WriteContextArgs { root, op_span, .. },
+                 &WriteIteratorArgs {
+                     ident,
+                     inputs,
+                     is_pull,
+                     ..
+                 },
+                 _| {
+        assert!(is_pull);
+
+        let uniquedata_ident = wc.make_ident("diffuniquedata");
+        let write_prologue = quote_spanned! {op_span=>
+            let #uniquedata_ident = df.add_state(::std::cell::RefCell::new(
+                #root::compiled::pull::DiffUniqueState::default()
+            ));
+        };
+
+        let input = &inputs[0];
+        let write_iterator = quote_spanned! {op_span=>
+            let #ident = {
+                let mut state = context.state_ref(#uniquedata_ident).borrow_mut();
+                state.unique(#input).into_iter()
+            };
+        };
+
+        Ok(OperatorWriteOutput {
+            write_prologue,
+            write_iterator,
+            ..Default::default()
+        })
+    }),
+};
@@ -1,12 +1,14 @@
+use crate::diagnostic::{Diagnostic, Level};
 use crate::graph::PortIndexValue;
 
 use super::{
-    DelayType, OperatorConstraints, OperatorWriteOutput, WriteContextArgs, WriteIteratorArgs,
-    RANGE_1,
+    parse_persistence_lifetimes, DelayType, OperatorConstraints, OperatorWriteOutput, Persistence,
+    WriteContextArgs, WriteIteratorArgs, RANGE_0, RANGE_1,
 };
 
 use quote::{quote_spanned, ToTokens};
 use syn::parse_quote;
+use syn::spanned::Spanned;
 
 /// > 2 input streams of the same type T, 1 output stream of type T
 ///
@@ -21,6 +23,38 @@ use syn::parse_quote;
 /// source_iter(vec!["dog", "cat", "gorilla"]) -> [neg]diff;
 /// diff -> for_each(|v| println!("{}", v));
 /// ```
+///
+/// `difference` can also be provided with one or two generic lifetime persistence arguments, either
+/// `'tick` or `'static`, to specify how data on each input port persists. With `'tick`, items are
+/// only considered within the same tick. A `'static` `neg` input remembers every item it has
+/// received, so items in later ticks are also subtracted (a growing set of tombstones). A
+/// `'static` `pos` input remembers the set of items it has received and re-emits those not (yet)
+/// found in `neg` once each tick. When not explicitly specified persistence defaults to `'tick`
+/// for both ports.
+///
+/// When two persistence arguments are supplied the first maps to port `pos` and the second maps to
+/// port `neg`. When a single persistence argument is supplied, it is applied to both input ports.
+///
+/// ```rustbook
+/// let (pos_send, pos_recv) = hydroflow::util::unbounded_channel::<&str>();
+/// let (neg_send, neg_recv) = hydroflow::util::unbounded_channel::<&str>();
+/// let mut flow = hydroflow::hydroflow_syntax! {
+///     diff = difference::<'tick, 'static>();
+///     source_stream(pos_recv) -> [pos]diff;
+///     source_stream(neg_recv) -> [neg]diff;
+///     diff -> for_each(|v| println!("{}", v));
+/// };
+/// neg_send.send("dog").unwrap();
+/// pos_send.send("cat").unwrap();
+/// flow.run_tick();
+/// // cat
+///
+/// pos_send.send("dog").unwrap();
+/// pos_send.send("elephant").unwrap();
+/// flow.run_tick();
+/// // elephant
+/// // Note: "dog" was removed in an earlier tick but is still subtracted.
+/// ```
 #[hydroflow_internalmacro::operator_docgen]
 pub const DIFFERENCE: OperatorConstraints = OperatorConstraints {
     name: "difference",
@@ -30,7 +64,7 @@ pub const DIFFERENCE: OperatorConstraints = OperatorConstraints {
     soft_range_out: RANGE_1,
     ports_inn: Some(&|| super::PortListSpec::Fixed(parse_quote! { pos, neg })),
     ports_out: None,
-    num_args: 0,
+    num_args: RANGE_0,
     input_delaytype_fn: &|idx| match idx {
         PortIndexValue::Path(path) if "neg" == path.to_token_stream().to_string() => {
             Some(DelayType::Stratum)
@@ -38,29 +72,117 @@ pub const DIFFERENCE: OperatorConstraints = OperatorConstraints {
         _else => None,
     },
     write_fn: &(|wc @ &WriteContextArgs { root, op_span, .. },
-                 &WriteIteratorArgs { ident, inputs, .. },
-                 _| {
+                 wi @ &WriteIteratorArgs {
+                     ident,
+                     inputs,
+                     generic_args,
+                     op_name,
+                     ..
+                 },
+                 diagnostics| {
+        let persistence = parse_persistence_lifetimes(wi, diagnostics);
+        let [pos_persistence, neg_persistence] = match *persistence {
+            [] => [Persistence::Tick, Persistence::Tick],
+            [a] => [a, a],
+            [a, b] => [a, b],
+            _ => {
+                diagnostics.push(Diagnostic::spanned(
+                    generic_args.map(Spanned::span).unwrap_or(op_span),
+                    Level::Error,
+                    format!(
+                        "Operator `{}` expects zero, one, or two persistence lifetime generic arguments",
+                        op_name
+                    ),
+                ));
+                [Persistence::Tick, Persistence::Tick]
+            }
+        };
+
+        let input_neg = &inputs[0]; // N before P
+        let input_pos = &inputs[1];
+
         let handle_ident = wc.make_ident("diffdata_handle");
-        let write_prologue = quote_spanned! {op_span=>
-            let #handle_ident = df.add_state(std::cell::RefCell::new(
-                #root::lang::monotonic_map::MonotonicMap::<_, std::collections::HashSet<_>>::default(),
-            ));
+        let borrow_ident = wc.make_ident("borrow");
+        let negset_ident = wc.make_ident("negset");
+        let (neg_prologue, neg_iterator) = match neg_persistence {
+            Persistence::Tick => (
+                quote_spanned! {op_span=>
+                    let #handle_ident = df.add_state(std::cell::RefCell::new(
+                        #root::lang::monotonic_map::MonotonicMap::<_, std::collections::HashSet<_>>::default(),
+                    ));
+                },
+                quote_spanned! {op_span=>
+                    let mut #borrow_ident = context.state_ref(#handle_ident).borrow_mut();
+                    let #negset_ident = #borrow_ident
+                        .try_insert_with((context.current_tick(), context.current_stratum()), || {
+                            #input_neg.collect()
+                        });
+                },
+            ),
+            Persistence::Static => (
+                quote_spanned! {op_span=>
+                    let #handle_ident = df.add_state(std::cell::RefCell::new(
+                        std::collections::HashSet::new(),
+                    ));
+                },
+                quote_spanned! {op_span=>
+                    let mut #borrow_ident = context.state_ref(#handle_ident).borrow_mut();
+                    #borrow_ident.extend(#input_neg);
+                    let #negset_ident = &*#borrow_ident;
+                },
+            ),
         };
-        let write_iterator = {
-            let borrow_ident = wc.make_ident("borrow");
-            let negset_ident = wc.make_ident("negset");
 
-            let input_neg = &inputs[0]; // N before P
-            let input_pos = &inputs[1];
-            quote_spanned! {op_span=>
-                let mut #borrow_ident = context.state_ref(#handle_ident).borrow_mut();
-                let #negset_ident = #borrow_ident
-                    .try_insert_with((context.current_tick(), context.current_stratum()), || {
-                        #input_neg.collect()
-                    });
-                let #ident = #input_pos.filter(move |x| !#negset_ident.contains(x));
+        let posdata_ident = wc.make_ident("diffdata_pos");
+        let (pos_prologue, write_iterator) = match pos_persistence {
+            Persistence::Tick => (
+                Default::default(),
+                quote_spanned! {op_span=>
+                    #neg_iterator
+                    let #ident = #input_pos.filter(move |x| !#negset_ident.contains(x));
+                },
+            ),
+            Persistence::Static => {
+                // Items removed by a `'static` `neg` can never reappear, so they are dropped from the state.
+                let retain = (Persistence::Static == neg_persistence).then(|| {
+                    quote_spanned! {op_span=>
+                        pos.retain(|x| !#negset_ident.contains(x));
+                    }
+                });
+                (
+                    quote_spanned! {op_span=>
+                        let #posdata_ident = df.add_state(std::cell::RefCell::new(
+                            (std::option::Option::None, std::collections::HashSet::new()),
+                        ));
+                    },
+                    // The remembered items are emitted on the first run of each tick, later runs
+                    // within the same tick only emit their new items.
+                    quote_spanned! {op_span=>
+                        #neg_iterator
+                        let #ident = {
+                            let mut state = context.state_ref(#posdata_ident).borrow_mut();
+                            let (last_tick, pos) = &mut *state;
+                            let new_items = #input_pos
+                                .filter(|x| pos.insert(::std::clone::Clone::clone(x)))
+                                .collect::<std::vec::Vec<_>>();
+                            #retain
+                            let items = if std::option::Option::Some(context.current_tick()) == *last_tick {
+                                new_items
+                            } else {
+                                *last_tick = std::option::Option::Some(context.current_tick());
+                                pos.iter().cloned().collect()
+                            };
+                            items.into_iter().filter(move |x| !#negset_ident.contains(x))
+                        };
+                    },
+                )
             }
         };
+
+        let write_prologue = quote_spanned! {op_span=>
+            #neg_prologue
+            #pos_prologue
+        };
         Ok(OperatorWriteOutput {
             write_prologue,
             write_iterator,
@@ -0,0 +1,114 @@
+use super::{
+    parse_single_persistence_lifetime, OperatorConstraints, OperatorWriteOutput, Persistence,
+    WriteContextArgs, WriteIteratorArgs, RANGE_0, RANGE_1,
+};
+
+use quote::quote_spanned;
+
+/// > 1 input stream of type `T`, 1 output stream of type `(usize, T)`
+///
+/// Pairs each item with its sequence number, starting at `0`, akin to Rust's built-in `enumerate`.
+///
+/// ```hydroflow
+/// // should print (0, "a"), (1, "b"), (2, "c")
+/// source_iter(vec!["a", "b", "c"])
+///     -> enumerate()
+///     -> for_each(|x| println!("{:?}", x));
+/// ```
+///
+/// `enumerate` can also be provided with one generic lifetime persistence argument, either
+/// `'tick` or `'static`, to specify how data persists. The default is `'tick`. With `'tick` the
+/// count restarts at `0` each tick. With `'static` the counter persists across ticks, so every item
+/// ever emitted gets a unique, increasing sequence number (e.g. for log sequence numbers).
+///
+/// ```rustbook
+/// let (input_send, input_recv) = hydroflow::util::unbounded_channel::<&str>();
+/// let mut flow = hydroflow::hydroflow_syntax! {
+///     source_stream(input_recv)
+///         -> enumerate::<'static>()
+///         -> for_each(|x| println!("{:?}", x));
+/// };
+///
+/// input_send.send("a").unwrap();
+/// input_send.send("b").unwrap();
+/// flow.run_available();
+/// // (0, "a"), (1, "b")
+///
+/// input_send.send("c").unwrap();
+/// flow.run_available();
+/// // (2, "c")
+/// ```
+#[hydroflow_internalmacro::operator_docgen]
+pub const ENUMERATE: OperatorConstraints = OperatorConstraints {
+    name: "enumerate",
+    hard_range_inn: RANGE_1,
+    soft_range_inn: RANGE_1,
+    hard_range_out: RANGE_1,
+    soft_range_out: RANGE_1,
+    ports_inn: None,
+    ports_out: None,
+    num_args: RANGE_0,
+    input_delaytype_fn: &|_| None,
+    write_fn: &(|wc @ &WriteContextArgs { root, op_span, .. },
+                 wi @ &WriteIteratorArgs {
+                     ident,
+                     inputs,
+                     outputs,
+                     is_pull,
+                     ..
+                 },
+                 diagnostics| {
+        let persistence =
+            parse_single_persistence_lifetime(wi, op_span, Persistence::Tick, diagnostics);
+
+        let enumeratedata_ident = wc.make_ident("enumeratedata");
+        let counter_ident = wc.make_ident("counter");
+        let (write_prologue, counter) = match persistence {
+            Persistence::Tick => (
+                quote_spanned! {op_span=>
+                    let #enumeratedata_ident = df.add_state(::std::cell::RefCell::new(
+                        #root::lang::monotonic_map::MonotonicMap::<_, #root::lang::clear::ClearDefault<usize>>::default(),
+                    ));
+                },
+                quote_spanned! {op_span=>
+                    let mut #counter_ident = context.state_ref(#enumeratedata_ident).borrow_mut();
+                    let #counter_ident = &mut #counter_ident.try_insert_with(context.current_tick(), Default::default).0;
+                },
+            ),
+            Persistence::Static => (
+                quote_spanned! {op_span=>
+                    let #enumeratedata_ident = df.add_state(::std::cell::RefCell::<usize>::new(0));
+                },
+                quote_spanned! {op_span=>
+                    let mut #counter_ident = context.state_ref(#enumeratedata_ident).borrow_mut();
+                },
+            ),
+        };
+
+        let map_fn = quote_spanned! {op_span=>
+            move |item| {
+                let index = *#counter_ident;
+                *#counter_ident += 1;
+                (index, item)
+            }
+        };
+        let write_iterator = if is_pull {
+            let input = &inputs[0];
+            quote_spanned! {op_span=>
+                #counter
+                let #ident = #input.map(#map_fn);
+            }
+        } else {
+            let output = &outputs[0];
+            quote_spanned! {op_span=>
+                #counter
+                let #ident = #root::pusherator::map::Map::new(#map_fn, #output);
+            }
+        };
+        Ok(OperatorWriteOutput {
+            write_prologue,
+            write_iterator,
+            ..Default::default()
+        })
+    }),
+};
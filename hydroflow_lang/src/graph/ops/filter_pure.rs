@@ -0,0 +1,28 @@
+use super::{OperatorConstraints, RANGE_1};
+
+use super::filter::FILTER;
+
+/// Same as [`filter`](#filter), but declares that the closure is pure: it has no side effects and
+/// its result depends only on its input. With `#![optimize]`, pure filters may be moved upstream
+/// of `merge()` and `tee()`, and into the inputs of a `join()` when they only use the key or one
+/// side's values, so less data reaches the join's state.
+///
+/// ```hydroflow
+/// my_join = join() -> filter_pure(|(_k, (v1, _v2))| v1.starts_with('w'))
+///     -> for_each(|x| println!("{:?}", x));
+/// source_iter(vec![("hello", "world"), ("stay", "gold")]) -> [0]my_join;
+/// source_iter(vec![("hello", "cleveland"), ("stay", "golden")]) -> [1]my_join;
+/// ```
+#[hydroflow_internalmacro::operator_docgen]
+pub const FILTER_PURE: OperatorConstraints = OperatorConstraints {
+    name: "filter_pure",
+    hard_range_inn: RANGE_1,
+    soft_range_inn: RANGE_1,
+    hard_range_out: RANGE_1,
+    soft_range_out: RANGE_1,
+    ports_inn: None,
+    ports_out: None,
+    num_args: RANGE_1,
+    input_delaytype_fn: &|_| None,
+    write_fn: FILTER.write_fn,
+};
@@ -1,15 +1,19 @@
 use super::{
-    DelayType, OperatorConstraints, OperatorWriteOutput, WriteContextArgs, WriteIteratorArgs,
+    parse_single_persistence_lifetime, write_aggregate, AggregateOutput, DelayType,
+    OperatorConstraints, OperatorWriteOutput, Persistence, WriteContextArgs, WriteIteratorArgs,
     RANGE_1,
 };
 
-use quote::quote_spanned;
+use quote::{quote_spanned, ToTokens};
 
 /// > 1 input stream, 1 output stream
 ///
 /// > Arguments: an initial value, and a closure which itself takes two arguments:
 /// an 'accumulator', and an element. The closure returns the value that the accumulator should have for the next iteration.
 ///
+/// > Alternatively a single argument, an [`Aggregator`](https://hydro-project.github.io/hydroflow/doc/hydroflow/lang/agg/trait.Aggregator.html)
+/// such as `agg::Sum` or a variadic of aggregators such as `var_expr!(agg::Count, agg::Sum)`.
+///
 /// Akin to Rust's built-in fold operator. Folds every element into an accumulator by applying a closure,
 /// returning the final result.
 ///
@@ -22,6 +26,36 @@ use quote::quote_spanned;
 ///     })
 ///     -> for_each(|e| println!("Ressembled vector {:?}", e));
 /// ```
+///
+/// ```hydroflow
+/// // should print `Average 3`
+/// source_iter([1,2,3,4,5])
+///     -> fold(hydroflow::lang::agg::Avg)
+///     -> for_each(|avg: Option<f64>| println!("Average {}", avg.unwrap()));
+/// ```
+///
+/// `fold` can also be provided with one generic lifetime persistence argument, either
+/// `'tick` or `'static`, to specify how data persists. The default is `'tick`. With `'tick` the
+/// accumulator starts from the initial value each tick. With `'static` the accumulator is kept
+/// across ticks and each tick's elements are folded into it, so the accumulator must be `Clone`.
+///
+/// ```rustbook
+/// let (input_send, input_recv) = hydroflow::util::unbounded_channel::<usize>();
+/// let mut flow = hydroflow::hydroflow_syntax! {
+///     source_stream(input_recv)
+///         -> fold::<'static>(0, |accum, elem| accum + elem)
+///         -> for_each(|sum| println!("{}", sum));
+/// };
+///
+/// input_send.send(1).unwrap();
+/// input_send.send(2).unwrap();
+/// flow.run_available();
+/// // 3
+///
+/// input_send.send(3).unwrap();
+/// flow.run_available();
+/// // 6
+/// ```
 #[hydroflow_internalmacro::operator_docgen]
 pub const FOLD: OperatorConstraints = OperatorConstraints {
     name: "fold",
@@ -31,27 +65,68 @@ pub const FOLD: OperatorConstraints = OperatorConstraints {
     soft_range_out: RANGE_1,
     ports_inn: None,
     ports_out: None,
-    num_args: 2,
+    num_args: &(1..=2),
     input_delaytype_fn: &|_| Some(DelayType::Stratum),
-    write_fn: &(|&WriteContextArgs { op_span, .. },
-                 &WriteIteratorArgs {
+    write_fn: &(|wc @ &WriteContextArgs { op_span, .. },
+                 wi @ &WriteIteratorArgs {
                      ident,
                      inputs,
                      arguments,
                      is_pull,
                      ..
                  },
-                 _| {
+                 diagnostics| {
         assert!(is_pull);
+
+        let persistence =
+            parse_single_persistence_lifetime(wi, op_span, Persistence::Tick, diagnostics);
+
+        if 1 == arguments.len() {
+            let aggregator = arguments[0].to_token_stream();
+            return Ok(write_aggregate(
+                wc,
+                wi,
+                persistence,
+                aggregator,
+                AggregateOutput::Single,
+            ));
+        }
+
         let input = &inputs[0];
-        // TODO(mingwei): Issues if initial value is not copy.
-        // TODO(mingwei): Might introduce the initial value multiple times on scheduling.
-        let write_iterator = quote_spanned! {op_span=>
-            let #ident = std::iter::once(#input.fold(#arguments));
-        };
-        Ok(OperatorWriteOutput {
-            write_iterator,
-            ..Default::default()
-        })
+        match persistence {
+            Persistence::Tick => {
+                // TODO(mingwei): Issues if initial value is not copy.
+                // TODO(mingwei): Might introduce the initial value multiple times on scheduling.
+                let write_iterator = quote_spanned! {op_span=>
+                    let #ident = std::iter::once(#input.fold(#arguments));
+                };
+                Ok(OperatorWriteOutput {
+                    write_iterator,
+                    ..Default::default()
+                })
+            }
+            Persistence::Static => {
+                let init = &arguments[0];
+                let func = &arguments[1];
+                let folddata_ident = wc.make_ident("folddata");
+
+                let write_prologue = quote_spanned! {op_span=>
+                    let #folddata_ident = df.add_state(::std::cell::RefCell::new(::std::option::Option::Some(#init)));
+                };
+                let write_iterator = quote_spanned! {op_span=>
+                    let #ident = {
+                        let mut accum = context.state_ref(#folddata_ident).borrow_mut();
+                        let folded = #input.fold(accum.take().unwrap(), #func);
+                        *accum = ::std::option::Option::Some(::std::clone::Clone::clone(&folded));
+                        ::std::iter::once(folded)
+                    };
+                };
+                Ok(OperatorWriteOutput {
+                    write_prologue,
+                    write_iterator,
+                    ..Default::default()
+                })
+            }
+        }
     }),
 };
@@ -24,7 +24,7 @@ pub const FOR_EACH: OperatorConstraints = OperatorConstraints {
     soft_range_out: RANGE_0,
     ports_inn: None,
     ports_out: None,
-    num_args: 1,
+    num_args: RANGE_1,
     input_delaytype_fn: &|_| None,
     write_fn: &(|&WriteContextArgs { root, op_span, .. },
                  &WriteIteratorArgs {
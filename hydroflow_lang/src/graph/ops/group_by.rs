@@ -1,9 +1,10 @@
 use super::{
-    parse_generic_types, parse_persistence_lifetimes, DelayType, OperatorConstraints,
-    OperatorWriteOutput, Persistence, WriteContextArgs, WriteIteratorArgs, RANGE_1,
+    parse_generic_types, parse_single_persistence_lifetime, write_aggregate, AggregateOutput,
+    DelayType, OperatorConstraints, OperatorWriteOutput, Persistence, WriteContextArgs,
+    WriteIteratorArgs, RANGE_1,
 };
 
-use quote::quote_spanned;
+use quote::{quote_spanned, ToTokens};
 use syn::spanned::Spanned;
 
 use crate::diagnostic::{Diagnostic, Level};
@@ -15,6 +16,9 @@ use crate::diagnostic::{Diagnostic, Level};
 /// itself takes two arguments: an 'accumulator', and an element. The second closure returns the
 /// value that the accumulator should have for the next iteration.
 ///
+/// > Alternatively a single argument, an [`Aggregator`](https://hydro-project.github.io/hydroflow/doc/hydroflow/lang/agg/trait.Aggregator.html)
+/// such as `agg::Sum` or a variadic of aggregators such as `var_expr!(agg::Count, agg::Max)`.
+///
 /// A special case of `fold`, in the spirit of SQL's GROUP BY and aggregation constructs. The input
 /// is partitioned into groups by the first field, and for each group the values in the second
 /// field are accumulated via the closures in the arguments.
@@ -26,7 +30,8 @@ use crate::diagnostic::{Diagnostic, Level};
 /// defaults to `static.
 ///
 /// `group_by` can also be provided with two type arguments, the key and value type. This is
-/// required when using `'static` persistence if the compiler cannot infer the types.
+/// required when using `'static` persistence if the compiler cannot infer the types. When using an
+/// aggregator the two type arguments are the key and the input value type instead.
 ///
 /// ```hydroflow
 /// source_iter([("toy", 1), ("toy", 2), ("shoe", 11), ("shoe", 35), ("haberdashery", 7)])
@@ -34,6 +39,15 @@ use crate::diagnostic::{Diagnostic, Level};
 ///     -> for_each(|(k, v)| println!("Total for group {} is {}", k, v));
 /// ```
 ///
+/// The standard aggregations in `hydroflow::lang::agg` avoid writing the closures by hand:
+/// ```hydroflow
+/// source_iter([("toy", 1), ("toy", 2), ("shoe", 11), ("shoe", 35), ("haberdashery", 7)])
+///     -> group_by(hydroflow::var_expr!(hydroflow::lang::agg::Count, hydroflow::lang::agg::Max))
+///     -> for_each(|(k, hydroflow::var_args!(count, max))| {
+///         println!("Group {} has {} items, the largest is {:?}", k, count, max)
+///     });
+/// ```
+///
 /// Example using `'tick` persistence:
 /// ```rustbook
 /// let (input_send, input_recv) = hydroflow::util::unbounded_channel::<(&str, &str)>();
@@ -65,7 +79,7 @@ pub const GROUP_BY: OperatorConstraints = OperatorConstraints {
     soft_range_out: RANGE_1,
     ports_inn: None,
     ports_out: None,
-    num_args: 2,
+    num_args: &(1..=2),
     input_delaytype_fn: &|_| Some(DelayType::Stratum),
     write_fn: &(|wc @ &WriteContextArgs { op_span, .. },
                  wi @ &WriteIteratorArgs {
@@ -82,22 +96,8 @@ pub const GROUP_BY: OperatorConstraints = OperatorConstraints {
 
         let generics_span = generic_args.map(Spanned::span).unwrap_or(op_span);
 
-        let persistence = parse_persistence_lifetimes(wi, diagnostics);
-        let persistence = match *persistence {
-            [] => Persistence::Static,
-            [a] => a,
-            _ => {
-                diagnostics.push(Diagnostic::spanned(
-                    generics_span,
-                    Level::Error,
-                    format!(
-                        "Operator `{}` expects zero or one persistence lifetime generic arguments",
-                        op_name
-                    ),
-                ));
-                Persistence::Static
-            }
-        };
+        let persistence =
+            parse_single_persistence_lifetime(wi, op_span, Persistence::Static, diagnostics);
 
         let mut generic_type_args = parse_generic_types(wi);
         if !generic_type_args.is_empty() && 2 != generic_type_args.len() {
@@ -109,6 +109,17 @@ pub const GROUP_BY: OperatorConstraints = OperatorConstraints {
             generic_type_args.clear();
         }
 
+        if 1 == arguments.len() {
+            let aggregator = arguments[0].to_token_stream();
+            return Ok(write_aggregate(
+                wc,
+                wi,
+                persistence,
+                aggregator,
+                AggregateOutput::Grouped(&generic_type_args),
+            ));
+        }
+
         let input = &inputs[0];
         let initfn = &arguments[0];
         let aggfn = &arguments[1];
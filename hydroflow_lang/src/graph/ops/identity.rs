@@ -1,4 +1,13 @@
-use super::{OperatorConstraints, IDENTITY_WRITE_FN, RANGE_1};
+use super::{
+    identity_write_iterator_fn, OperatorConstraints, OperatorWriteOutput, WriteContextArgs,
+    WriteIteratorArgs, RANGE_0, RANGE_1,
+};
+
+use crate::diagnostic::{Diagnostic, Level};
+
+use quote::quote_spanned;
+use syn::spanned::Spanned;
+use syn::GenericArgument;
 
 /// > 1 input stream of type T, 1 output stream of type T
 ///
@@ -9,6 +18,17 @@ use super::{OperatorConstraints, IDENTITY_WRITE_FN, RANGE_1};
 /// source_iter(vec!["hello", "world"]) -> identity()
 ///     -> for_each(|x| println!("{}", x));
 /// ```
+///
+/// A type argument checks that the items have that type, so type errors are reported at that
+/// point instead of deep inside the generated code. An edge type annotation, `-> :Type ->`, is
+/// shorthand for this.
+///
+/// ```hydroflow
+/// source_iter(vec![("hello", 5)]) -> identity::<(&str, usize)>()
+///     -> map(|(s, n)| &s[..n]) -> for_each(|s| println!("{}", s));
+/// source_iter(vec![("world", 5)]) -> :(&str, usize)
+///     -> map(|(s, n)| &s[..n]) -> for_each(|s| println!("{}", s));
+/// ```
 #[hydroflow_internalmacro::operator_docgen]
 pub const IDENTITY: OperatorConstraints = OperatorConstraints {
     name: "identity",
@@ -18,7 +38,63 @@ pub const IDENTITY: OperatorConstraints = OperatorConstraints {
     soft_range_out: RANGE_1,
     ports_inn: None,
     ports_out: None,
-    num_args: 0,
+    num_args: RANGE_0,
     input_delaytype_fn: &|_| None,
-    write_fn: IDENTITY_WRITE_FN,
+    write_fn: &(|wc @ &WriteContextArgs { root, .. },
+                 wi @ &WriteIteratorArgs {
+                     ident,
+                     inputs,
+                     outputs,
+                     is_pull,
+                     generic_args,
+                     ..
+                 },
+                 diagnostics| {
+        let ty = match generic_args
+            .map(|args| args.iter().collect::<Vec<_>>())
+            .as_deref()
+        {
+            None => None,
+            Some(&[GenericArgument::Type(ty)]) => Some(ty),
+            Some(_) => {
+                diagnostics.push(Diagnostic::spanned(
+                    generic_args.span(),
+                    Level::Error,
+                    "`identity` takes a single type argument, the type of its items.",
+                ));
+                return Err(());
+            }
+        };
+        let write_iterator = if let Some(ty) = ty {
+            // Spanned at the type, so type errors point at it. The type is passed as a generic
+            // argument so elided lifetimes (e.g. `&str`) are inferred.
+            let ty_span = ty.span();
+            let (input, bound) = if is_pull {
+                (
+                    &inputs[0],
+                    quote_spanned! {ty_span=> ::std::iter::Iterator<Item = Item> },
+                )
+            } else {
+                (
+                    &outputs[0],
+                    quote_spanned! {ty_span=> #root::pusherator::Pusherator<Item = Item> },
+                )
+            };
+            quote_spanned! {ty_span=>
+                let #ident = {
+                    #[inline(always)]
+                    fn check_type<Item, Input: #bound>(input: Input) -> Input {
+                        input
+                    }
+                    check_type::<#ty, _>(#input)
+                };
+            }
+        } else {
+            identity_write_iterator_fn(wc, wi)
+        };
+        Ok(OperatorWriteOutput {
+            write_iterator,
+            ..Default::default()
+        })
+    }),
 };
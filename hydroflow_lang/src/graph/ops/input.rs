@@ -0,0 +1,90 @@
+use crate::diagnostic::{Diagnostic, Level};
+
+use super::{
+    parse_generic_types, OperatorConstraints, OperatorWriteOutput, WriteContextArgs,
+    WriteIteratorArgs, RANGE_0, RANGE_1,
+};
+
+use quote::quote_spanned;
+use syn::spanned::Spanned;
+
+/// > 0 input streams, 1 output stream
+///
+/// > Arguments: None, optionally the item type `T` as a type argument
+///
+/// An external input port for the graph. It must be assigned to a name, for example
+/// `inp = input::<u32>()`. Instead of a `Hydroflow` instance, `hydroflow_syntax!` then returns a
+/// struct with the graph as field `df` plus a field named after each port. For `input` that field
+/// is an [`UnboundedSender<T>`](crate::tokio::sync::mpsc::UnboundedSender) whose items the
+/// operator emits downstream.
+///
+/// ```rustbook
+/// let mut flow = hydroflow::hydroflow_syntax! {
+///     inp = input::<(u32, String)>();
+///     out = output();
+///     inp -> map(|(n, s)| format!("{}: {}", n, s)) -> out;
+/// };
+/// flow.inp.send((1, "hello".to_owned())).unwrap();
+/// flow.df.run_available();
+/// let out: Vec<String> = hydroflow::util::collect_ready(&mut flow.out);
+/// assert_eq!(&["1: hello"], &*out);
+/// ```
+#[hydroflow_internalmacro::operator_docgen]
+pub const INPUT: OperatorConstraints = OperatorConstraints {
+    name: "input",
+    hard_range_inn: RANGE_0,
+    soft_range_inn: RANGE_0,
+    hard_range_out: RANGE_1,
+    soft_range_out: RANGE_1,
+    ports_inn: None,
+    ports_out: None,
+    num_args: RANGE_0,
+    input_delaytype_fn: &|_| None,
+    write_fn: &(|wc @ &WriteContextArgs { root, op_span, .. },
+                 wi @ &WriteIteratorArgs {
+                     ident,
+                     generic_args,
+                     op_name,
+                     ..
+                 },
+                 diagnostics| {
+        let generic_type_args = parse_generic_types(wi);
+        let item_type = match &*generic_type_args {
+            [] => None,
+            [item_type] => Some(item_type),
+            _ => {
+                diagnostics.push(Diagnostic::spanned(
+                    generic_args.map(Spanned::span).unwrap_or(op_span),
+                    Level::Error,
+                    format!("Operator `{}` expects zero or one type arguments", op_name),
+                ));
+                return Err(());
+            }
+        };
+        let turbofish = item_type.map(|item_type| quote_spanned!(op_span=> ::<#item_type>));
+
+        let send_ident = wc.make_ident("send");
+        let recv_ident = wc.make_ident("recv");
+        let write_prologue = quote_spanned! {op_span=>
+            let (#send_ident, mut #recv_ident) = #root::util::unbounded_channel #turbofish ();
+        };
+        let write_iterator = quote_spanned! {op_span=>
+            let #ident = std::iter::from_fn(|| {
+                // Backpressure: stop polling while downstream is full or busy.
+                if context.is_blocked() {
+                    return None;
+                }
+                match #root::futures::stream::Stream::poll_next(std::pin::Pin::new(&mut #recv_ident), &mut std::task::Context::from_waker(&context.waker())) {
+                    std::task::Poll::Ready(maybe) => maybe,
+                    std::task::Poll::Pending => None,
+                }
+            });
+        };
+        Ok(OperatorWriteOutput {
+            write_prologue,
+            write_iterator,
+            external_handle: Some(quote_spanned!(op_span=> #send_ident)),
+            ..Default::default()
+        })
+    }),
+};
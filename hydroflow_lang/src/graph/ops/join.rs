@@ -2,7 +2,7 @@ use crate::diagnostic::{Diagnostic, Level};
 
 use super::{
     parse_persistence_lifetimes, OperatorConstraints, OperatorWriteOutput, Persistence,
-    WriteContextArgs, WriteIteratorArgs, RANGE_1,
+    WriteContextArgs, WriteIteratorArgs, RANGE_0, RANGE_1,
 };
 
 use quote::quote_spanned;
@@ -88,7 +88,7 @@ pub const JOIN: OperatorConstraints = OperatorConstraints {
     soft_range_out: RANGE_1,
     ports_inn: Some(&(|| super::PortListSpec::Fixed(parse_quote! { 0, 1 }))),
     ports_out: None,
-    num_args: 0,
+    num_args: RANGE_0,
     input_delaytype_fn: &|_| None,
     write_fn: &(|wc @ &WriteContextArgs { root, op_span, .. },
                  wi @ &WriteIteratorArgs {
@@ -0,0 +1,127 @@
+use super::{
+    parse_single_persistence_lifetime, OperatorConstraints, OperatorWriteOutput, Persistence,
+    WriteContextArgs, WriteIteratorArgs, RANGE_1,
+};
+
+use quote::quote_spanned;
+
+/// > 1 input stream, 1 output stream
+///
+/// > Arguments: the maximum number of items `n` to emit, a `usize`.
+///
+/// Passes through the first `n` items and drops the rest.
+///
+/// ```hydroflow
+/// // should print 1, 2, 3
+/// source_iter(1..=10)
+///     -> limit(3)
+///     -> for_each(|x| println!("{}", x));
+/// ```
+///
+/// `limit` can also be provided with one generic lifetime persistence argument, either
+/// `'tick` or `'static`, to specify how data persists. The default is `'tick`. With `'tick` the
+/// first `n` items of each tick are emitted. With `'static` only the first `n` items ever are
+/// emitted, after which all items are dropped.
+///
+/// ```rustbook
+/// let (input_send, input_recv) = hydroflow::util::unbounded_channel::<usize>();
+/// let mut flow = hydroflow::hydroflow_syntax! {
+///     source_stream(input_recv)
+///         -> limit::<'static>(3)
+///         -> for_each(|n| println!("{}", n));
+/// };
+///
+/// input_send.send(1).unwrap();
+/// input_send.send(2).unwrap();
+/// flow.run_available();
+/// // 1, 2
+///
+/// input_send.send(3).unwrap();
+/// input_send.send(4).unwrap();
+/// flow.run_available();
+/// // 3
+/// ```
+#[hydroflow_internalmacro::operator_docgen]
+pub const LIMIT: OperatorConstraints = OperatorConstraints {
+    name: "limit",
+    hard_range_inn: RANGE_1,
+    soft_range_inn: RANGE_1,
+    hard_range_out: RANGE_1,
+    soft_range_out: RANGE_1,
+    ports_inn: None,
+    ports_out: None,
+    num_args: RANGE_1,
+    input_delaytype_fn: &|_| None,
+    write_fn: &(|wc @ &WriteContextArgs { root, op_span, .. },
+                 wi @ &WriteIteratorArgs {
+                     ident,
+                     inputs,
+                     outputs,
+                     is_pull,
+                     arguments,
+                     ..
+                 },
+                 diagnostics| {
+        let persistence =
+            parse_single_persistence_lifetime(wi, op_span, Persistence::Tick, diagnostics);
+
+        let limit = &arguments[0];
+        let limit_ident = wc.make_ident("limit");
+        let limitdata_ident = wc.make_ident("limitdata");
+        let count_ident = wc.make_ident("count");
+        // The number of items emitted so far, within the current tick or ever.
+        let (write_prologue, count) = match persistence {
+            Persistence::Tick => (
+                quote_spanned! {op_span=>
+                    let #limitdata_ident = df.add_state(::std::cell::RefCell::new(
+                        #root::lang::monotonic_map::MonotonicMap::<_, #root::lang::clear::ClearDefault<usize>>::default(),
+                    ));
+                },
+                quote_spanned! {op_span=>
+                    let mut #count_ident = context.state_ref(#limitdata_ident).borrow_mut();
+                    let #count_ident = &mut #count_ident.try_insert_with(context.current_tick(), Default::default).0;
+                },
+            ),
+            Persistence::Static => (
+                quote_spanned! {op_span=>
+                    let #limitdata_ident = df.add_state(::std::cell::RefCell::<usize>::new(0));
+                },
+                quote_spanned! {op_span=>
+                    let mut #count_ident = context.state_ref(#limitdata_ident).borrow_mut();
+                },
+            ),
+        };
+
+        // Consumes (and drops) the items past the limit rather than leaving them in the input.
+        let filter_fn = quote_spanned! {op_span=>
+            move |_: &_| {
+                if *#count_ident < #limit_ident {
+                    *#count_ident += 1;
+                    true
+                } else {
+                    false
+                }
+            }
+        };
+        let write_iterator = if is_pull {
+            let input = &inputs[0];
+            quote_spanned! {op_span=>
+                let #limit_ident: usize = #limit;
+                #count
+                let #ident = #input.filter(#filter_fn);
+            }
+        } else {
+            let output = &outputs[0];
+            quote_spanned! {op_span=>
+                let #limit_ident: usize = #limit;
+                #count
+                let #ident = #root::pusherator::filter::Filter::new(#filter_fn, #output);
+            }
+        };
+        Ok(OperatorWriteOutput {
+            write_prologue,
+            write_iterator,
+            ..Default::default()
+        })
+    }),
+};
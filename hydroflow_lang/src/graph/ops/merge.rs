@@ -1,6 +1,6 @@
 use super::{
-    OperatorConstraints, OperatorWriteOutput, WriteContextArgs, WriteIteratorArgs, RANGE_1,
-    RANGE_ANY,
+    OperatorConstraints, OperatorWriteOutput, WriteContextArgs, WriteIteratorArgs, RANGE_0,
+    RANGE_1, RANGE_ANY,
 };
 
 use quote::{quote_spanned, ToTokens};
@@ -29,7 +29,7 @@ pub const MERGE: OperatorConstraints = OperatorConstraints {
     soft_range_out: RANGE_1,
     ports_inn: None,
     ports_out: None,
-    num_args: 0,
+    num_args: RANGE_0,
     input_delaytype_fn: &|_| None,
     write_fn: &(|&WriteContextArgs { op_span, .. },
                  &WriteIteratorArgs {
@@ -13,26 +13,42 @@ use crate::parse::PortIndex;
 
 use super::{GraphNodeId, GraphSubgraphId, PortIndexValue};
 
+mod approx_distinct;
+mod approx_quantile;
+mod approx_top_k;
+mod buffer_until;
+mod consolidate;
 mod cross_join;
 mod demux;
 mod dest_sink;
 mod dest_sink_serde;
+mod diff_difference;
+mod diff_group_by;
+mod diff_join;
+mod diff_unique;
 mod difference;
+mod enumerate;
 mod filter;
 mod filter_map;
+mod filter_pure;
 mod flat_map;
 mod flatten;
 mod fold;
 mod for_each;
 mod group_by;
 mod identity;
+mod input;
 mod inspect;
 mod join;
+mod limit;
 mod map;
 mod merge;
 mod next_stratum;
 mod next_tick;
 mod null;
+mod output;
+mod persist_keyed;
+mod priority;
 mod reduce;
 mod repeat_iter;
 mod sort;
@@ -42,8 +58,12 @@ mod source_stdin;
 mod source_stream;
 mod source_stream_serde;
 mod tee;
+mod top_k;
+mod top_k_by_group;
 mod unique;
 mod unzip;
+mod zip;
+mod zip_longest;
 
 #[derive(Clone, Copy, PartialOrd, Ord, PartialEq, Eq, Debug)]
 pub enum DelayType {
@@ -60,7 +80,6 @@ pub struct OperatorConstraints {
     /// Operator's name.
     pub name: &'static str,
 
-    // TODO: generic argument ranges.
     /// Input argument range required to not show an error.
     pub hard_range_inn: &'static dyn RangeTrait<usize>,
     /// Input argument range required to not show a warning.
@@ -69,8 +88,8 @@ pub struct OperatorConstraints {
     pub hard_range_out: &'static dyn RangeTrait<usize>,
     /// Output argument range required to not show an warning.
     pub soft_range_out: &'static dyn RangeTrait<usize>,
-    /// Number of arguments i.e. `operator(a, b, c)` has `num_args = 3`.
-    pub num_args: usize,
+    /// Range of the number of arguments, i.e. `operator(a, b, c)` has 3 arguments.
+    pub num_args: &'static dyn RangeTrait<usize>,
 
     /// What named or numbered input ports to expect?
     pub ports_inn: Option<&'static dyn Fn() -> PortListSpec>,
@@ -84,6 +103,14 @@ pub struct OperatorConstraints {
     pub write_fn: WriteFn,
 }
 
+impl std::fmt::Debug for OperatorConstraints {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("OperatorConstraints")
+            .field("name", &self.name)
+            .finish_non_exhaustive()
+    }
+}
+
 pub type WriteFn = &'static dyn Fn(
     &WriteContextArgs<'_>,
     &WriteIteratorArgs<'_>,
@@ -105,6 +132,13 @@ pub struct OperatorWriteOutput {
     pub write_iterator: TokenStream,
     /// Code which runs after iterators have been run. Mainly for flushing IO.
     pub write_iterator_after: TokenStream,
+    /// Expression for the scheduling priority of the containing subgraph, if this operator sets
+    /// one. The highest priority in a subgraph is used.
+    pub subgraph_priority: Option<TokenStream>,
+    /// Expression for the external handle of a boundary port operator, such as `input` or
+    /// `output`. The handle becomes a field, named after the variable the operator is assigned
+    /// to, of the struct returned by `hydroflow_syntax!`.
+    pub external_handle: Option<TokenStream>,
 }
 
 pub const RANGE_ANY: &'static dyn RangeTrait<usize> = &(0..);
@@ -148,36 +182,71 @@ pub const OPERATORS: &[OperatorConstraints] = &[
     null::NULL,
     merge::MERGE,
     join::JOIN,
+    buffer_until::BUFFER_UNTIL,
     cross_join::CROSS_JOIN,
     tee::TEE,
     unzip::UNZIP,
+    zip::ZIP,
+    zip_longest::ZIP_LONGEST,
     identity::IDENTITY,
+    priority::PRIORITY,
     map::MAP,
     inspect::INSPECT,
+    enumerate::ENUMERATE,
     flat_map::FLAT_MAP,
     flatten::FLATTEN,
     filter_map::FILTER_MAP,
     filter::FILTER,
+    filter_pure::FILTER_PURE,
     fold::FOLD,
     reduce::REDUCE,
     group_by::GROUP_BY,
     unique::UNIQUE,
     sort::SORT,
     sort_by::SORT_BY,
+    top_k::TOP_K,
+    top_k_by_group::TOP_K_BY_GROUP,
+    limit::LIMIT,
+    approx_distinct::APPROX_DISTINCT,
+    approx_top_k::APPROX_TOP_K,
+    approx_quantile::APPROX_QUANTILE,
+    input::INPUT,
     source_iter::SOURCE_ITER,
     source_stdin::SOURCE_STDIN,
     source_stream::SOURCE_STREAM,
     source_stream_serde::SOURCE_STREAM_SERDE,
     repeat_iter::REPEAT_ITER,
     difference::DIFFERENCE,
+    persist_keyed::PERSIST_KEYED,
+    diff_join::DIFF_JOIN,
+    diff_group_by::DIFF_GROUP_BY,
+    diff_unique::DIFF_UNIQUE,
+    diff_difference::DIFF_DIFFERENCE,
+    consolidate::CONSOLIDATE,
     next_stratum::NEXT_STRATUM,
     next_tick::NEXT_TICK,
+    output::OUTPUT,
     for_each::FOR_EACH,
     demux::DEMUX,
     dest_sink::DEST_SINK,
     dest_sink_serde::DEST_SINK_SERDE,
 ];
 
+/// Finds an operator by name, in the built-in [`OPERATORS`] and then in `custom_operators`.
+///
+/// Custom operators can be defined by other crates, see [`crate::hydroflow_syntax_with_operators`].
+/// They must not have the same name as a built-in operator, [`FlatGraph`](super::flat_graph::FlatGraph)
+/// reports an error if they do.
+pub fn find_operator(
+    name: &str,
+    custom_operators: &'static [OperatorConstraints],
+) -> Option<&'static OperatorConstraints> {
+    OPERATORS
+        .iter()
+        .chain(custom_operators)
+        .find(|op| name == op.name)
+}
+
 pub struct WriteContextArgs<'a> {
     /// `hydroflow` crate name for `use #root::something`.
     pub root: &'a TokenStream,
@@ -342,3 +411,145 @@ pub fn parse_persistence_lifetimes(
         Vec::new()
     }
 }
+
+/// Parses zero or one persistence lifetime generic arguments, for operators with a single input
+/// (or a single persisted state). Returns `default` if there are none.
+pub fn parse_single_persistence_lifetime(
+    wi @ &WriteIteratorArgs {
+        generic_args,
+        op_name,
+        ..
+    }: &WriteIteratorArgs,
+    op_span: Span,
+    default: Persistence,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> Persistence {
+    match *parse_persistence_lifetimes(wi, diagnostics) {
+        [] => default,
+        [a] => a,
+        _ => {
+            diagnostics.push(Diagnostic::spanned(
+                generic_args.map(Spanned::span).unwrap_or(op_span),
+                Level::Error,
+                format!(
+                    "Operator `{}` expects zero or one persistence lifetime generic arguments",
+                    op_name
+                ),
+            ));
+            default
+        }
+    }
+}
+
+/// How [`write_aggregate`] outputs the aggregate.
+#[derive(Clone, Copy, Debug)]
+pub enum AggregateOutput<'a> {
+    /// The aggregate is the one output item.
+    Single,
+    /// The items of the aggregate are output.
+    Items,
+    /// The input is `(key, item)` pairs which are aggregated by key, and each `(key, aggregate)`
+    /// is output. Holds the key and item type arguments, if any.
+    Grouped(&'a [&'a Type]),
+}
+
+/// Helper: writes an operator which aggregates its input with `aggregator`, an expression of a
+/// `hydroflow::lang::agg::Aggregator`, within each tick or across ticks depending on
+/// `persistence`.
+pub fn write_aggregate(
+    wc @ &WriteContextArgs { root, op_span, .. }: &WriteContextArgs,
+    &WriteIteratorArgs { ident, inputs, .. }: &WriteIteratorArgs,
+    persistence: Persistence,
+    aggregator: TokenStream,
+    output: AggregateOutput,
+) -> OperatorWriteOutput {
+    let input = &inputs[0];
+    let (fold_fn, fold_into_fn, accum, input) = match output {
+        AggregateOutput::Single | AggregateOutput::Items => (
+            quote_spanned! {op_span=> fold },
+            quote_spanned! {op_span=> fold_into },
+            quote_spanned! {op_span=> ::std::option::Option::None },
+            quote_spanned! {op_span=> #input },
+        ),
+        AggregateOutput::Grouped(type_args) => {
+            let check_input_generics = if type_args.is_empty() {
+                Default::default()
+            } else {
+                quote_spanned! {op_span=> ::<_, #( #type_args ),*> }
+            };
+            (
+                quote_spanned! {op_span=> group_by },
+                quote_spanned! {op_span=> group_by_into },
+                quote_spanned! {op_span=> ::std::collections::HashMap::new() },
+                quote_spanned! {op_span=>
+                    {
+                        #[inline(always)]
+                        fn check_input<Iter: ::std::iter::Iterator<Item = (A, B)>, A, B>(iter: Iter) -> impl ::std::iter::Iterator<Item = (A, B)> { iter }
+                        check_input #check_input_generics (#input)
+                    }
+                },
+            )
+        }
+    };
+    let (write_prologue, aggregate) = match persistence {
+        Persistence::Tick => (
+            Default::default(),
+            quote_spanned! {op_span=>
+                #root::lang::agg::#fold_fn(&#aggregator, #input)
+            },
+        ),
+        Persistence::Static => {
+            let aggdata_ident = wc.make_ident("aggdata");
+            (
+                quote_spanned! {op_span=>
+                    let #aggdata_ident = df.add_state(::std::cell::RefCell::new((#aggregator, #accum)));
+                },
+                quote_spanned! {op_span=>
+                    {
+                        let mut state = context.state_ref(#aggdata_ident).borrow_mut();
+                        let (agg, accum) = &mut *state;
+                        #root::lang::agg::#fold_into_fn(agg, accum, #input)
+                    }
+                },
+            )
+        }
+    };
+    let write_iterator = match output {
+        AggregateOutput::Single => quote_spanned! {op_span=>
+            let #ident = ::std::iter::once(#aggregate);
+        },
+        AggregateOutput::Items | AggregateOutput::Grouped(_) => quote_spanned! {op_span=>
+            let #ident = #aggregate.into_iter();
+        },
+    };
+    OperatorWriteOutput {
+        write_prologue,
+        write_iterator,
+        ..Default::default()
+    }
+}
+
+/// Helper: for stream sources with an optional second argument, the maximum number of items to
+/// emit per run. Returns the code to initialize the count, before the iterator, and the code to
+/// check it before each item, which reschedules the subgraph behind other scheduled work once the
+/// maximum is reached. Both are empty without a maximum.
+pub fn write_max_items_per_run(
+    wc @ &WriteContextArgs { op_span, .. }: &WriteContextArgs,
+    &WriteIteratorArgs { arguments, .. }: &WriteIteratorArgs,
+) -> (TokenStream, TokenStream) {
+    let Some(max_items) = arguments.iter().nth(1) else {
+        return Default::default();
+    };
+    let count_ident = wc.make_ident("count");
+    let count_init = quote_spanned! {op_span=>
+        let mut #count_ident = 0_usize;
+    };
+    let count_check = quote_spanned! {op_span=>
+        if #max_items <= #count_ident {
+            context.schedule_subgraph(context.current_subgraph());
+            return None;
+        }
+        #count_ident += 1;
+    };
+    (count_init, count_check)
+}
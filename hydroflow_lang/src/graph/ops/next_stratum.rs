@@ -1,4 +1,4 @@
-use super::{DelayType, OperatorConstraints, IDENTITY_WRITE_FN, RANGE_1};
+use super::{DelayType, OperatorConstraints, IDENTITY_WRITE_FN, RANGE_0, RANGE_1};
 
 /// Delays all elements which pass through to the next stratum (in the same
 /// tick).
@@ -11,7 +11,7 @@ pub const NEXT_STRATUM: OperatorConstraints = OperatorConstraints {
     soft_range_out: RANGE_1,
     ports_inn: None,
     ports_out: None,
-    num_args: 0,
+    num_args: RANGE_0,
     input_delaytype_fn: &|_| Some(DelayType::Stratum),
     write_fn: IDENTITY_WRITE_FN,
 };
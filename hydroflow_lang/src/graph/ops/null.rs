@@ -1,4 +1,6 @@
-use super::{OperatorConstraints, OperatorWriteOutput, WriteContextArgs, WriteIteratorArgs};
+use super::{
+    OperatorConstraints, OperatorWriteOutput, WriteContextArgs, WriteIteratorArgs, RANGE_0,
+};
 
 use quote::quote_spanned;
 
@@ -25,7 +27,7 @@ pub const NULL: OperatorConstraints = OperatorConstraints {
     soft_range_out: &(0..=1),
     ports_inn: None,
     ports_out: None,
-    num_args: 0,
+    num_args: RANGE_0,
     input_delaytype_fn: &|_| None,
     write_fn: &(|&WriteContextArgs { root, op_span, .. },
                  &WriteIteratorArgs {
@@ -0,0 +1,57 @@
+use super::{
+    OperatorConstraints, OperatorWriteOutput, WriteContextArgs, WriteIteratorArgs, RANGE_0, RANGE_1,
+};
+
+use quote::quote_spanned;
+
+/// > 1 input stream of type T, 0 output streams
+///
+/// > Arguments: None
+///
+/// An external output port for the graph. Like [`input`](#input), it must be assigned to a name,
+/// e.g. `out = output()`, and `hydroflow_syntax!` then returns a struct with a field of that name.
+/// For `output` the field is an [`UnboundedReceiverStream<T>`](crate::tokio_stream::wrappers::UnboundedReceiverStream)
+/// of the items received. Items are dropped if the receiver has been dropped.
+///
+/// ```rustbook
+/// let mut flow = hydroflow::hydroflow_syntax! {
+///     evens = output();
+///     source_iter(0..6) -> filter(|x| x % 2 == 0) -> evens;
+/// };
+/// flow.df.run_available();
+/// let evens: Vec<_> = hydroflow::util::collect_ready(&mut flow.evens);
+/// assert_eq!(&[0, 2, 4], &*evens);
+/// ```
+#[hydroflow_internalmacro::operator_docgen]
+pub const OUTPUT: OperatorConstraints = OperatorConstraints {
+    name: "output",
+    hard_range_inn: RANGE_1,
+    soft_range_inn: RANGE_1,
+    hard_range_out: RANGE_0,
+    soft_range_out: RANGE_0,
+    ports_inn: None,
+    ports_out: None,
+    num_args: RANGE_0,
+    input_delaytype_fn: &|_| None,
+    write_fn: &(|wc @ &WriteContextArgs { root, op_span, .. },
+                 &WriteIteratorArgs { ident, .. },
+                 _| {
+        let send_ident = wc.make_ident("send");
+        let recv_ident = wc.make_ident("recv");
+        let write_prologue = quote_spanned! {op_span=>
+            let (#send_ident, #recv_ident) = #root::util::unbounded_channel();
+        };
+        let write_iterator = quote_spanned! {op_span=>
+            let #ident = #root::pusherator::for_each::ForEach::new(|item| {
+                // Ignore the error if the receiver was dropped.
+                let _ = #send_ident.send(item);
+            });
+        };
+        Ok(OperatorWriteOutput {
+            write_prologue,
+            write_iterator,
+            external_handle: Some(quote_spanned!(op_span=> #recv_ident)),
+            ..Default::default()
+        })
+    }),
+};
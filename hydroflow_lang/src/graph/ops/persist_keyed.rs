@@ -0,0 +1,127 @@
+use crate::diagnostic::{Diagnostic, Level};
+
+use super::{
+    parse_generic_types, DelayType, OperatorConstraints, OperatorWriteOutput, WriteContextArgs,
+    WriteIteratorArgs, RANGE_0,
+};
+
+use quote::quote_spanned;
+use syn::parse_quote;
+use syn::spanned::Spanned;
+
+/// > 2 input streams, `upsert` of type `(K, V)` and `delete` of type `K`, 2 output streams,
+/// > `contents` of type `(K, V)` and `changes` of type `(K, Option<V>)`
+///
+/// Keyed mutable state with overwrite and delete semantics, like Bloom's `<+-` upsert. Each
+/// `(key, value)` pair received on the `upsert` port sets the value of `key`, replacing any previous
+/// value, and each `key` received on the `delete` port removes it. Within a tick deletes are applied
+/// before upserts, so a key both deleted and upserted keeps the upserted value.
+///
+/// Changes become visible at the next tick: both inputs are tick barriers, so the operator runs in
+/// the tick after the changes are received, applies them atomically, and then emits on its outputs:
+/// * `contents`: every current `(key, value)` pair, at most once per tick.
+/// * `changes`: `(key, Some(value))` for each key whose value changed, and `(key, None)` for each
+///   key which was removed.
+///
+/// The operator only runs in ticks after it received input, so in a tick without new input
+/// neither output emits anything.
+///
+/// Both outputs must be connected, use `null()` to discard one. `persist_keyed` can also be
+/// provided with two type arguments, the key and value type.
+///
+/// ```rustbook
+/// let (upsert_send, upsert_recv) = hydroflow::util::unbounded_channel::<(&str, usize)>();
+/// let (delete_send, delete_recv) = hydroflow::util::unbounded_channel::<&str>();
+/// let mut flow = hydroflow::hydroflow_syntax! {
+///     kvs = persist_keyed::<&str, usize>();
+///     source_stream(upsert_recv) -> [upsert]kvs;
+///     source_stream(delete_recv) -> [delete]kvs;
+///     kvs[contents] -> for_each(|kv| println!("contents: {:?}", kv));
+///     kvs[changes] -> for_each(|kv| println!("changes: {:?}", kv));
+/// };
+/// upsert_send.send(("hello", 1)).unwrap();
+/// upsert_send.send(("world", 2)).unwrap();
+/// flow.run_tick();
+/// // nothing yet, changes are visible next tick.
+/// flow.run_tick();
+/// // contents: ("hello", 1), ("world", 2)
+/// // changes: ("hello", Some(1)), ("world", Some(2))
+///
+/// upsert_send.send(("hello", 3)).unwrap();
+/// delete_send.send("world").unwrap();
+/// flow.run_available();
+/// // contents: ("hello", 3)
+/// // changes: ("hello", Some(3)), ("world", None)
+/// ```
+#[hydroflow_internalmacro::operator_docgen]
+pub const PERSIST_KEYED: OperatorConstraints = OperatorConstraints {
+    name: "persist_keyed",
+    hard_range_inn: &(2..=2),
+    soft_range_inn: &(2..=2),
+    hard_range_out: &(2..=2),
+    soft_range_out: &(2..=2),
+    ports_inn: Some(&|| super::PortListSpec::Fixed(parse_quote! { delete, upsert })),
+    ports_out: Some(&|| super::PortListSpec::Fixed(parse_quote! { changes, contents })),
+    num_args: RANGE_0,
+    input_delaytype_fn: &|_| Some(DelayType::Tick),
+    write_fn: &(|wc @ &WriteContextArgs { root, op_span, .. },
+                 wi @ &WriteIteratorArgs {
+                     inputs,
+                     outputs,
+                     is_pull,
+                     generic_args,
+                     op_name,
+                     ..
+                 },
+                 diagnostics| {
+        assert!(!is_pull);
+
+        let generics_span = generic_args.map(Spanned::span).unwrap_or(op_span);
+
+        let mut generic_type_args = parse_generic_types(wi);
+        if !generic_type_args.is_empty() && 2 != generic_type_args.len() {
+            diagnostics.push(Diagnostic::spanned(
+                generics_span,
+                Level::Error,
+                format!("Operator `{}` expects zero or two type arguments", op_name),
+            ));
+            generic_type_args.clear();
+        }
+
+        let persistdata_ident = wc.make_ident("persistdata");
+        let emitteddata_ident = wc.make_ident("emitteddata");
+        let write_prologue = quote_spanned! {op_span=>
+            let #persistdata_ident = df.add_state(::std::cell::RefCell::new(
+                #root::compiled::pull::PersistKeyedState::<#( #generic_type_args ),*>::default()
+            ));
+            // If `contents` was already emitted in the current tick.
+            let #emitteddata_ident = df.add_state(::std::cell::RefCell::new(
+                #root::lang::monotonic_map::MonotonicMap::<_, #root::lang::clear::ClearDefault<bool>>::default(),
+            ));
+        };
+
+        let delete = &inputs[0]; // `delete` before `upsert`.
+        let upsert = &inputs[1];
+        let changes_output = &outputs[0]; // `changes` before `contents`.
+        let contents_output = &outputs[1];
+        let write_iterator = quote_spanned! {op_span=>
+            {
+                let mut state = context.state_ref(#persistdata_ident).borrow_mut();
+                let changes = state.apply(#delete, #upsert);
+                #root::pusherator::pivot::Pivot::new(changes.into_iter(), #changes_output).run();
+
+                let mut emitted = context.state_ref(#emitteddata_ident).borrow_mut();
+                let emitted = &mut emitted.try_insert_with(context.current_tick(), Default::default).0;
+                if !::std::mem::replace(emitted, true) {
+                    #root::pusherator::pivot::Pivot::new(state.contents().into_iter(), #contents_output).run();
+                }
+            }
+        };
+
+        Ok(OperatorWriteOutput {
+            write_prologue,
+            write_iterator,
+            ..Default::default()
+        })
+    }),
+};
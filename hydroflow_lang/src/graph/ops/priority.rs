@@ -0,0 +1,55 @@
+use super::{
+    identity_write_iterator_fn, OperatorConstraints, OperatorWriteOutput, WriteContextArgs,
+    WriteIteratorArgs, RANGE_1,
+};
+
+use quote::quote_spanned;
+
+/// > 1 input stream of type T, 1 output stream of type T
+///
+/// > Arguments: The priority, an `i32`, of the subgraph containing this operator
+///
+/// Passes each item through unchanged, and sets the [`Priority`](crate::scheduled::scheduler::Priority)
+/// of the subgraph it ends up in. Priorities only take effect with a priority-based scheduler,
+/// see [`Hydroflow::set_scheduler`](crate::scheduled::graph::Hydroflow::set_scheduler). Higher
+/// priorities run first, and subgraphs default to priority `0`. If a subgraph contains several
+/// `priority` operators the highest one is used.
+///
+/// ```rustbook
+/// use hydroflow::scheduled::scheduler::PriorityScheduler;
+///
+/// let (requests_send, requests_recv) = hydroflow::util::unbounded_channel::<u32>();
+/// let mut flow = hydroflow::hydroflow_syntax! {
+///     // Serve requests before background work.
+///     source_stream(requests_recv) -> priority(10)
+///         -> for_each(|x| println!("request {}", x));
+///     source_iter(0..3) -> priority(-10) -> next_stratum() -> next_stratum()
+///         -> for_each(|x| println!("background {}", x));
+/// };
+/// flow.set_scheduler::<PriorityScheduler>();
+/// requests_send.send(1).unwrap();
+/// flow.run_available();
+/// ```
+#[hydroflow_internalmacro::operator_docgen]
+pub const PRIORITY: OperatorConstraints = OperatorConstraints {
+    name: "priority",
+    hard_range_inn: RANGE_1,
+    soft_range_inn: RANGE_1,
+    hard_range_out: RANGE_1,
+    soft_range_out: RANGE_1,
+    ports_inn: None,
+    ports_out: None,
+    num_args: RANGE_1,
+    input_delaytype_fn: &|_| None,
+    write_fn: &(|write_context_args @ &WriteContextArgs { op_span, .. },
+                 write_iterator_args @ &WriteIteratorArgs { arguments, .. },
+                 _| {
+        let priority = &arguments[0];
+        let write_iterator = identity_write_iterator_fn(write_context_args, write_iterator_args);
+        Ok(OperatorWriteOutput {
+            write_iterator,
+            subgraph_priority: Some(quote_spanned! {op_span=> #priority }),
+            ..Default::default()
+        })
+    }),
+};
@@ -1,6 +1,6 @@
 use super::{
-    DelayType, OperatorConstraints, OperatorWriteOutput, WriteContextArgs, WriteIteratorArgs,
-    RANGE_1,
+    parse_single_persistence_lifetime, DelayType, OperatorConstraints, OperatorWriteOutput,
+    Persistence, WriteContextArgs, WriteIteratorArgs, RANGE_1,
 };
 
 use quote::quote_spanned;
@@ -22,6 +22,29 @@ use quote::quote_spanned;
 ///     })
 ///     -> for_each(|e| println!("{}", e));
 /// ```
+///
+/// `reduce` can also be provided with one generic lifetime persistence argument, either
+/// `'tick` or `'static`, to specify how data persists. The default is `'tick`. With `'tick` only
+/// the elements within the current tick are reduced. With `'static` the accumulator is kept across
+/// ticks and each tick's elements are reduced into it, so the element type must be `Clone`.
+///
+/// ```rustbook
+/// let (input_send, input_recv) = hydroflow::util::unbounded_channel::<usize>();
+/// let mut flow = hydroflow::hydroflow_syntax! {
+///     source_stream(input_recv)
+///         -> reduce::<'static>(|accum, elem| std::cmp::max(accum, elem))
+///         -> for_each(|max| println!("{}", max));
+/// };
+///
+/// input_send.send(5).unwrap();
+/// input_send.send(2).unwrap();
+/// flow.run_available();
+/// // 5
+///
+/// input_send.send(3).unwrap();
+/// flow.run_available();
+/// // 5
+/// ```
 #[hydroflow_internalmacro::operator_docgen]
 pub const REDUCE: OperatorConstraints = OperatorConstraints {
     name: "reduce",
@@ -31,25 +54,52 @@ pub const REDUCE: OperatorConstraints = OperatorConstraints {
     soft_range_out: RANGE_1,
     ports_inn: None,
     ports_out: None,
-    num_args: 1,
+    num_args: RANGE_1,
     input_delaytype_fn: &|_| Some(DelayType::Stratum),
-    write_fn: &(|&WriteContextArgs { op_span, .. },
-                 &WriteIteratorArgs {
+    write_fn: &(|wc @ &WriteContextArgs { op_span, .. },
+                 wi @ &WriteIteratorArgs {
                      ident,
                      inputs,
                      arguments,
                      is_pull,
                      ..
                  },
-                 _| {
+                 diagnostics| {
         assert!(is_pull);
+
+        let persistence =
+            parse_single_persistence_lifetime(wi, op_span, Persistence::Tick, diagnostics);
+
         let input = &inputs[0];
-        let write_iterator = quote_spanned! {op_span=>
-            let #ident = #input.reduce(#arguments).into_iter();
-        };
-        Ok(OperatorWriteOutput {
-            write_iterator,
-            ..Default::default()
-        })
+        match persistence {
+            Persistence::Tick => {
+                let write_iterator = quote_spanned! {op_span=>
+                    let #ident = #input.reduce(#arguments).into_iter();
+                };
+                Ok(OperatorWriteOutput {
+                    write_iterator,
+                    ..Default::default()
+                })
+            }
+            Persistence::Static => {
+                let reducedata_ident = wc.make_ident("reducedata");
+
+                let write_prologue = quote_spanned! {op_span=>
+                    let #reducedata_ident = df.add_state(::std::cell::RefCell::new(::std::option::Option::None));
+                };
+                let write_iterator = quote_spanned! {op_span=>
+                    let #ident = {
+                        let mut accum = context.state_ref(#reducedata_ident).borrow_mut();
+                        *accum = accum.take().into_iter().chain(#input).reduce(#arguments);
+                        ::std::clone::Clone::clone(&*accum).into_iter()
+                    };
+                };
+                Ok(OperatorWriteOutput {
+                    write_prologue,
+                    write_iterator,
+                    ..Default::default()
+                })
+            }
+        }
     }),
 };
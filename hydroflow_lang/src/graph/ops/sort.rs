@@ -1,12 +1,9 @@
-use crate::diagnostic::{Diagnostic, Level};
-
 use super::{
-    parse_persistence_lifetimes, DelayType, OperatorConstraints, OperatorWriteOutput, Persistence,
-    WriteContextArgs, WriteIteratorArgs, RANGE_1,
+    parse_single_persistence_lifetime, DelayType, OperatorConstraints, OperatorWriteOutput,
+    Persistence, WriteContextArgs, WriteIteratorArgs, RANGE_0, RANGE_1,
 };
 
 use quote::quote_spanned;
-use syn::spanned::Spanned;
 
 /// Takes a stream as input and produces a sorted version of the stream as output.
 ///
@@ -51,38 +48,20 @@ pub const SORT: OperatorConstraints = OperatorConstraints {
     soft_range_out: RANGE_1,
     ports_inn: None,
     ports_out: None,
-    num_args: 0,
+    num_args: RANGE_0,
     input_delaytype_fn: &|_| Some(DelayType::Stratum),
     write_fn: &(|wc @ &WriteContextArgs { op_span, .. },
                  wi @ &WriteIteratorArgs {
                      ident,
                      inputs,
                      is_pull,
-                     generic_args,
-                     op_name,
                      ..
                  },
                  diagnostics| {
         assert!(is_pull);
 
-        let generics_span = generic_args.map(Spanned::span).unwrap_or(op_span);
-
-        let persistence = parse_persistence_lifetimes(wi, diagnostics);
-        let persistence = match *persistence {
-            [] => Persistence::Tick,
-            [a] => a,
-            _ => {
-                diagnostics.push(Diagnostic::spanned(
-                    generics_span,
-                    Level::Error,
-                    format!(
-                        "Operator `{}` expects zero or one persistence lifetime generic arguments",
-                        op_name
-                    ),
-                ));
-                Persistence::Static
-            }
-        };
+        let persistence =
+            parse_single_persistence_lifetime(wi, op_span, Persistence::Tick, diagnostics);
 
         let input = &inputs[0];
         match persistence {
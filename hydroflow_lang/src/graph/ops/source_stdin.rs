@@ -27,7 +27,7 @@ pub const SOURCE_STDIN: OperatorConstraints = OperatorConstraints {
     soft_range_out: RANGE_1,
     ports_inn: None,
     ports_out: None,
-    num_args: 0,
+    num_args: RANGE_0,
     input_delaytype_fn: &|_| None,
     write_fn: &(|wc @ &WriteContextArgs { root, op_span, .. },
                  &WriteIteratorArgs { ident, .. },
@@ -43,6 +43,10 @@ pub const SOURCE_STDIN: OperatorConstraints = OperatorConstraints {
         };
         let write_iterator = quote_spanned! {op_span=>
             let #ident = std::iter::from_fn(|| {
+                // Backpressure: stop polling while downstream is full or busy.
+                if context.is_blocked() {
+                    return None;
+                }
                 match #root::futures::stream::Stream::poll_next(std::pin::Pin::new(&mut #stream_ident), &mut std::task::Context::from_waker(&context.waker())) {
                     std::task::Poll::Ready(maybe) => maybe,
                     std::task::Poll::Pending => None,
@@ -1,17 +1,26 @@
 use super::{
-    OperatorConstraints, OperatorWriteOutput, WriteContextArgs, WriteIteratorArgs, RANGE_0, RANGE_1,
+    write_max_items_per_run, OperatorConstraints, OperatorWriteOutput, WriteContextArgs,
+    WriteIteratorArgs, RANGE_0, RANGE_1,
 };
 
 use quote::quote_spanned;
 
 /// > 0 input streams, 1 output stream
 ///
-/// > Arguments: [`Stream`](https://docs.rs/futures/latest/futures/stream/trait.Stream.html)
+/// > Arguments: [`Stream`](https://docs.rs/futures/latest/futures/stream/trait.Stream.html), and optionally the maximum number of items to
+/// emit per run
 ///
 /// Given a [`Stream`](https://docs.rs/futures/latest/futures/stream/trait.Stream.html)
 /// of (serialized payload, addr) pairs, deserializes the payload and emits each of the
 /// elements it receives downstream.
 ///
+/// Stops polling the stream while the subgraph is blocked by backpressure, i.e. while a
+/// downstream handoff is full or a `dest_sink` is waiting on its `Sink`.
+///
+/// With a maximum, e.g. `source_stream_serde(inbound, 1000)`, the operator emits at most that many
+/// items each time its subgraph runs, then reschedules the subgraph behind any other scheduled
+/// work. A flood of input then can't hold up other subgraphs, or the [`RunBudget`](crate::scheduled::graph::RunBudget).
+///
 /// ```rustbook
 /// async fn serde_in() {
 ///     let addr = hydroflow::util::ipv4_resolve("localhost:9000".into()).unwrap();
@@ -32,20 +41,28 @@ pub const SOURCE_STREAM_SERDE: OperatorConstraints = OperatorConstraints {
     soft_range_out: RANGE_1,
     ports_inn: None,
     ports_out: None,
-    num_args: 1,
+    num_args: &(1..=2),
     input_delaytype_fn: &|_| None,
     write_fn: &(|wc @ &WriteContextArgs { root, op_span, .. },
-                 &WriteIteratorArgs {
+                 wi @ &WriteIteratorArgs {
                      ident, arguments, ..
                  },
                  _| {
         let receiver = &arguments[0];
         let stream_ident = wc.make_ident("stream");
+
+        let (count_init, count_check) = write_max_items_per_run(wc, wi);
         let write_prologue = quote_spanned! {op_span=>
             let mut #stream_ident = Box::pin(#receiver);
         };
         let write_iterator = quote_spanned! {op_span=>
+            #count_init
             let #ident = std::iter::from_fn(|| {
+                // Backpressure: stop polling while downstream is full or busy.
+                if context.is_blocked() {
+                    return None;
+                }
+                #count_check
                 match #root::futures::stream::Stream::poll_next(#stream_ident.as_mut(), &mut std::task::Context::from_waker(&context.waker())) {
                     std::task::Poll::Ready(Some(std::result::Result::Ok((payload, addr)))) => Some((#root::util::deserialize_from_bytes(payload), addr)),
                     std::task::Poll::Ready(Some(Err(_))) => None,
@@ -0,0 +1,111 @@
+use super::{
+    parse_single_persistence_lifetime, DelayType, OperatorConstraints, OperatorWriteOutput,
+    Persistence, WriteContextArgs, WriteIteratorArgs, RANGE_1,
+};
+
+use quote::quote_spanned;
+
+/// > 1 input stream of type T, 1 output stream of type T
+///
+/// > Arguments: the number of items `k` to keep, a `usize`, and a closure which takes a reference
+/// to an item and returns its (`Ord`) ranking key.
+///
+/// Emits the `k` items with the greatest keys, greatest first. Items with equal keys are ranked by
+/// arrival order, earlier items first.
+///
+/// ```hydroflow
+/// // should print ("carol", 30), ("alice", 20)
+/// source_iter(vec![("alice", 20), ("bob", 10), ("carol", 30)])
+///     -> top_k(2, |&(_name, score): &(&str, u32)| score)
+///     -> for_each(|x| println!("{:?}", x));
+/// ```
+///
+/// `top_k` can also be provided with one generic lifetime persistence argument, either
+/// `'tick` or `'static`, to specify how data persists. The default is `'tick`. With `'tick` only
+/// the items within the current tick are ranked. With `'static` the top `k` items are remembered
+/// across ticks and re-emitted (in order) each tick. Only `k` items are ever kept, in a bounded
+/// heap, so unlike `sort::<'static>()` memory does not grow with the input.
+///
+/// ```rustbook
+/// let (input_send, input_recv) = hydroflow::util::unbounded_channel::<(&str, u32)>();
+/// let mut flow = hydroflow::hydroflow_syntax! {
+///     source_stream(input_recv)
+///         -> top_k::<'static>(2, |&(_name, score): &(&str, u32)| score)
+///         -> for_each(|x| println!("{:?}", x));
+/// };
+///
+/// input_send.send(("alice", 20)).unwrap();
+/// input_send.send(("bob", 10)).unwrap();
+/// flow.run_available();
+/// // ("alice", 20), ("bob", 10)
+///
+/// input_send.send(("carol", 30)).unwrap();
+/// flow.run_available();
+/// // ("carol", 30), ("alice", 20)
+/// ```
+#[hydroflow_internalmacro::operator_docgen]
+pub const TOP_K: OperatorConstraints = OperatorConstraints {
+    name: "top_k",
+    hard_range_inn: RANGE_1,
+    soft_range_inn: RANGE_1,
+    hard_range_out: RANGE_1,
+    soft_range_out: RANGE_1,
+    ports_inn: None,
+    ports_out: None,
+    num_args: &(2..=2),
+    input_delaytype_fn: &|_| Some(DelayType::Stratum),
+    write_fn: &(|wc @ &WriteContextArgs { root, op_span, .. },
+                 wi @ &WriteIteratorArgs {
+                     ident,
+                     inputs,
+                     arguments,
+                     is_pull,
+                     ..
+                 },
+                 diagnostics| {
+        assert!(is_pull);
+
+        let persistence =
+            parse_single_persistence_lifetime(wi, op_span, Persistence::Tick, diagnostics);
+
+        let input = &inputs[0];
+        let k = &arguments[0];
+        let key_fn = &arguments[1];
+        match persistence {
+            Persistence::Tick => {
+                let write_iterator = quote_spanned! {op_span=>
+                    let #ident = {
+                        let mut state = #root::compiled::pull::TopKState::new(#k);
+                        state.extend(#input, #key_fn);
+                        state.into_sorted_vec().into_iter()
+                    };
+                };
+                Ok(OperatorWriteOutput {
+                    write_iterator,
+                    ..Default::default()
+                })
+            }
+            Persistence::Static => {
+                let topkdata_ident = wc.make_ident("topkdata");
+
+                let write_prologue = quote_spanned! {op_span=>
+                    let #topkdata_ident = df.add_state(::std::cell::RefCell::new(
+                        #root::compiled::pull::TopKState::new(#k)
+                    ));
+                };
+                let write_iterator = quote_spanned! {op_span=>
+                    let #ident = {
+                        let mut state = context.state_ref(#topkdata_ident).borrow_mut();
+                        state.extend(#input, #key_fn);
+                        state.sorted().into_iter()
+                    };
+                };
+                Ok(OperatorWriteOutput {
+                    write_prologue,
+                    write_iterator,
+                    ..Default::default()
+                })
+            }
+        }
+    }),
+};
@@ -0,0 +1,110 @@
+use super::{
+    parse_single_persistence_lifetime, DelayType, OperatorConstraints, OperatorWriteOutput,
+    Persistence, WriteContextArgs, WriteIteratorArgs, RANGE_1,
+};
+
+use quote::quote_spanned;
+
+/// > 1 input stream of type (G, T), 1 output stream of type (G, T)
+///
+/// > Arguments: the number of items `k` to keep per group, a `usize`, and a closure which takes a
+/// reference to an item's value `T` and returns its (`Ord`) ranking key.
+///
+/// Keyed version of [`top_k`](#top_k). The input is partitioned into groups by the first field,
+/// and the `k` values with the greatest keys are emitted for each group, greatest first within
+/// each group. Items with equal keys are ranked by arrival order, earlier items first.
+///
+/// ```hydroflow
+/// // should print the two latest events of each user
+/// source_iter(vec![("alice", (1, "login")), ("bob", (2, "login")), ("alice", (3, "post")), ("alice", (4, "logout"))])
+///     -> top_k_by_group(2, |&(timestamp, _event): &(u32, &str)| timestamp)
+///     -> for_each(|x| println!("{:?}", x));
+/// ```
+///
+/// `top_k_by_group` can also be provided with one generic lifetime persistence argument, either
+/// `'tick` or `'static`, with the same meaning as for [`top_k`](#top_k). With `'static` only `k`
+/// values are kept for each group.
+///
+/// ```rustbook
+/// let (input_send, input_recv) = hydroflow::util::unbounded_channel::<(&str, (u32, &str))>();
+/// let mut flow = hydroflow::hydroflow_syntax! {
+///     source_stream(input_recv)
+///         -> top_k_by_group::<'static>(1, |&(timestamp, _event): &(u32, &str)| timestamp)
+///         -> for_each(|x| println!("{:?}", x));
+/// };
+///
+/// input_send.send(("alice", (1, "login"))).unwrap();
+/// flow.run_available();
+/// // ("alice", (1, "login"))
+///
+/// input_send.send(("alice", (3, "post"))).unwrap();
+/// input_send.send(("bob", (2, "login"))).unwrap();
+/// flow.run_available();
+/// // ("alice", (3, "post")), ("bob", (2, "login")) (groups in any order)
+/// ```
+#[hydroflow_internalmacro::operator_docgen]
+pub const TOP_K_BY_GROUP: OperatorConstraints = OperatorConstraints {
+    name: "top_k_by_group",
+    hard_range_inn: RANGE_1,
+    soft_range_inn: RANGE_1,
+    hard_range_out: RANGE_1,
+    soft_range_out: RANGE_1,
+    ports_inn: None,
+    ports_out: None,
+    num_args: &(2..=2),
+    input_delaytype_fn: &|_| Some(DelayType::Stratum),
+    write_fn: &(|wc @ &WriteContextArgs { root, op_span, .. },
+                 wi @ &WriteIteratorArgs {
+                     ident,
+                     inputs,
+                     arguments,
+                     is_pull,
+                     ..
+                 },
+                 diagnostics| {
+        assert!(is_pull);
+
+        let persistence =
+            parse_single_persistence_lifetime(wi, op_span, Persistence::Tick, diagnostics);
+
+        let input = &inputs[0];
+        let k = &arguments[0];
+        let key_fn = &arguments[1];
+        match persistence {
+            Persistence::Tick => {
+                let write_iterator = quote_spanned! {op_span=>
+                    let #ident = {
+                        let mut state = #root::compiled::pull::TopKByGroupState::new(#k);
+                        state.extend(#input, #key_fn);
+                        state.into_sorted_vec().into_iter()
+                    };
+                };
+                Ok(OperatorWriteOutput {
+                    write_iterator,
+                    ..Default::default()
+                })
+            }
+            Persistence::Static => {
+                let topkbygroupdata_ident = wc.make_ident("topkbygroupdata");
+
+                let write_prologue = quote_spanned! {op_span=>
+                    let #topkbygroupdata_ident = df.add_state(::std::cell::RefCell::new(
+                        #root::compiled::pull::TopKByGroupState::new(#k)
+                    ));
+                };
+                let write_iterator = quote_spanned! {op_span=>
+                    let #ident = {
+                        let mut state = context.state_ref(#topkbygroupdata_ident).borrow_mut();
+                        state.extend(#input, #key_fn);
+                        state.sorted().into_iter()
+                    };
+                };
+                Ok(OperatorWriteOutput {
+                    write_prologue,
+                    write_iterator,
+                    ..Default::default()
+                })
+            }
+        }
+    }),
+};
@@ -1,5 +1,5 @@
 use super::{
-    OperatorConstraints, OperatorWriteOutput, WriteContextArgs, WriteIteratorArgs, RANGE_1,
+    OperatorConstraints, OperatorWriteOutput, WriteContextArgs, WriteIteratorArgs, RANGE_0, RANGE_1,
 };
 
 use quote::quote_spanned;
@@ -24,7 +24,7 @@ pub const UNZIP: OperatorConstraints = OperatorConstraints {
     soft_range_out: &(2..=2),
     ports_inn: None,
     ports_out: Some(&|| super::PortListSpec::Fixed(parse_quote!(0, 1))),
-    num_args: 0,
+    num_args: RANGE_0,
     input_delaytype_fn: &|_| None,
     write_fn: &(|&WriteContextArgs { root, op_span, .. },
                  &WriteIteratorArgs {
@@ -0,0 +1,93 @@
+use super::{
+    OperatorConstraints, OperatorWriteOutput, WriteContextArgs, WriteIteratorArgs, RANGE_0, RANGE_1,
+};
+
+use quote::quote_spanned;
+use syn::parse_quote;
+
+/// > 2 input streams of type `A` and `B`, 1 output stream of type `(A, B)`
+///
+/// Pairs up the items of the two inputs positionally: the first item of input `0` with the first
+/// item of input `1`, the second with the second, and so on. Items of the longer side that don't
+/// have a partner yet are buffered across ticks until the matching item arrives on the other side,
+/// so each input's order is preserved in the output.
+///
+/// ```hydroflow
+/// // should print (1, "a"), (2, "b")
+/// my_zip = zip();
+/// source_iter(vec![1, 2, 3]) -> [0]my_zip;
+/// source_iter(vec!["a", "b"]) -> [1]my_zip;
+/// my_zip -> for_each(|x| println!("{:?}", x));
+/// ```
+///
+/// ```rustbook
+/// let (request_send, request_recv) = hydroflow::util::unbounded_channel::<&str>();
+/// let (response_send, response_recv) = hydroflow::util::unbounded_channel::<usize>();
+/// let mut flow = hydroflow::hydroflow_syntax! {
+///     my_zip = zip();
+///     source_stream(request_recv) -> [0]my_zip;
+///     source_stream(response_recv) -> [1]my_zip;
+///     my_zip -> for_each(|(req, resp)| println!("{} -> {}", req, resp));
+/// };
+/// request_send.send("first").unwrap();
+/// request_send.send("second").unwrap();
+/// response_send.send(1).unwrap();
+/// flow.run_available();
+/// // first -> 1
+///
+/// response_send.send(2).unwrap();
+/// flow.run_available();
+/// // second -> 2
+/// ```
+#[hydroflow_internalmacro::operator_docgen]
+pub const ZIP: OperatorConstraints = OperatorConstraints {
+    name: "zip",
+    hard_range_inn: &(2..=2),
+    soft_range_inn: &(2..=2),
+    hard_range_out: RANGE_1,
+    soft_range_out: RANGE_1,
+    ports_inn: Some(&(|| super::PortListSpec::Fixed(parse_quote! { 0, 1 }))),
+    ports_out: None,
+    num_args: RANGE_0,
+    input_delaytype_fn: &|_| None,
+    write_fn: &(|wc @ &WriteContextArgs { op_span, .. },
+                 &WriteIteratorArgs {
+                     ident,
+                     inputs,
+                     is_pull,
+                     ..
+                 },
+                 _| {
+        assert!(is_pull);
+
+        let zipdata_ident = wc.make_ident("zipdata");
+        let write_prologue = quote_spanned! {op_span=>
+            let #zipdata_ident = df.add_state(::std::cell::RefCell::new((
+                ::std::collections::VecDeque::new(),
+                ::std::collections::VecDeque::new(),
+            )));
+        };
+
+        let lhs = &inputs[0];
+        let rhs = &inputs[1];
+        let write_iterator = quote_spanned! {op_span=>
+            let #ident = {
+                let mut buffers = context.state_ref(#zipdata_ident).borrow_mut();
+                let (lhs_buf, rhs_buf) = &mut *buffers;
+                lhs_buf.extend(#lhs);
+                rhs_buf.extend(#rhs);
+                let len = ::std::cmp::min(lhs_buf.len(), rhs_buf.len());
+                lhs_buf.drain(..len)
+                    .zip(rhs_buf.drain(..len))
+                    .collect::<::std::vec::Vec<_>>()
+                    .into_iter()
+            };
+        };
+
+        Ok(OperatorWriteOutput {
+            write_prologue,
+            write_iterator,
+            ..Default::default()
+        })
+    }),
+};
@@ -0,0 +1,63 @@
+use super::{
+    DelayType, OperatorConstraints, OperatorWriteOutput, WriteContextArgs, WriteIteratorArgs,
+    RANGE_0, RANGE_1,
+};
+
+use quote::quote_spanned;
+use syn::parse_quote;
+
+/// > 2 input streams of type `A` and `B`, 1 output stream of type `(Option<A>, Option<B>)`
+///
+/// Pairs up the items of the two inputs positionally within a tick, like [`zip`](#zip). Unlike
+/// `zip`, items are not buffered across ticks: once the shorter input runs out, the remaining items
+/// of the longer input are emitted paired with `None`.
+///
+/// Both inputs are stratum barriers, so all items for the tick are available before pairing.
+///
+/// ```hydroflow
+/// // should print (Some(1), Some("a")), (Some(2), None)
+/// my_zip = zip_longest();
+/// source_iter(vec![1, 2]) -> [0]my_zip;
+/// source_iter(vec!["a"]) -> [1]my_zip;
+/// my_zip -> for_each(|x| println!("{:?}", x));
+/// ```
+#[hydroflow_internalmacro::operator_docgen]
+pub const ZIP_LONGEST: OperatorConstraints = OperatorConstraints {
+    name: "zip_longest",
+    hard_range_inn: &(2..=2),
+    soft_range_inn: &(2..=2),
+    hard_range_out: RANGE_1,
+    soft_range_out: RANGE_1,
+    ports_inn: Some(&(|| super::PortListSpec::Fixed(parse_quote! { 0, 1 }))),
+    ports_out: None,
+    num_args: RANGE_0,
+    input_delaytype_fn: &|_| Some(DelayType::Stratum),
+    write_fn: &(|&WriteContextArgs { op_span, .. },
+                 &WriteIteratorArgs {
+                     ident,
+                     inputs,
+                     is_pull,
+                     ..
+                 },
+                 _| {
+        assert!(is_pull);
+
+        let lhs = &inputs[0];
+        let rhs = &inputs[1];
+        let write_iterator = quote_spanned! {op_span=>
+            let #ident = {
+                let mut lhs = #lhs.fuse();
+                let mut rhs = #rhs.fuse();
+                ::std::iter::from_fn(move || match (lhs.next(), rhs.next()) {
+                    (::std::option::Option::None, ::std::option::Option::None) => ::std::option::Option::None,
+                    pair => ::std::option::Option::Some(pair),
+                })
+            };
+        };
+
+        Ok(OperatorWriteOutput {
+            write_iterator,
+            ..Default::default()
+        })
+    }),
+};
@@ -0,0 +1,374 @@
+//! Optimization passes which rewrite a [`FlatGraph`] before partitioning.
+
+use proc_macro2::Span;
+use quote::quote_spanned;
+use slotmap::Key;
+use syn::spanned::Spanned;
+use syn::{parse_quote_spanned, Expr};
+
+use super::flat_graph::FlatGraph;
+use super::{GraphEdgeId, GraphNodeId, Node, PortIndexValue};
+use crate::parse::Operator;
+
+impl FlatGraph {
+    /// Runs all optimization passes, which remove redundant operators and fuse chains of
+    /// operators. Each rewrite is recorded in [`Self::optimization_log`].
+    ///
+    /// Should be run after [`Self::insert_implicit_tee_merge`] and before partitioning.
+    pub fn optimize(&mut self) {
+        self.remove_null_tee_branches();
+        self.remove_pass_through_nodes();
+        self.push_down_filters();
+        self.fuse_chains();
+    }
+
+    /// Returns the rewrites made by [`Self::optimize`], for debugging.
+    pub fn optimization_log(&self) -> &[String] {
+        &self.optimization_log
+    }
+
+    /// Helper: the operator of `node_id`, if it is an operator.
+    pub(super) fn operator(&self, node_id: GraphNodeId) -> Option<&Operator> {
+        match &self.nodes[node_id] {
+            Node::Operator(operator) => Some(operator),
+            Node::Handoff { .. } => None,
+        }
+    }
+
+    /// Helper: if `node_id` is the operator `name`, without any generic arguments.
+    pub(super) fn is_operator(&self, node_id: GraphNodeId, name: &str) -> bool {
+        self.operator(node_id).map_or(false, |operator| {
+            name == operator.name_string() && operator.type_arguments().is_none()
+        })
+    }
+
+    /// Helper: removes a node and its edges.
+    pub(super) fn remove_node(&mut self, node_id: GraphNodeId) {
+        let edges: Vec<_> = self
+            .graph
+            .predecessor_edges(node_id)
+            .chain(self.graph.successor_edges(node_id))
+            .collect();
+        for edge_id in edges {
+            self.graph.remove_edge(edge_id);
+            self.ports.remove(edge_id);
+        }
+        self.nodes.remove(node_id);
+        self.node_names.remove(node_id);
+    }
+
+    /// Helper: removes a node with one input and one output, connecting its predecessor directly
+    /// to its successor. Returns the predecessor and successor.
+    pub(super) fn bypass_node(&mut self, node_id: GraphNodeId) -> (GraphNodeId, GraphNodeId) {
+        let (pred_edge, pred) = self.graph.predecessors(node_id).next().unwrap();
+        let (succ_edge, succ) = self.graph.successors(node_id).next().unwrap();
+        let (src_port, _) = self.ports[pred_edge].clone();
+        let (_, dst_port) = self.ports[succ_edge].clone();
+        self.remove_node(node_id);
+        let edge_id = self.graph.insert_edge(pred, succ);
+        self.ports.insert(edge_id, (src_port, dst_port));
+        (pred, succ)
+    }
+
+    /// Helper: inserts `operator` along the edge `edge_id`, keeping the edge's ports on the
+    /// outside. Returns the new node.
+    pub(super) fn insert_intermediate_operator(
+        &mut self,
+        edge_id: GraphEdgeId,
+        operator: Operator,
+    ) -> GraphNodeId {
+        let span = operator.span();
+        let node_id = self.nodes.insert(Node::Operator(operator));
+        let (src_port, dst_port) = self.ports.remove(edge_id).unwrap();
+        let (edge_inn, edge_out) = self
+            .graph
+            .insert_intermediate_node(node_id, edge_id)
+            .unwrap();
+        self.ports
+            .insert(edge_inn, (src_port, PortIndexValue::Elided(span)));
+        self.ports
+            .insert(edge_out, (PortIndexValue::Elided(span), dst_port));
+        node_id
+    }
+
+    /// Removes `null()` sinks which are an output of a `tee()`. Upstream operators may have side
+    /// effects, so only the `null()` itself is removed.
+    fn remove_null_tee_branches(&mut self) {
+        let null_ids: Vec<_> = self
+            .nodes
+            .keys()
+            .filter(|&node_id| {
+                self.is_operator(node_id, "null")
+                    && 0 == self.graph.degree_out(node_id)
+                    && 1 == self.graph.degree_in(node_id)
+            })
+            .collect();
+        for null_id in null_ids {
+            let tee_id = self.graph.predecessor_nodes(null_id).next().unwrap();
+            if self.is_operator(tee_id, "tee") && 1 < self.graph.degree_out(tee_id) {
+                self.remove_node(null_id);
+                self.optimization_log.push(format!(
+                    "Removed `null()` ({:?}) branch of `tee()` ({:?}).",
+                    null_id.data(),
+                    tee_id.data(),
+                ));
+            }
+        }
+    }
+
+    /// Removes `identity()` operators, `merge()`s with a single input, and `tee()`s with a single
+    /// output, connecting their predecessor directly to their successor.
+    fn remove_pass_through_nodes(&mut self) {
+        let node_ids: Vec<_> = self.nodes.keys().collect();
+        for node_id in node_ids {
+            if 1 != self.graph.degree_in(node_id) || 1 != self.graph.degree_out(node_id) {
+                continue;
+            }
+            let Some(name) = ["identity", "merge", "tee"]
+                .into_iter()
+                .find(|&name| self.is_operator(node_id, name))
+            else {
+                continue;
+            };
+            if self
+                .graph
+                .predecessor_nodes(node_id)
+                .any(|pred| pred == node_id)
+            {
+                continue;
+            }
+
+            let (pred, succ) = self.bypass_node(node_id);
+
+            self.optimization_log.push(format!(
+                "Removed pass-through `{}()` ({:?}) between {:?} and {:?}.",
+                name,
+                node_id.data(),
+                pred.data(),
+                succ.data(),
+            ));
+        }
+    }
+
+    /// Fuses chains of `map()`, `filter()`, `filter_pure()`, and `filter_map()` operators into a
+    /// single operator.
+    fn fuse_chains(&mut self) {
+        let is_fusable = |graph: &Self, node_id| {
+            ["map", "filter", "filter_pure", "filter_map"]
+                .into_iter()
+                .any(|name| graph.is_operator(node_id, name))
+                && 1 == graph.operator(node_id).unwrap().args.len()
+        };
+        // If `node_id` can be fused with its successor.
+        let fuses_with_succ = |graph: &Self, node_id| {
+            if !is_fusable(graph, node_id) || 1 != graph.graph.degree_out(node_id) {
+                return None;
+            }
+            let (edge_id, succ) = graph.graph.successors(node_id).next().unwrap();
+            let (src_port, dst_port) = &graph.ports[edge_id];
+            (succ != node_id
+                && is_fusable(graph, succ)
+                && 1 == graph.graph.degree_in(succ)
+                && !src_port.is_specified()
+                && !dst_port.is_specified())
+            .then_some(succ)
+        };
+
+        let node_ids: Vec<_> = self.nodes.keys().collect();
+        for head in node_ids {
+            if !self.nodes.contains_key(head) {
+                continue;
+            }
+            // Only start at the head of a chain.
+            if let Some(pred) = self.graph.predecessor_nodes(head).next() {
+                if 1 == self.graph.degree_in(head) && Some(head) == fuses_with_succ(self, pred) {
+                    continue;
+                }
+            }
+            let mut chain = vec![head];
+            while let Some(succ) = fuses_with_succ(self, *chain.last().unwrap()) {
+                if chain.contains(&succ) {
+                    break;
+                }
+                chain.push(succ);
+            }
+            if chain.len() < 2 {
+                continue;
+            }
+
+            let operators: Vec<&Operator> = chain
+                .iter()
+                .map(|&node_id| self.operator(node_id).unwrap())
+                .collect();
+            let fused = fuse_operators(&operators);
+            let names: Vec<_> = operators
+                .iter()
+                .map(|operator| format!("`{}()`", operator.name_string()))
+                .collect();
+            self.optimization_log.push(format!(
+                "Fused {} ({}) into `{}()` ({:?}).",
+                names.join(", "),
+                chain
+                    .iter()
+                    .map(|node_id| format!("{:?}", node_id.data()))
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                fused.name_string(),
+                head.data(),
+            ));
+
+            // Replace the head with the fused operator, and move the tail's outputs to it.
+            let tail = *chain.last().unwrap();
+            let tail_succs: Vec<_> = self.graph.successors(tail).collect();
+            for (edge_id, succ) in tail_succs {
+                let ports = self.ports.remove(edge_id).unwrap();
+                self.graph.remove_edge(edge_id);
+                let new_edge_id = self.graph.insert_edge(head, succ);
+                self.ports.insert(new_edge_id, ports);
+            }
+            for &node_id in &chain[1..] {
+                self.remove_node(node_id);
+            }
+            self.nodes[head] = Node::Operator(fused);
+        }
+    }
+}
+
+/// Helper: fuses a chain of `map()`, `filter()`, `filter_pure()`, and `filter_map()` operators into
+/// a single `filter_map()`, or a single `map()` or `filter()` if there are only `map()`s or only
+/// filters.
+///
+/// Each operator's argument is evaluated once, outside of the fused closure, as it would be
+/// without fusing.
+fn fuse_operators(operators: &[&Operator]) -> Operator {
+    let span = operators[0].span();
+    let item = syn::Ident::new("__hydroflow_item", Span::mixed_site());
+    let keep = syn::Ident::new("__hydroflow_keep", Span::mixed_site());
+    let only_maps = operators.iter().all(|op| "map" == op.name_string());
+    let only_filters = operators
+        .iter()
+        .all(|op| matches!(&*op.name_string(), "filter" | "filter_pure"));
+
+    let fn_idents: Vec<syn::Ident> = (0..operators.len())
+        .map(|idx| syn::Ident::new(&*format!("__hydroflow_f_{}", idx), Span::mixed_site()))
+        .collect();
+    // Filter closures must take any `&T` lifetime, which they only do when passed to a function
+    // expecting that.
+    let filter_fn = syn::Ident::new("__hydroflow_filter_fn", Span::mixed_site());
+    let filter_fn_def = (!only_maps).then(|| {
+        quote_spanned! {span=>
+            #[inline(always)]
+            fn #filter_fn<T, F: ::std::ops::FnMut(&T) -> bool>(f: F) -> F {
+                f
+            }
+        }
+    });
+    let fn_lets: Vec<_> = operators
+        .iter()
+        .zip(&fn_idents)
+        .map(|(operator, fn_ident)| {
+            let arg: &Expr = &operator.args[0];
+            let value = match &*operator.name_string() {
+                "filter" | "filter_pure" => quote_spanned! {span=> #filter_fn(#arg) },
+                _ => quote_spanned! {span=> #arg },
+            };
+            quote_spanned! {span=>
+                #[allow(unused_mut)]
+                let mut #fn_ident = #value;
+            }
+        })
+        .collect();
+
+    if only_filters {
+        // The item is already a reference.
+        let steps = fn_idents.iter().map(|fn_ident| {
+            quote_spanned! {span=>
+                let #keep = #fn_ident(#item);
+                if !#keep {
+                    return false;
+                }
+            }
+        });
+        return parse_quote_spanned! {span=>
+            filter({
+                #filter_fn_def
+                #( #fn_lets )*
+                move |#item| {
+                    #( #steps )*
+                    true
+                }
+            })
+        };
+    }
+
+    let steps = operators
+        .iter()
+        .zip(&fn_idents)
+        .map(|(operator, fn_ident)| match &*operator.name_string() {
+            "map" => quote_spanned! {span=>
+                let #item = #fn_ident(#item);
+            },
+            "filter" | "filter_pure" => quote_spanned! {span=>
+                let #keep = #fn_ident(&#item);
+                if !#keep {
+                    return ::std::option::Option::None;
+                }
+            },
+            "filter_map" => quote_spanned! {span=>
+                let #item = #fn_ident(#item)?;
+            },
+            other => unreachable!("Cannot fuse `{}`.", other),
+        });
+
+    if only_maps {
+        parse_quote_spanned! {span=>
+            map({
+                #filter_fn_def
+                #( #fn_lets )*
+                move |#item| {
+                    #( #steps )*
+                    #item
+                }
+            })
+        }
+    } else {
+        parse_quote_spanned! {span=>
+            filter_map({
+                #filter_fn_def
+                #( #fn_lets )*
+                move |#item| {
+                    #( #steps )*
+                    ::std::option::Option::Some(#item)
+                }
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use syn::parse_quote;
+
+    use super::*;
+    use crate::diagnostic::Diagnostic;
+
+    #[test]
+    fn optimize() {
+        let flat_graph = FlatGraph::from_hfcode(parse_quote! {
+            #![optimize]
+            nums = source_iter(0..10) -> identity() -> tee();
+            nums -> map(|x| x + 1) -> filter(|x| x % 2 == 0) -> filter_map(|x| x.checked_sub(4)) -> merge() -> for_each(std::mem::drop);
+            nums -> null();
+        });
+        // Only the warning for the single-input `merge()`.
+        assert!(!flat_graph.diagnostics.iter().any(Diagnostic::is_error));
+        assert_eq!(5, flat_graph.optimization_log().len());
+        // `source_iter()`, fused `filter_map()`, and `for_each()`.
+        assert_eq!(3, flat_graph.nodes.len());
+        assert!(flat_graph
+            .optimization_log()
+            .last()
+            .unwrap()
+            .starts_with("Fused `map()`, `filter()`, `filter_map()`"));
+    }
+}
@@ -3,11 +3,14 @@ use quote::{format_ident, quote, quote_spanned, ToTokens};
 use slotmap::{Key, SecondaryMap, SlotMap, SparseSecondaryMap};
 use syn::spanned::Spanned;
 
-use crate::diagnostic::Diagnostic;
+use crate::diagnostic::{Diagnostic, Level};
 
 use super::di_mul_graph::DiMulGraph;
 use super::flat_graph::FlatGraph;
-use super::ops::{DelayType, OperatorWriteOutput, WriteContextArgs, WriteIteratorArgs, OPERATORS};
+use super::ops::{
+    find_operator, DelayType, OperatorConstraints, OperatorWriteOutput, WriteContextArgs,
+    WriteIteratorArgs,
+};
 use super::serde_graph::{SerdeEdge, SerdeGraph};
 use super::{node_color, Color, GraphEdgeId, GraphNodeId, GraphSubgraphId, Node, PortIndexValue};
 
@@ -35,6 +38,10 @@ pub struct PartitionedGraph {
     pub(crate) subgraph_internal_handoffs: SecondaryMap<GraphSubgraphId, Vec<GraphNodeId>>,
     /// The modality of each non-handoff node (Push or Pull)
     pub(crate) node_color_map: SparseSecondaryMap<GraphNodeId, Color>,
+    /// The variable name of named nodes, see [`FlatGraph`].
+    pub(crate) node_names: SecondaryMap<GraphNodeId, Ident>,
+    /// Operators defined outside of this crate, see [`FlatGraph`].
+    pub(crate) custom_operators: &'static [OperatorConstraints],
 }
 impl PartitionedGraph {
     pub fn new() -> Self {
@@ -46,6 +53,74 @@ impl PartitionedGraph {
         flat_graph.try_into()
     }
 
+    /// The node `node_id`.
+    pub fn node(&self, node_id: GraphNodeId) -> &Node {
+        &self.nodes[node_id]
+    }
+
+    /// The variable name of `node_id`, if it was assigned to one.
+    pub fn node_name(&self, node_id: GraphNodeId) -> Option<&Ident> {
+        self.node_names.get(node_id)
+    }
+
+    /// The inputs of `node_id` with their input ports and edges, sorted by port.
+    pub fn node_predecessors(
+        &self,
+        node_id: GraphNodeId,
+    ) -> Vec<(&PortIndexValue, GraphEdgeId, GraphNodeId)> {
+        let mut preds: Vec<_> = self
+            .graph
+            .predecessors(node_id)
+            .map(|(edge_id, pred)| (&self.ports[edge_id].1, edge_id, pred))
+            .collect();
+        preds.sort();
+        preds
+    }
+
+    /// The outputs of `node_id` with their output ports and edges, sorted by port.
+    pub fn node_successors(
+        &self,
+        node_id: GraphNodeId,
+    ) -> Vec<(&PortIndexValue, GraphEdgeId, GraphNodeId)> {
+        let mut succs: Vec<_> = self
+            .graph
+            .successors(node_id)
+            .map(|(edge_id, succ)| (&self.ports[edge_id].0, edge_id, succ))
+            .collect();
+        succs.sort();
+        succs
+    }
+
+    /// Each handoff node.
+    pub fn handoff_ids(&self) -> impl '_ + Iterator<Item = GraphNodeId> {
+        self.nodes
+            .iter()
+            .filter(|(_, node)| matches!(node, Node::Handoff { .. }))
+            .map(|(node_id, _)| node_id)
+    }
+
+    /// Each subgraph, with its nodes in topological order.
+    pub fn subgraphs(&self) -> impl '_ + Iterator<Item = (GraphSubgraphId, &[GraphNodeId])> {
+        self.subgraph_nodes
+            .iter()
+            .map(|(subgraph_id, nodes)| (subgraph_id, &**nodes))
+    }
+
+    /// The stratum of `subgraph_id`.
+    pub fn subgraph_stratum(&self, subgraph_id: GraphSubgraphId) -> usize {
+        self.subgraph_stratum.get(subgraph_id).cloned().unwrap_or(0)
+    }
+
+    /// The handoffs which `subgraph_id` receives from.
+    pub fn subgraph_recv_handoffs(&self, subgraph_id: GraphSubgraphId) -> &[GraphNodeId] {
+        &self.subgraph_recv_handoffs[subgraph_id]
+    }
+
+    /// The handoffs which `subgraph_id` sends to.
+    pub fn subgraph_send_handoffs(&self, subgraph_id: GraphSubgraphId) -> &[GraphNodeId] {
+        &self.subgraph_send_handoffs[subgraph_id]
+    }
+
     pub fn serde_string(&self) -> String {
         let mut string = String::new();
         self.write_serde_graph(&mut string).unwrap();
@@ -92,8 +167,10 @@ impl PartitionedGraph {
             });
 
         let mut diagnostics = Vec::new();
+        // Field name and handle expression for each external port.
+        let mut external_handles: Vec<(Ident, TokenStream)> = Vec::new();
 
-        let subgraphs = self
+        let subgraphs: Vec<TokenStream> = self
             .subgraph_nodes
             .iter()
             .map(|(subgraph_id, subgraph_nodes)| {
@@ -120,6 +197,7 @@ impl PartitionedGraph {
                 let mut op_prologue_code = Vec::new();
                 let mut subgraph_op_iter_code = Vec::new();
                 let mut subgraph_op_iter_after_code = Vec::new();
+                let mut subgraph_priorities = Vec::new();
                 {
                     let pull_to_push_idx = subgraph_nodes
                         .iter()
@@ -134,6 +212,17 @@ impl PartitionedGraph {
                         })
                         .unwrap_or(subgraph_nodes.len());
 
+                    // A computation (many-in many-out) operator pulls its inputs and pushes to
+                    // its outputs itself, so it acts as the pivot.
+                    let comp_pivot = subgraph_nodes.get(pull_to_push_idx).filter(|&&node_id| {
+                        Some(Color::Comp)
+                            == node_color(
+                                &self.nodes[node_id],
+                                self.graph.degree_in(node_id),
+                                self.graph.degree_out(node_id),
+                            )
+                    });
+
                     let (pull_half, push_half) = subgraph_nodes.split_at(pull_to_push_idx);
                     let nodes_iter = pull_half.iter().chain(push_half.iter().rev());
 
@@ -146,9 +235,7 @@ impl PartitionedGraph {
 
                         let op_span = node.span();
                         let op_name = &*op.name_string();
-                        let op_constraints = OPERATORS
-                            .iter()
-                            .find(|op| op_name == op.name)
+                        let op_constraints = find_operator(op_name, self.custom_operators)
                             .unwrap_or_else(|| panic!("Failed to find op: {}", op_name));
 
                         let ident = self.node_id_as_ident(node_id, false);
@@ -209,13 +296,38 @@ impl PartitionedGraph {
                                 write_prologue,
                                 write_iterator,
                                 write_iterator_after,
+                                subgraph_priority,
+                                external_handle,
                             }) = write_result else {
                                 continue;
                             };
+                            subgraph_priorities.extend(subgraph_priority);
+                            if let Some(external_handle) = external_handle {
+                                match self.node_names.get(node_id) {
+                                    Some(name) if *name == "df" => diagnostics.push(Diagnostic::spanned(
+                                        name.span(),
+                                        Level::Error,
+                                        format!("`{}` is reserved for the graph, use a different name for the `{}()` port", name, op_name),
+                                    )),
+                                    Some(name) if external_handles.iter().any(|(other, _)| other == name) => diagnostics.push(Diagnostic::spanned(
+                                        op_span,
+                                        Level::Error,
+                                        format!("Name `{}` is already used by another port, each `{}()` port must be assigned to its own name", name, op_name),
+                                    )),
+                                    Some(name) => {
+                                        external_handles.push((name.clone(), external_handle));
+                                    }
+                                    None => diagnostics.push(Diagnostic::spanned(
+                                        op_span,
+                                        Level::Error,
+                                        format!("Port operator `{}()` must be assigned to a name, e.g. `my_port = {}()`, which names its handle", op_name, op_name),
+                                    )),
+                                }
+                            }
 
                             op_prologue_code.push(write_prologue);
                             subgraph_op_iter_code.push(write_iterator);
-                            if include_type_guards {
+                            if include_type_guards && Some(&node_id) != comp_pivot {
                                 let fn_ident = format_ident!("check_{}", ident, span = op_span);
                                 let pull_push_trait = if is_pull {
                                     quote_spanned! {op_span=>
@@ -239,11 +351,29 @@ impl PartitionedGraph {
                         }
                     }
 
-                    {
+                    if comp_pivot.is_none() {
                         // Determine pull and push halves of the `Pivot`.
                         let pull_to_push_idx = pull_to_push_idx;
-                        let pull_ident =
-                            self.node_id_as_ident(subgraph_nodes[pull_to_push_idx - 1], false);
+                        let pull_ident = if 0 < pull_to_push_idx {
+                            self.node_id_as_ident(subgraph_nodes[pull_to_push_idx - 1], false)
+                        } else if let [recv_port] = &*recv_ports {
+                            // Entire subgraph is push (except for a single recv/pull handoff input).
+                            recv_port.clone()
+                        } else {
+                            // The first node is push, so it has at most one input. Subgraphs are
+                            // connected, so other nodes should not have handoff inputs.
+                            let first_node = subgraph_nodes[0];
+                            diagnostics.push(Diagnostic::spanned(
+                                self.nodes[first_node].span(),
+                                Level::Error,
+                                format!(
+                                    "Subgraph starting at this operator is entirely push, so it must have exactly one handoff input, but it has {}. This is a bug in partitioning.",
+                                    recv_ports.len()
+                                ),
+                            ));
+                            // Not used, the code is discarded when there are errors.
+                            self.node_id_as_ident(first_node, false)
+                        };
 
                         #[rustfmt::skip]
                         let push_ident = if let Some(&node_id) =
@@ -279,10 +409,23 @@ impl PartitionedGraph {
                 let stratum = Literal::usize_unsuffixed(
                     self.subgraph_stratum.get(subgraph_id).cloned().unwrap_or(0),
                 );
+                let (sg_id_code, set_priority_code) = if subgraph_priorities.is_empty() {
+                    Default::default()
+                } else {
+                    (
+                        quote! { let sg_id = },
+                        quote! {
+                            df.set_subgraph_priority(
+                                sg_id,
+                                [ #( #subgraph_priorities ),* ].into_iter().max().unwrap(),
+                            );
+                        },
+                    )
+                };
                 quote! {
                     #( #op_prologue_code )*
 
-                    df.add_subgraph_stratified(
+                    #sg_id_code df.add_subgraph_stratified(
                         #hoff_name,
                         #stratum,
                         var_expr!( #( #recv_ports ),* ),
@@ -294,8 +437,32 @@ impl PartitionedGraph {
                             #( #subgraph_op_iter_after_code )*
                         },
                     );
+                    #set_priority_code
                 }
-            });
+            })
+            .collect();
+
+        // With external ports, return the graph along with the port handles.
+        let output = if external_handles.is_empty() {
+            quote! { df }
+        } else {
+            let (names, handles): (Vec<_>, Vec<_>) = external_handles.into_iter().unzip();
+            let type_params: Vec<Ident> = (0..names.len())
+                .map(|i| format_ident!("Port{}", i))
+                .collect();
+            quote! {
+                /// A Hydroflow graph along with the handles of its external ports.
+                struct HydroflowPorts< #( #type_params ),* > {
+                    /// The graph.
+                    pub df: #root::scheduled::graph::Hydroflow,
+                    #( pub #names: #type_params, )*
+                }
+                HydroflowPorts {
+                    df,
+                    #( #names: #handles, )*
+                }
+            }
+        };
 
         let serde_string = Literal::string(&*self.serde_string());
         let code = quote! {
@@ -307,7 +474,7 @@ impl PartitionedGraph {
                 #( #handoffs )*
                 #( #subgraphs )*
 
-                df
+                #output
             }
         };
 
@@ -338,9 +505,7 @@ impl PartitionedGraph {
             let the_ports = &self.ports[edge_id];
             if let Node::Operator(dest_op) = &self.nodes[dst] {
                 let op_name = &*dest_op.name_string();
-                let op_constraints = OPERATORS
-                    .iter()
-                    .find(|op| op_name == op.name)
+                let op_constraints = find_operator(op_name, self.custom_operators)
                     .unwrap_or_else(|| panic!("Failed to find op: {}", op_name));
                 if let Some(delay) = (op_constraints.input_delaytype_fn)(&the_ports.1) {
                     if delay == DelayType::Stratum {
@@ -405,6 +570,7 @@ impl PartitionedGraph {
             g.subgraph_internal_handoffs = self.subgraph_internal_handoffs.clone();
             g.node_color_map = self.node_color_map.clone();
         }
+        g.subgraph_count = self.subgraph_nodes.len();
         g
     }
 
@@ -0,0 +1,639 @@
+//! Pushdown passes which move pure filters and projections towards the sources of a
+//! [`FlatGraph`], so less data flows through the graph and into `join()` state.
+
+use proc_macro2::{Span, TokenStream, TokenTree};
+use quote::{format_ident, ToTokens};
+use slotmap::Key;
+use syn::spanned::Spanned;
+use syn::visit::Visit;
+use syn::visit_mut::VisitMut;
+use syn::{parse_quote, parse_quote_spanned, Expr, ExprClosure, Ident, Member, Pat, Type};
+
+use super::flat_graph::FlatGraph;
+use super::{GraphEdgeId, GraphNodeId, Node, PortIndexValue};
+use crate::parse::Operator;
+
+impl FlatGraph {
+    /// Moves `filter_pure()` operators upstream: before a `merge()` (onto each of its inputs),
+    /// before a `tee()` (if each of its outputs has the same filter), and into the inputs of a
+    /// `join()` if the filter only uses the key or one side's values.
+    ///
+    /// Each move is recorded in [`Self::optimization_log`].
+    pub fn push_down_filters(&mut self) {
+        // Moving a filter may let it move further upstream, so repeat until nothing moves. Bounded
+        // in case filters keep moving around a cycle.
+        for _ in 0..self.nodes.len() {
+            let filter_ids: Vec<_> = self
+                .nodes
+                .keys()
+                .filter(|&node_id| self.is_operator(node_id, "filter_pure"))
+                .collect();
+            let mut moved = false;
+            for filter_id in filter_ids {
+                if self.nodes.contains_key(filter_id) {
+                    moved |= self.push_down_filter(filter_id);
+                }
+            }
+            if !moved {
+                break;
+            }
+        }
+    }
+
+    /// Drops the parts of `join()` inputs which are not used by the `map()` after the join: a
+    /// side's values which are not used are replaced with `()`, and unused fields of a side's
+    /// tuple values are replaced with `()`.
+    ///
+    /// `join()` ignores duplicate values for each key, so this may remove duplicates from the
+    /// output. Only use this when duplicates do not matter, for example in datalog programs.
+    pub fn push_down_projections(&mut self) {
+        let join_ids: Vec<_> = self
+            .nodes
+            .keys()
+            .filter(|&node_id| {
+                self.operator(node_id)
+                    .map_or(false, |operator| "join" == operator.name_string())
+            })
+            .collect();
+        for join_id in join_ids {
+            let Some(map_id) = self.join_successor(join_id, "map") else {
+                continue;
+            };
+            let Some(closure) = single_closure(self.operator(map_id).unwrap()) else {
+                continue;
+            };
+            let Some(join_use) = JoinUse::analyze(closure) else {
+                continue;
+            };
+
+            let mut closure = closure.clone();
+            let mut projected = Vec::new();
+            for side in 0..2 {
+                let Some(projection) = join_use.projection(&closure, side) else {
+                    continue;
+                };
+                let Some(edge_id) = self.join_input(join_id, side) else {
+                    continue;
+                };
+                let span = closure.span();
+                let key = Ident::new("__hydroflow_key", Span::mixed_site());
+                let operator: Operator = match &projection {
+                    None => parse_quote_spanned! {span=>
+                        map(|(#key, _)| (#key, ()))
+                    },
+                    Some(kept) => {
+                        let fields: Vec<Ident> = (0..kept.len())
+                            .map(|idx| {
+                                format_ident!(
+                                    "__hydroflow_field_{}",
+                                    idx,
+                                    span = Span::mixed_site()
+                                )
+                            })
+                            .collect();
+                        let pats = kept.iter().zip(&fields).map(|(&keep, field)| {
+                            if keep {
+                                field.to_token_stream()
+                            } else {
+                                quote::quote! { _ }
+                            }
+                        });
+                        let vals = kept.iter().zip(&fields).map(|(&keep, field)| {
+                            if keep {
+                                field.to_token_stream()
+                            } else {
+                                quote::quote! { () }
+                            }
+                        });
+                        parse_quote_spanned! {span=>
+                            map(|(#key, ( #( #pats, )* ))| (#key, ( #( #vals, )* )))
+                        }
+                    }
+                };
+                let node_id = self.insert_intermediate_operator(edge_id, operator);
+                set_unit_types(&mut closure, side, projection.as_deref());
+                projected.push(format!("{} ({:?})", side, node_id.data()));
+            }
+            if projected.is_empty() {
+                continue;
+            }
+
+            let Node::Operator(map_op) = &mut self.nodes[map_id] else {
+                unreachable!();
+            };
+            map_op.args[0] = Expr::Closure(closure);
+            self.optimization_log.push(format!(
+                "Projected unused values of `join()` ({:?}) inputs {}, before `map()` ({:?}).",
+                join_id.data(),
+                projected.join(", "),
+                map_id.data(),
+            ));
+        }
+    }
+
+    /// Helper: tries to move the filter `filter_id` upstream. Returns if it moved.
+    fn push_down_filter(&mut self, filter_id: GraphNodeId) -> bool {
+        if 1 != self.graph.degree_in(filter_id) || 1 != self.graph.degree_out(filter_id) {
+            return false;
+        }
+        let (edge_id, pred) = self.graph.predecessors(filter_id).next().unwrap();
+        if pred == filter_id || self.ports[edge_id].0.is_specified() {
+            return false;
+        }
+        let filter = self.operator(filter_id).unwrap().clone();
+
+        if self.is_operator(pred, "merge") && 1 == self.graph.degree_out(pred) {
+            let inn_edges: Vec<_> = self.graph.predecessor_edges(pred).collect();
+            for inn_edge in inn_edges {
+                self.insert_intermediate_operator(inn_edge, filter.clone());
+            }
+            self.bypass_node(filter_id);
+            self.optimization_log.push(format!(
+                "Moved `filter_pure()` ({:?}) before `merge()` ({:?}).",
+                filter_id.data(),
+                pred.data(),
+            ));
+            return true;
+        }
+
+        if self.is_operator(pred, "tee") && 1 == self.graph.degree_in(pred) {
+            let filter_str = filter.to_token_stream().to_string();
+            let branches: Vec<_> = self.graph.successors(pred).collect();
+            let all_same = branches.iter().all(|&(branch_edge, branch)| {
+                self.is_operator(branch, "filter_pure")
+                    && 1 == self.graph.degree_in(branch)
+                    && 1 == self.graph.degree_out(branch)
+                    && !self.ports[branch_edge].1.is_specified()
+                    && filter_str == self.operator(branch).unwrap().to_token_stream().to_string()
+            });
+            if !all_same {
+                return false;
+            }
+            let inn_edge = self.graph.predecessor_edges(pred).next().unwrap();
+            self.insert_intermediate_operator(inn_edge, filter);
+            for (_, branch) in branches {
+                self.bypass_node(branch);
+            }
+            self.optimization_log.push(format!(
+                "Moved `filter_pure()` ({:?}) before `tee()` ({:?}).",
+                filter_id.data(),
+                pred.data(),
+            ));
+            return true;
+        }
+
+        if Some(filter_id) == self.join_successor(pred, "filter_pure") {
+            let Some(closure) = single_closure(&filter) else {
+                return false;
+            };
+            let Some(join_use) = JoinUse::analyze(closure) else {
+                return false;
+            };
+            // Sides whose values are used, a filter on the key alone can go on both sides.
+            let sides: Vec<usize> = match (join_use.vals[0].is_used(), join_use.vals[1].is_used()) {
+                (false, false) => vec![0, 1],
+                (true, false) => vec![0],
+                (false, true) => vec![1],
+                (true, true) => return false,
+            };
+            let Some(inn_edges) = sides
+                .iter()
+                .map(|&side| self.join_input(pred, side))
+                .collect::<Option<Vec<_>>>()
+            else {
+                return false;
+            };
+            for (side, inn_edge) in sides.iter().zip(inn_edges) {
+                let mut side_filter = filter.clone();
+                side_filter.args[0] = Expr::Closure(join_use.side_closure(closure, *side));
+                self.insert_intermediate_operator(inn_edge, side_filter);
+            }
+            self.bypass_node(filter_id);
+            self.optimization_log.push(format!(
+                "Moved `filter_pure()` ({:?}) into input(s) {:?} of `join()` ({:?}).",
+                filter_id.data(),
+                sides,
+                pred.data(),
+            ));
+            return true;
+        }
+
+        false
+    }
+
+    /// Helper: if `join_id`'s only output is the operator `name`, with elided ports, returns it.
+    fn join_successor(&self, join_id: GraphNodeId, name: &str) -> Option<GraphNodeId> {
+        if !self
+            .operator(join_id)
+            .map_or(false, |operator| "join" == operator.name_string())
+            || 1 != self.graph.degree_out(join_id)
+        {
+            return None;
+        }
+        let (edge_id, succ) = self.graph.successors(join_id).next().unwrap();
+        let (src_port, dst_port) = &self.ports[edge_id];
+        (self.is_operator(succ, name)
+            && 1 == self.graph.degree_in(succ)
+            && !src_port.is_specified()
+            && !dst_port.is_specified())
+        .then_some(succ)
+    }
+
+    /// Helper: the edge into input port `side` of `join_id`.
+    fn join_input(&self, join_id: GraphNodeId, side: usize) -> Option<GraphEdgeId> {
+        self.graph.predecessor_edges(join_id).find(|&edge_id| {
+            matches!(&self.ports[edge_id].1, PortIndexValue::Int(idx) if side as isize == idx.value)
+        })
+    }
+}
+
+/// Helper: the operator's argument, if it is its only argument and is a closure with one input.
+fn single_closure(operator: &Operator) -> Option<&ExprClosure> {
+    let mut args = operator.args.iter();
+    match (args.next(), args.next()) {
+        (Some(Expr::Closure(closure)), None) if 1 == closure.inputs.len() => Some(closure),
+        _ => None,
+    }
+}
+
+/// Helper: strips `pat: Type` and `&pat` from a closure input, returning the inner pattern.
+fn strip_pat(mut pat: &Pat) -> &Pat {
+    loop {
+        pat = match pat {
+            Pat::Type(pat_type) => &*pat_type.pat,
+            Pat::Reference(pat_ref) => &*pat_ref.pat,
+            _ => return pat,
+        };
+    }
+}
+
+/// Which values of a `join()` output, `(key, (v0, v1))`, a side of the join uses.
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum ValUse {
+    /// Not used.
+    Unused,
+    /// Only the fields of the (tuple) values which are `true`, if the number of fields is known,
+    /// otherwise only the listed fields.
+    Fields(Vec<bool>),
+    /// The whole value.
+    All,
+}
+impl ValUse {
+    fn is_used(&self) -> bool {
+        Self::Unused != *self
+    }
+
+    fn add_field(&mut self, field: usize) {
+        match self {
+            Self::Unused => {
+                let mut fields = vec![false; field + 1];
+                fields[field] = true;
+                *self = Self::Fields(fields);
+            }
+            Self::Fields(fields) => {
+                if fields.len() <= field {
+                    fields.resize(field + 1, false);
+                }
+                fields[field] = true;
+            }
+            Self::All => {}
+        }
+    }
+}
+
+/// How a closure uses its input, the output of a `join()`.
+#[derive(Debug)]
+struct JoinUse {
+    /// If the closure's input is a single identifier, e.g. `kv`, which is used through field
+    /// accesses like `kv.1.0`. Otherwise the input is the pattern `(key, (v0, v1))`.
+    ident: Option<Ident>,
+    vals: [ValUse; 2],
+}
+impl JoinUse {
+    /// Returns how `closure` uses the values from each side of the join, or `None` if its input
+    /// is not understood.
+    fn analyze(closure: &ExprClosure) -> Option<Self> {
+        match strip_pat(closure.inputs.first()?) {
+            Pat::Ident(pat_ident) if pat_ident.subpat.is_none() && pat_ident.by_ref.is_none() => {
+                let mut visitor = FieldUseVisitor {
+                    ident: &pat_ident.ident,
+                    vals: [ValUse::Unused, ValUse::Unused],
+                };
+                visitor.visit_expr(&closure.body);
+                Some(Self {
+                    ident: Some(pat_ident.ident.clone()),
+                    vals: visitor.vals,
+                })
+            }
+            Pat::Tuple(pat_tuple) if 2 == pat_tuple.elems.len() => {
+                let Pat::Tuple(vals_tuple) = strip_pat(&pat_tuple.elems[1]) else {
+                    return None;
+                };
+                if 2 != vals_tuple.elems.len() {
+                    return None;
+                }
+                let body = closure.body.to_token_stream();
+                let unused = |pat: &Pat| match pat {
+                    Pat::Wild(_) => true,
+                    Pat::Ident(pat_ident) if pat_ident.subpat.is_none() => {
+                        !tokens_contain(body.clone(), &pat_ident.ident)
+                    }
+                    _ => false,
+                };
+                let vals = [0, 1].map(|side| match &vals_tuple.elems[side] {
+                    pat if unused(pat) => ValUse::Unused,
+                    Pat::Tuple(fields)
+                        if !fields.elems.iter().any(|pat| matches!(pat, Pat::Rest(_))) =>
+                    {
+                        ValUse::Fields(fields.elems.iter().map(|pat| !unused(pat)).collect())
+                    }
+                    _ => ValUse::All,
+                });
+                Some(Self { ident: None, vals })
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns a closure equivalent to `closure`, but which takes the input of `side` of the
+    /// join, `(key, v)`, instead of the join's output. The other side must be unused.
+    fn side_closure(&self, closure: &ExprClosure, side: usize) -> ExprClosure {
+        let mut closure = closure.clone();
+        let input = closure.inputs.first_mut().unwrap();
+        // The type annotation is for the join's output, `(K, (V0, V1))`, change it to `(K, V)`.
+        if let Pat::Type(pat_type) = input {
+            if side_type(&mut pat_type.ty, side).is_none() {
+                let pat = (*pat_type.pat).clone();
+                *input = pat;
+            }
+        }
+        let mut pat = input;
+        loop {
+            pat = match pat {
+                Pat::Type(pat_type) => &mut *pat_type.pat,
+                Pat::Reference(pat_ref) => &mut *pat_ref.pat,
+                _ => break,
+            };
+        }
+
+        if let Some(ident) = &self.ident {
+            // Replace `kv.1.side` with `kv.1`.
+            FieldRewriter { ident, side }.visit_expr_mut(&mut closure.body);
+        } else if let Pat::Tuple(pat_tuple) = pat {
+            let Pat::Tuple(vals_tuple) = strip_pat(&pat_tuple.elems[1]) else {
+                unreachable!();
+            };
+            let val_pat = vals_tuple.elems[side].clone();
+            pat_tuple.elems[1] = val_pat;
+        }
+        closure
+    }
+
+    /// Returns the projection of `side` of the join which keeps the used values, if any can be
+    /// dropped: `None` to replace the whole value with `()`, or which fields of a tuple to keep.
+    ///
+    /// `closure` is the (possibly already projected) closure after the join, whose type annotation
+    /// gives the number of fields and shows which fields have already been replaced with `()`.
+    fn projection(&self, closure: &ExprClosure, side: usize) -> Option<Option<Vec<bool>>> {
+        let val_type = val_type(closure, side);
+        if val_type.map_or(false, is_unit) {
+            return None;
+        }
+        match &self.vals[side] {
+            ValUse::Unused => Some(None),
+            ValUse::Fields(used) => {
+                let Some(Type::Tuple(type_tuple)) = val_type else {
+                    // The number of fields isn't known from the type, but is from the pattern.
+                    return (self.ident.is_none() && used.contains(&false)).then(|| Some(used.clone()));
+                };
+                if type_tuple.elems.len() < used.len() {
+                    return None;
+                }
+                let kept: Vec<bool> = type_tuple
+                    .elems
+                    .iter()
+                    .enumerate()
+                    .map(|(idx, ty)| used.get(idx).copied().unwrap_or(false) && !is_unit(ty))
+                    .collect();
+                let droppable = type_tuple
+                    .elems
+                    .iter()
+                    .zip(&kept)
+                    .any(|(ty, &keep)| !keep && !is_unit(ty));
+                droppable.then_some(Some(kept))
+            }
+            ValUse::All => None,
+        }
+    }
+}
+
+/// Helper: the type annotation of `side`'s values in the input of a closure after a join,
+/// `|x: (K, (V0, V1))|`.
+fn val_type(closure: &ExprClosure, side: usize) -> Option<&Type> {
+    let Pat::Type(pat_type) = closure.inputs.first()? else {
+        return None;
+    };
+    let Type::Tuple(kv) = &*pat_type.ty else {
+        return None;
+    };
+    let Type::Tuple(vals) = kv.elems.iter().nth(1)? else {
+        return None;
+    };
+    vals.elems.iter().nth(side)
+}
+
+/// Helper: changes the type of a join's output, `(K, (V0, V1))` or a reference to it, to the type
+/// of the input `side`, `(K, V)`. Returns `None` if the type does not have that form.
+fn side_type(ty: &mut Type, side: usize) -> Option<()> {
+    let mut ty = ty;
+    while let Type::Reference(type_ref) = ty {
+        ty = &mut *type_ref.elem;
+    }
+    let Type::Tuple(kv) = ty else {
+        return None;
+    };
+    let Some(Type::Tuple(vals)) = kv.elems.iter().nth(1) else {
+        return None;
+    };
+    let val_type = vals.elems.iter().nth(side)?.clone();
+    *kv.elems.iter_mut().nth(1).unwrap() = val_type;
+    Some(())
+}
+
+/// Helper: updates the type annotation of a closure after a join to match a projection.
+fn set_unit_types(closure: &mut ExprClosure, side: usize, kept: Option<&[bool]>) {
+    let Some(Pat::Type(pat_type)) = closure.inputs.first_mut() else {
+        return;
+    };
+    let Type::Tuple(kv) = &mut *pat_type.ty else {
+        return;
+    };
+    let Some(Type::Tuple(vals)) = kv.elems.iter_mut().nth(1) else {
+        return;
+    };
+    let Some(val_type) = vals.elems.iter_mut().nth(side) else {
+        return;
+    };
+    match (kept, val_type) {
+        (None, val_type) => *val_type = parse_quote!(()),
+        (Some(kept), Type::Tuple(fields)) => {
+            for (ty, &keep) in fields.elems.iter_mut().zip(kept) {
+                if !keep {
+                    *ty = parse_quote!(());
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Helper: if `ty` is `()`.
+fn is_unit(ty: &Type) -> bool {
+    matches!(ty, Type::Tuple(tuple) if tuple.elems.is_empty())
+}
+
+/// Helper: if `expr` is `ident` followed by tuple field accesses, e.g. `kv.1.0`, returns the
+/// field indices.
+fn field_path(expr: &Expr, ident: &Ident) -> Option<Vec<u32>> {
+    match expr {
+        Expr::Path(expr_path) if expr_path.qself.is_none() && expr_path.path.is_ident(ident) => {
+            Some(Vec::new())
+        }
+        Expr::Field(expr_field) => {
+            let Member::Unnamed(index) = &expr_field.member else {
+                return None;
+            };
+            let mut path = field_path(&expr_field.base, ident)?;
+            path.push(index.index);
+            Some(path)
+        }
+        Expr::Paren(expr_paren) => field_path(&expr_paren.expr, ident),
+        _ => None,
+    }
+}
+
+/// Helper: if `tokens` contains `ident`.
+fn tokens_contain(tokens: TokenStream, ident: &Ident) -> bool {
+    tokens.into_iter().any(|token| match token {
+        TokenTree::Group(group) => tokens_contain(group.stream(), ident),
+        TokenTree::Ident(token_ident) => &token_ident == ident,
+        _ => false,
+    })
+}
+
+/// Finds which values of a join's output, `ident`, are used.
+struct FieldUseVisitor<'a> {
+    ident: &'a Ident,
+    vals: [ValUse; 2],
+}
+impl<'ast> Visit<'ast> for FieldUseVisitor<'_> {
+    fn visit_expr(&mut self, expr: &'ast Expr) {
+        let Some(path) = field_path(expr, self.ident) else {
+            syn::visit::visit_expr(self, expr);
+            return;
+        };
+        match path[..] {
+            // The key.
+            [0, ..] => {}
+            [1, side, field, ..] if side < 2 => self.vals[side as usize].add_field(field as usize),
+            [1, side] if side < 2 => self.vals[side as usize] = ValUse::All,
+            _ => self.vals = [ValUse::All, ValUse::All],
+        }
+    }
+
+    fn visit_macro(&mut self, mac: &'ast syn::Macro) {
+        // Macro arguments aren't parsed, assume they use everything.
+        if tokens_contain(mac.tokens.clone(), self.ident) {
+            self.vals = [ValUse::All, ValUse::All];
+        }
+    }
+}
+
+/// Replaces `ident.1.side` with `ident.1`.
+struct FieldRewriter<'a> {
+    ident: &'a Ident,
+    side: usize,
+}
+impl VisitMut for FieldRewriter<'_> {
+    fn visit_expr_mut(&mut self, expr: &mut Expr) {
+        if let Expr::Field(expr_field) = expr {
+            if matches!(&expr_field.member, Member::Unnamed(index) if self.side as u32 == index.index)
+                && Some(vec![1]) == field_path(&expr_field.base, self.ident)
+            {
+                *expr = (*expr_field.base).clone();
+                return;
+            }
+        }
+        syn::visit_mut::visit_expr_mut(self, expr);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn push_down_filters() {
+        let mut flat_graph = FlatGraph::from_hfcode(parse_quote! {
+            my_join = join() -> filter_pure(|kv: &(u32, (u32, u32))| kv.1.0 > 10) -> for_each(std::mem::drop);
+            lhs = merge() -> [0]my_join;
+            source_iter([(1, 5)]) -> [0]lhs;
+            source_iter([(1, 15)]) -> [1]lhs;
+            source_iter([(1, 20)]) -> [1]my_join;
+        });
+        flat_graph.push_down_filters();
+        assert_eq!(
+            vec![
+                "Moved `filter_pure()` (2v1) into input(s) [0] of `join()` (1v1).",
+                "Moved `filter_pure()` (8v1) before `merge()` (4v1).",
+            ],
+            flat_graph.optimization_log()
+        );
+        // The filter on each merge input only sees the left side, `(u32, u32)`.
+        let filter_count = flat_graph
+            .surface_syntax_string()
+            .matches("filter_pure (| kv : & (u32 , u32) | kv . 1 > 10)")
+            .count();
+        assert_eq!(2, filter_count);
+    }
+
+    #[test]
+    fn push_down_projections() {
+        let mut flat_graph = FlatGraph::from_hfcode(parse_quote! {
+            my_join = join() -> map(|(k, ((_a, b), _c)): (u32, ((u32, u32), u32))| (k, b)) -> for_each(std::mem::drop);
+            source_iter([(1, (5, 6))]) -> [0]my_join;
+            source_iter([(1, 7)]) -> [1]my_join;
+        });
+        flat_graph.push_down_projections();
+        assert_eq!(
+            vec![
+                "Projected unused values of `join()` (1v1) inputs 0 (6v1), 1 (7v1), before `map()` (2v1).",
+            ],
+            flat_graph.optimization_log()
+        );
+        let surface = flat_graph.surface_syntax_string();
+        // The unused field of the left side, and all of the right side, are replaced with `()`.
+        assert!(surface.contains(
+            "map (| (__hydroflow_key , (_ , __hydroflow_field_1 ,)) | (__hydroflow_key , (() , __hydroflow_field_1 ,)))"
+        ), "{}", surface);
+        assert!(
+            surface.contains("map (| (__hydroflow_key , _) | (__hydroflow_key , ()))"),
+            "{}",
+            surface
+        );
+        assert!(
+            surface.contains("map (| (k , ((_a , b) , _c)) : (u32 , ((() , u32) , ())) | (k , b))"),
+            "{}",
+            surface
+        );
+
+        // Nothing to project when all values are used.
+        let mut flat_graph = FlatGraph::from_hfcode(parse_quote! {
+            my_join = join() -> map(|(k, (a, b)): (u32, (u32, u32))| (k, a + b)) -> for_each(std::mem::drop);
+            source_iter([(1, 5)]) -> [0]my_join;
+            source_iter([(1, 7)]) -> [1]my_join;
+        });
+        flat_graph.push_down_projections();
+        assert!(flat_graph.optimization_log().is_empty());
+    }
+}
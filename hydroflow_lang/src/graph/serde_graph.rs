@@ -22,6 +22,9 @@ pub struct SerdeGraph {
     pub subgraph_nodes: SlotMap<GraphSubgraphId, Vec<GraphNodeId>>,
     pub subgraph_stratum: SecondaryMap<GraphSubgraphId, usize>,
     pub subgraph_internal_handoffs: SecondaryMap<GraphSubgraphId, Vec<GraphNodeId>>,
+    /// The number of subgraphs the graph was partitioned into.
+    #[serde(default)]
+    pub subgraph_count: usize,
 }
 
 impl SerdeGraph {
@@ -2,8 +2,67 @@
 #![feature(iter_intersperse, array_zip)]
 #![allow(clippy::let_and_return)]
 #![allow(clippy::explicit_auto_deref)]
+extern crate proc_macro;
+
 pub mod diagnostic;
 pub mod graph;
+pub mod module;
 pub mod parse;
 pub mod pretty_span;
 pub mod union_find;
+
+use proc_macro2::TokenStream;
+use quote::quote;
+
+use crate::graph::flat_graph::FlatGraph;
+use crate::graph::ops::OperatorConstraints;
+use crate::module::expand_module_call;
+use crate::parse::HfCode;
+
+/// Generates the code for a `hydroflow_syntax!` invocation, which may use `custom_operators` in
+/// addition to the built-in operators.
+///
+/// Proc macros can't see code from the crates using them, so crates defining their own operators
+/// define their own proc macro which calls this:
+///
+/// ```ignore
+/// const MY_OPERATORS: &[OperatorConstraints] = &[BUS_SOURCE];
+///
+/// #[proc_macro]
+/// pub fn my_hydroflow_syntax(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+///     hydroflow_lang::hydroflow_syntax_with_operators(
+///         input.into(),
+///         quote::quote! { hydroflow },
+///         quote::quote! { my_macros::my_hydroflow_syntax },
+///         MY_OPERATORS,
+///     )
+///     .into()
+/// }
+/// ```
+///
+/// `root` is the path of the `hydroflow` crate, and `callback` is the path of the calling macro,
+/// which is called again to expand module calls (see [`module`]). The `hydroflow_example_ops`
+/// crate is a complete example.
+pub fn hydroflow_syntax_with_operators(
+    input: TokenStream,
+    root: TokenStream,
+    callback: TokenStream,
+    custom_operators: &'static [OperatorConstraints],
+) -> TokenStream {
+    if let Some(expanded) = expand_module_call(input.clone(), callback) {
+        return expanded;
+    }
+    let input: HfCode = match syn::parse2(input) {
+        Ok(input) => input,
+        Err(err) => return err.to_compile_error(),
+    };
+
+    let flat_graph = FlatGraph::from_hfcode_with_operators(input, custom_operators);
+    if !flat_graph.emit_diagnostics() {
+        match flat_graph.into_partitioned_graph() {
+            Ok(part_graph) => return part_graph.as_code(root, true),
+            Err(diagnostic) => diagnostic.emit(),
+        }
+    }
+    quote! { #root::scheduled::graph::Hydroflow::new() }
+}
@@ -0,0 +1,236 @@
+//! Reusable modules of surface syntax, defined with `hydroflow_module!` and used inside
+//! `hydroflow_syntax!` as `my_module!(args)`.
+//!
+//! A proc macro cannot see the definition of another macro, so modules are expanded through a
+//! callback: `hydroflow_module!` generates a `macro_rules!` macro, and `hydroflow_syntax!` replaces
+//! the first module call with a fresh instance name and passes its whole input to that macro. The
+//! macro prepends the module's body as `instance = mod { ... };`, after the graph's inner
+//! attributes, and calls `hydroflow_syntax!` again, which repeats until no module calls are left.
+
+use proc_macro2::{Delimiter, Group, Ident, Punct, Spacing, Span, TokenStream, TokenTree};
+use quote::quote;
+
+use crate::parse::HfModule;
+
+/// Prefix of the generated names for module instances.
+const INSTANCE_PREFIX: &str = "__hydroflow_module_";
+
+/// Generates the `macro_rules!` macro for a module definition.
+pub fn module_macro_rules(module: &HfModule) -> TokenStream {
+    let HfModule {
+        attrs,
+        name,
+        params,
+        body,
+        ..
+    } = module;
+    let params: Vec<&Ident> = params.iter().collect();
+    let body = substitute_params(body.clone(), &params);
+    let message = format!(
+        "`{}!` is a hydroflow module, it can only be used inside `hydroflow_syntax!`.",
+        name
+    );
+    quote! {
+        #( #attrs )*
+        macro_rules! #name {
+            ([$($callback:tt)*] [$($attrs:tt)*] $instance:ident ( #( $#params:expr ),* $(,)? ) { $($rest:tt)* }) => {
+                $($callback)*! {
+                    $($attrs)*
+                    $instance = mod { #body };
+                    $($rest)*
+                }
+            };
+            ($($tokens:tt)*) => {
+                ::std::compile_error!(#message)
+            };
+        }
+    }
+}
+
+/// Replaces uses of the module's parameters with `macro_rules!` metavariables.
+///
+/// A closure parameter with the same name as a module parameter shadows it: the closure's
+/// parameter list and its body, up to the next `,` or `;`, are left unchanged.
+fn substitute_params(tokens: TokenStream, params: &[&Ident]) -> TokenStream {
+    let tokens: Vec<TokenTree> = tokens.into_iter().collect();
+    let mut output: Vec<TokenTree> = Vec::new();
+    // The parameters which are not shadowed.
+    let mut active: Vec<&Ident> = params.to_vec();
+    let mut idx = 0;
+    while idx < tokens.len() {
+        match &tokens[idx] {
+            TokenTree::Group(group) => {
+                let mut new_group = Group::new(
+                    group.delimiter(),
+                    substitute_params(group.stream(), &active),
+                );
+                new_group.set_span(group.span());
+                output.push(new_group.into());
+            }
+            TokenTree::Ident(ident) if active.contains(&ident) => {
+                output.push(Punct::new('$', Spacing::Alone).into());
+                output.push(ident.clone().into());
+            }
+            TokenTree::Punct(punct) if matches!(punct.as_char(), ',' | ';') => {
+                active = params.to_vec();
+                output.push(punct.clone().into());
+            }
+            TokenTree::Punct(punct) if '|' == punct.as_char() && starts_expr(output.last()) => {
+                // A closure, copy its parameter list unchanged.
+                let end = tokens[idx + 1..]
+                    .iter()
+                    .position(
+                        |token| matches!(token, TokenTree::Punct(punct) if '|' == punct.as_char()),
+                    )
+                    .map_or(tokens.len(), |len| idx + 2 + len);
+                let closure_params = &tokens[idx..end];
+                active.retain(|param| !contains_ident(closure_params, param));
+                output.extend(closure_params.iter().cloned());
+                idx = end;
+                continue;
+            }
+            token => output.push(token.clone()),
+        }
+        idx += 1;
+    }
+    output.into_iter().collect()
+}
+
+/// Helper: if an expression may start after the `prev` token, i.e. if a `|` after it starts a
+/// closure rather than being a binary operator.
+fn starts_expr(prev: Option<&TokenTree>) -> bool {
+    match prev {
+        None => true,
+        Some(TokenTree::Punct(punct)) => matches!(punct.as_char(), ',' | ';' | '='),
+        Some(TokenTree::Ident(ident)) => ident == "move" || ident == "return",
+        Some(_) => false,
+    }
+}
+
+/// Helper: if `tokens` contain `ident`, including inside groups.
+fn contains_ident(tokens: &[TokenTree], ident: &Ident) -> bool {
+    tokens.iter().any(|token| match token {
+        TokenTree::Group(group) => {
+            contains_ident(&group.stream().into_iter().collect::<Vec<_>>(), ident)
+        }
+        TokenTree::Ident(token_ident) => token_ident == ident,
+        _ => false,
+    })
+}
+
+/// Replaces the first module call in `input` with a fresh instance name, and returns the call to
+/// the module's macro which expands it, calling `callback` with the result. Module calls are found
+/// in statements and in module bodies, but not inside parentheses.
+///
+/// Returns `None` if there are no module calls.
+pub fn expand_module_call(input: TokenStream, callback: TokenStream) -> Option<TokenStream> {
+    let instance = Ident::new(
+        &format!("{}{}", INSTANCE_PREFIX, next_instance_idx(input.clone())),
+        Span::call_site(),
+    );
+    let (rest, path, args) = replace_module_call(input, &instance)?;
+    let (attrs, rest) = split_inner_attrs(rest);
+    Some(quote! {
+        #path! { [#callback] [#attrs] #instance (#args) { #rest } }
+    })
+}
+
+/// Helper: splits the leading inner attributes, `#![...]`, off of `tokens`, so they stay at the
+/// top when a module body is prepended.
+fn split_inner_attrs(tokens: TokenStream) -> (TokenStream, TokenStream) {
+    let tokens: Vec<TokenTree> = tokens.into_iter().collect();
+    let mut len = 0;
+    while let [TokenTree::Punct(pound), TokenTree::Punct(bang), TokenTree::Group(group), ..] =
+        &tokens[len..]
+    {
+        if '#' != pound.as_char()
+            || '!' != bang.as_char()
+            || Delimiter::Bracket != group.delimiter()
+        {
+            break;
+        }
+        len += 3;
+    }
+    let mut tokens = tokens.into_iter();
+    let attrs = tokens.by_ref().take(len).collect();
+    (attrs, tokens.collect())
+}
+
+/// Helper: replaces the first module call, returning the new tokens, the module's path, and the
+/// call's arguments.
+fn replace_module_call(
+    tokens: TokenStream,
+    instance: &Ident,
+) -> Option<(TokenStream, TokenStream, TokenStream)> {
+    let mut tokens: Vec<TokenTree> = tokens.into_iter().collect();
+    for idx in 0..tokens.len() {
+        if let Some(len) = macro_call_len(&tokens[idx..]) {
+            let mut call: Vec<TokenTree> = tokens
+                .splice(idx..idx + len, [instance.clone().into()])
+                .collect();
+            let Some(TokenTree::Group(args)) = call.pop() else {
+                unreachable!();
+            };
+            call.pop(); // `!`
+            return Some((
+                tokens.into_iter().collect(),
+                call.into_iter().collect(),
+                args.stream(),
+            ));
+        }
+
+        // Recurse into module bodies, `mod { ... }`.
+        if let (TokenTree::Ident(ident), Some(TokenTree::Group(group))) =
+            (&tokens[idx], tokens.get(idx + 1))
+        {
+            if ident == "mod" && Delimiter::Brace == group.delimiter() {
+                if let Some((stream, path, args)) = replace_module_call(group.stream(), instance) {
+                    let mut new_group = Group::new(Delimiter::Brace, stream);
+                    new_group.set_span(group.span());
+                    tokens[idx + 1] = new_group.into();
+                    return Some((tokens.into_iter().collect(), path, args));
+                }
+            }
+        }
+    }
+    None
+}
+
+/// Helper: if `tokens` starts with a macro call, `path::to::name!(...)`, returns its length.
+fn macro_call_len(tokens: &[TokenTree]) -> Option<usize> {
+    let mut len = 0;
+    loop {
+        let TokenTree::Ident(_) = tokens.get(len)? else {
+            return None;
+        };
+        len += 1;
+        match (tokens.get(len)?, tokens.get(len + 1)) {
+            (TokenTree::Punct(bang), Some(TokenTree::Group(_))) if '!' == bang.as_char() => {
+                return Some(len + 2);
+            }
+            (TokenTree::Punct(colon_a), Some(TokenTree::Punct(colon_b)))
+                if ':' == colon_a.as_char() && ':' == colon_b.as_char() =>
+            {
+                len += 2;
+            }
+            _ => return None,
+        }
+    }
+}
+
+/// Helper: returns an index not used by any existing instance name.
+fn next_instance_idx(tokens: TokenStream) -> usize {
+    tokens
+        .into_iter()
+        .map(|token| match token {
+            TokenTree::Group(group) => next_instance_idx(group.stream()),
+            TokenTree::Ident(ident) => ident
+                .to_string()
+                .strip_prefix(INSTANCE_PREFIX)
+                .and_then(|idx| idx.parse::<usize>().ok())
+                .map_or(0, |idx| idx + 1),
+            _ => 0,
+        })
+        .max()
+        .unwrap_or(0)
+}
@@ -1,26 +1,64 @@
 use std::hash::Hash;
 
 use proc_macro2::{Span, TokenStream};
-use quote::ToTokens;
+use quote::{ToTokens, TokenStreamExt};
 use syn::parse::{Parse, ParseStream};
 use syn::punctuated::Punctuated;
-use syn::token::{Bracket, Paren};
+use syn::token::{Brace, Bracket, Paren};
 use syn::{
-    bracketed, parenthesized, Expr, ExprPath, GenericArgument, Ident, LitInt, Path, PathArguments,
-    PathSegment, Token,
+    braced, bracketed, parenthesized, Attribute, Expr, ExprPath, GenericArgument, Ident, LitInt,
+    Path, PathArguments, PathSegment, Token, Type,
 };
 
 pub struct HfCode {
+    /// Inner attributes, `#![optimize]` and `#![allow(lint, ...)]` are supported.
+    pub attrs: Vec<Attribute>,
     pub statements: Punctuated<HfStatement, Token![;]>,
 }
+impl HfCode {
+    /// If the graph should be optimized, with `#![optimize]`.
+    pub fn optimize(&self) -> bool {
+        self.attrs.iter().any(|attr| attr.path.is_ident("optimize"))
+    }
+
+    /// The names of the lints allowed with `#![allow(lint, ...)]`.
+    pub fn allowed_lints(&self) -> Vec<Ident> {
+        self.attrs
+            .iter()
+            .filter(|attr| attr.path.is_ident("allow"))
+            .flat_map(|attr| {
+                attr.parse_args_with(Punctuated::<Ident, Token![,]>::parse_terminated)
+                    .unwrap_or_default()
+            })
+            .collect()
+    }
+}
 impl Parse for HfCode {
     fn parse(input: ParseStream) -> syn::Result<Self> {
+        let attrs = input.call(Attribute::parse_inner)?;
+        for attr in attrs.iter() {
+            let valid = if attr.path.is_ident("optimize") {
+                attr.tokens.is_empty()
+            } else if attr.path.is_ident("allow") {
+                attr.parse_args_with(Punctuated::<Ident, Token![,]>::parse_terminated)
+                    .is_ok()
+            } else {
+                false
+            };
+            if !valid {
+                return Err(syn::Error::new_spanned(
+                    attr,
+                    "Unknown attribute, expected `#![optimize]` or `#![allow(lint, ...)]`.",
+                ));
+            }
+        }
         let statements = input.parse_terminated(HfStatement::parse)?;
-        Ok(HfCode { statements })
+        Ok(HfCode { attrs, statements })
     }
 }
 impl ToTokens for HfCode {
     fn to_tokens(&self, tokens: &mut TokenStream) {
+        tokens.append_all(&self.attrs);
         self.statements.to_tokens(tokens)
     }
 }
@@ -77,6 +115,8 @@ pub enum Pipeline {
     Link(PipelineLink),
     Name(Ident),
     Operator(Operator),
+    Module(PipelineModule),
+    Type(PipelineType),
 }
 impl Pipeline {
     fn parse_helper(input: ParseStream) -> syn::Result<Self> {
@@ -97,6 +137,10 @@ impl Pipeline {
     fn parse_one(input: ParseStream) -> syn::Result<Self> {
         if input.peek(Paren) {
             Ok(Self::Paren(input.parse()?))
+        } else if input.peek(Token![mod]) {
+            Ok(Self::Module(input.parse()?))
+        } else if input.peek(Token![:]) && !input.peek(Token![::]) {
+            Ok(Self::Type(input.parse()?))
         } else if input.peek2(Paren) || input.peek2(Token![<]) || input.peek2(Token![::]) {
             Ok(Self::Operator(input.parse()?))
         } else {
@@ -116,10 +160,31 @@ impl ToTokens for Pipeline {
             Pipeline::Link(x) => x.to_tokens(tokens),
             Pipeline::Name(x) => x.to_tokens(tokens),
             Pipeline::Operator(x) => x.to_tokens(tokens),
+            Pipeline::Module(x) => x.to_tokens(tokens),
+            Pipeline::Type(x) => x.to_tokens(tokens),
         }
     }
 }
 
+/// A type annotation on an edge, `a -> :Type -> b`.
+pub struct PipelineType {
+    pub colon_token: Token![:],
+    pub ty: Type,
+}
+impl Parse for PipelineType {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let colon_token = input.parse()?;
+        let ty = input.parse()?;
+        Ok(Self { colon_token, ty })
+    }
+}
+impl ToTokens for PipelineType {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        self.colon_token.to_tokens(tokens);
+        self.ty.to_tokens(tokens);
+    }
+}
+
 pub struct PipelineParen {
     pub paren_token: Paren,
     pub pipeline: Box<Pipeline>,
@@ -143,6 +208,36 @@ impl ToTokens for PipelineParen {
     }
 }
 
+/// An inline module, `mod { ... }`. Names assigned inside the module are scoped to it, and are
+/// used as the module's ports: `[name]my_module` and `my_module[name]`. The `input` and `output`
+/// names are the module's default ports.
+pub struct PipelineModule {
+    pub mod_token: Token![mod],
+    pub brace_token: Brace,
+    pub statements: Punctuated<HfStatement, Token![;]>,
+}
+impl Parse for PipelineModule {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let mod_token = input.parse()?;
+        let content;
+        let brace_token = braced!(content in input);
+        let statements = content.parse_terminated(HfStatement::parse)?;
+        Ok(Self {
+            mod_token,
+            brace_token,
+            statements,
+        })
+    }
+}
+impl ToTokens for PipelineModule {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        self.mod_token.to_tokens(tokens);
+        self.brace_token.surround(tokens, |tokens| {
+            self.statements.to_tokens(tokens);
+        });
+    }
+}
+
 pub struct PipelineLink {
     pub lhs: Box<Pipeline>,
     pub connector: ArrowConnector,
@@ -244,12 +339,77 @@ impl ToTokens for PortIndex {
     }
 }
 
+/// A reusable module definition, the input of `hydroflow_module!`:
+/// `name(param, ...) { statements }`.
+pub struct HfModule {
+    pub attrs: Vec<Attribute>,
+    pub name: Ident,
+    pub paren_token: Paren,
+    pub params: Punctuated<Ident, Token![,]>,
+    pub brace_token: Brace,
+    /// The statements, left unparsed as they may use other modules which are not expanded yet.
+    pub body: TokenStream,
+}
+impl Parse for HfModule {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let attrs = input.call(Attribute::parse_outer)?;
+        let name = input.parse()?;
+
+        let params;
+        let paren_token = parenthesized!(params in input);
+        let params = Punctuated::parse_terminated(&params)?;
+
+        let body;
+        let brace_token = braced!(body in input);
+        let body = body.parse()?;
+
+        Ok(Self {
+            attrs,
+            name,
+            paren_token,
+            params,
+            brace_token,
+            body,
+        })
+    }
+}
+impl ToTokens for HfModule {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        tokens.append_all(&self.attrs);
+        self.name.to_tokens(tokens);
+        self.paren_token.surround(tokens, |tokens| {
+            self.params.to_tokens(tokens);
+        });
+        self.brace_token.surround(tokens, |tokens| {
+            self.body.to_tokens(tokens);
+        });
+    }
+}
+
+#[derive(Clone)]
 pub struct Operator {
     pub path: Path,
     pub paren_token: Paren,
     pub args: Punctuated<Expr, Token![,]>,
 }
 impl Operator {
+    /// Makes the operator's name and parentheses resolve names at the call site, keeping their
+    /// location.
+    pub fn resolve_at_call_site(&mut self) {
+        let call_site = Span::call_site();
+        if let Some(leading_colon) = &mut self.path.leading_colon {
+            for span in &mut leading_colon.spans {
+                *span = span.resolved_at(call_site);
+            }
+        }
+        for segment in self.path.segments.iter_mut() {
+            segment
+                .ident
+                .set_span(segment.ident.span().resolved_at(call_site));
+        }
+        self.paren_token.span = self.paren_token.span.resolved_at(call_site);
+    }
+
     pub fn name(&self) -> Path {
         Path {
             leading_colon: self.path.leading_colon,
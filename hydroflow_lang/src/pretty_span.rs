@@ -3,6 +3,10 @@
 pub struct PrettySpan(pub proc_macro2::Span);
 impl std::fmt::Display for PrettySpan {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if !proc_macro::is_available() {
+            // Outside of a proc macro, e.g. in the CLI, there is no source file.
+            return PrettyRowCol(self.0).fmt(f);
+        }
         let span = self.0.unwrap();
         write!(
             f,
@@ -18,6 +22,16 @@ impl std::fmt::Display for PrettySpan {
 pub struct PrettyRowCol(pub proc_macro2::Span);
 impl std::fmt::Display for PrettyRowCol {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if !proc_macro::is_available() {
+            // Line and column info is only available with the `span-locations` feature.
+            #[cfg(feature = "span-locations")]
+            {
+                let start = self.0.start();
+                return write!(f, "{}:{}", start.line, start.column);
+            }
+            #[cfg(not(feature = "span-locations"))]
+            return write!(f, "?:?");
+        }
         let span = self.0.unwrap();
         write!(f, "{}:{}", span.start().line, span.start().column)
     }
@@ -11,7 +11,6 @@ impl<K> UnionFind<K>
 where
     K: Key,
 {
-    #[allow(dead_code)]
     pub fn new() -> Self {
         Self::default()
     }
@@ -4,6 +4,7 @@ use std::env::VarError;
 use std::error::Error;
 use std::fs::File;
 use std::io::{BufReader, BufWriter, Write};
+use std::ops::Bound;
 use std::path::{Path, PathBuf};
 
 use quote::ToTokens;
@@ -64,7 +65,11 @@ fn main() -> Result<(), Box<dyn Error>> {
             },
             op.name,
             ('A'..)
-                .take(op.num_args)
+                .take(match op.num_args.end_bound() {
+                    Bound::Included(&n) => n,
+                    Bound::Excluded(&n) => n.saturating_sub(1),
+                    Bound::Unbounded => 0,
+                })
                 .map(|c| format!("{}, ", c))
                 .collect::<String>()
                 .strip_suffix(", ")
@@ -154,4 +159,9 @@ const PREFIX: &str = "\
 
 In our previous examples we made use of some of Hydroflow's built-in operators.
 Here we document each operators in more detail. Most of these operators
-are based on the Rust equivalents for iterators; see the [Rust documentation](https://doc.rust-lang.org/std/iter/trait.Iterator.html).";
+are based on the Rust equivalents for iterators; see the [Rust documentation](https://doc.rust-lang.org/std/iter/trait.Iterator.html).
+
+Other crates can define their own operators as `OperatorConstraints`, like the built-in operators
+in `hydroflow_lang::graph::ops`, including their port ranges, delay types, persistence lifetimes
+(with `parse_persistence_lifetimes`) and code generation. To use them, the crate defines its own
+syntax proc macro which calls `hydroflow_lang::hydroflow_syntax_with_operators`.";
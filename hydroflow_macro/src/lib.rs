@@ -6,48 +6,81 @@ use quote::quote;
 use syn::{parse_macro_input, LitStr};
 
 use hydroflow_lang::graph::flat_graph::FlatGraph;
-use hydroflow_lang::parse::HfCode;
+use hydroflow_lang::hydroflow_syntax_with_operators;
+use hydroflow_lang::module::{expand_module_call, module_macro_rules};
+use hydroflow_lang::parse::{HfCode, HfModule};
 
-#[proc_macro]
-pub fn hydroflow_syntax(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+fn root() -> proc_macro2::TokenStream {
     let hydroflow_crate = proc_macro_crate::crate_name("hydroflow")
         .expect("hydroflow should be present in `Cargo.toml`");
-    let root = match hydroflow_crate {
+    match hydroflow_crate {
         proc_macro_crate::FoundCrate::Itself => quote! { hydroflow },
         proc_macro_crate::FoundCrate::Name(name) => {
             let ident = Ident::new(&name, Span::call_site());
             quote! { #ident }
         }
-    };
-
-    let input = parse_macro_input!(input as HfCode);
-
-    let flat_graph = FlatGraph::from_hfcode(input);
-    if !flat_graph.emit_diagnostics() {
-        match flat_graph.into_partitioned_graph() {
-            Ok(part_graph) => return part_graph.as_code(root, true).into(),
-            Err(diagnostic) => diagnostic.emit(),
-        }
     }
-    quote! { #root::scheduled::graph::Hydroflow::new() }.into()
+}
+
+#[proc_macro]
+pub fn hydroflow_syntax(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let root = root();
+    let callback = quote! { #root::hydroflow_syntax };
+    hydroflow_syntax_with_operators(input.into(), root, callback, &[]).into()
 }
 
 #[proc_macro]
 pub fn hydroflow_parser(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let root = root();
+
+    if let Some(expanded) =
+        expand_module_call(input.clone().into(), quote! { #root::hydroflow_parser })
+    {
+        return expanded.into();
+    }
     let input = parse_macro_input!(input as HfCode);
 
     let flat_graph = FlatGraph::from_hfcode(input);
     flat_graph.emit_diagnostics();
 
     let flat_mermaid = flat_graph.mermaid_string();
+    let optimization_log = flat_graph.optimization_log().join("\n");
 
     let part_graph = flat_graph.into_partitioned_graph().unwrap();
     let part_mermaid = part_graph.to_serde_graph().to_mermaid();
 
     let lit0 = Literal::string(&*flat_mermaid);
     let lit1 = Literal::string(&*part_mermaid);
+    let lit2 = Literal::string(&*optimization_log);
+
+    quote! { println!("{}\n\n{}\n\n{}\n", #lit0, #lit1, #lit2); }.into()
+}
 
-    quote! { println!("{}\n\n{}\n", #lit0, #lit1); }.into()
+/// Defines a reusable module of surface syntax, which can be used inside `hydroflow_syntax!` as a
+/// macro call with the module's arguments:
+///
+/// ```ignore
+/// hydroflow_module! {
+///     dedupe_then_map(f) {
+///         input = unique();
+///         output = map(f);
+///         input -> output;
+///     }
+/// }
+///
+/// let mut df = hydroflow_syntax! {
+///     source_iter([1, 1, 2]) -> dedupe_then_map!(|x| x * 10) -> for_each(|x| println!("{}", x));
+/// };
+/// ```
+///
+/// Names assigned inside the module do not conflict with the graph's names. They are the module's
+/// ports, `[name]my_instance` and `my_instance[name]`, and `input` and `output` are the default
+/// ports. This generates a `macro_rules!` macro, so add `#[macro_export]` to use it from other
+/// crates.
+#[proc_macro]
+pub fn hydroflow_module(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let module = parse_macro_input!(input as HfModule);
+    module_macro_rules(&module).into()
 }
 
 #[doc(hidden)]